@@ -3,13 +3,19 @@ pub const BIOS_SIZE: usize = 512 * 1024;
 pub const HARDWARE_REGISTER_START: usize = 0x1f801000;
 pub const RAM_SIZE_START: usize = 0x1f801060;
 pub const RAM_SIZE: usize = 2 * 1024 * 1024;
+/// Part of the address space RAM_SIZE maps RAM into
+pub const RAM_WINDOW_SIZE: usize = 8 * 1024 * 1024;
 pub const RAM_START: usize = 0x00000000;
+pub const SCRATCHPAD_START: usize = 0x1f800000;
+pub const SCRATCHPAD_SIZE: usize = 1024;
 pub const CACHE_CONTROL_START: usize = 0xfffe0130;
 pub const SYS_CONTROL_START: usize = 0x1f801000;
 pub const SPU_START: usize = 0x1f801c00;
 pub const EXPANSION_1_START: usize = 0x1f000000;
+pub const EXPANSION_1_SIZE: usize = 8 * 1024 * 1024;
 pub const EXPANSION_2_START: usize = 0x1f802000;
 pub const IRQ_START: usize = 0x1f801070;
 pub const TIMER_REGISTER_START: usize = 0x1f801100;
 pub const DMA_START: usize = 0x1f801080;
 pub const GPU_START: usize = 0x1f801810;
+pub const CDROM_START: usize = 0x1f801800;
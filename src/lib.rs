@@ -0,0 +1,6 @@
+// Every component is built through an explicit `new()`, like the hardware
+// it models there is no meaningful "default" instance
+#![allow(clippy::new_without_default)]
+
+pub mod consts;
+pub mod libs;
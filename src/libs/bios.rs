@@ -1,9 +1,12 @@
 use crate::consts;
+use crate::libs::hle;
 use std::fs::File;
 use std::io::Read;
 
 pub struct Bios {
     data: Vec<u8>,
+    /// The kernel is emulated instead of running from the ROM
+    hle: bool,
 }
 
 impl Bios {
@@ -11,9 +14,31 @@ impl Bios {
         let mut f = File::open(filename).expect("file not found");
         let mut buffer = vec![0; consts::BIOS_SIZE];
 
-        f.read(&mut buffer).expect("file couldn't be read");
+        f.read_exact(&mut buffer).expect("file couldn't be read");
 
-        Self { data: buffer }
+        Self {
+            data: buffer,
+            hle: false,
+        }
+    }
+
+    /// BIOS image from memory, padded to the size of the ROM
+    pub fn from_bytes(mut data: Vec<u8>) -> Self {
+        data.resize(consts::BIOS_SIZE, 0);
+
+        Self { data, hle: false }
+    }
+
+    /// High level emulated BIOS, no ROM dump needed
+    pub fn hle() -> Self {
+        Self {
+            data: hle::rom(),
+            hle: true,
+        }
+    }
+
+    pub fn is_hle(&self) -> bool {
+        self.hle
     }
 
     pub fn load32(&self, addr: usize) -> u32 {
@@ -1,21 +1,275 @@
+use crate::consts;
 use crate::libs::bios::Bios;
+use crate::libs::cartridge::Cartridge;
+use crate::libs::cdrom::CdRom;
 use crate::libs::channel::{Direction, Step, Sync};
+use crate::libs::disc::Disc;
 use crate::libs::dma::{Dma, Port};
+use crate::libs::error::BusError;
+use crate::libs::exe::Exe;
+use crate::libs::gpu::Gpu;
+use crate::libs::irq::{Interrupt, Irq};
 use crate::libs::map::memory;
 use crate::libs::ram::Ram;
+use crate::libs::scratchpad::Scratchpad;
+use crate::libs::timers::Timers;
+use crate::libs::tty::{self, Tty};
+
+/// What the RAM_SIZE register maps at an offset of the 8MB RAM window
+enum Window {
+    Ram(usize),
+    /// Nothing drives the bus, reads return open bus values
+    HighZ,
+    /// Accesses raise bus errors
+    Locked,
+}
 
 pub struct Bus {
     bios: Bios,
     ram: Ram,
+    scratchpad: Scratchpad,
     dma: Dma,
+    gpu: Gpu,
+    cdrom: CdRom,
+    /// ROM in Expansion 1, if any
+    cartridge: Option<Cartridge>,
+    irq: Irq,
+    timers: Timers,
+    tty: Tty,
+    /// CPU cycles elapsed since reset
+    cycles: u64,
+    cache_control: u32,
+    /// Expansion, BIOS and device delay registers at 0x1f801000
+    mem_control: [u32; 9],
+    ram_size: u32,
 }
 
 impl Bus {
     pub fn new(bios: Bios, ram: Ram) -> Self {
         Self {
-            bios: bios,
-            ram: ram,
+            bios,
+            ram,
+            scratchpad: Scratchpad::new(),
             dma: Dma::new(),
+            gpu: Gpu::new(),
+            cdrom: CdRom::new(),
+            cartridge: None,
+            irq: Irq::new(),
+            timers: Timers::new(),
+            tty: Tty::new(),
+            cycles: 0,
+            cache_control: 0,
+            mem_control: [0; 9],
+            // 8MB window, as set up by the BIOS
+            ram_size: 0xb88,
+        }
+    }
+
+    /// Advance the peripherals by `cycles` CPU clock cycles
+    pub fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+
+        self.timers.tick(cycles, &mut self.irq);
+        self.gpu.tick(cycles, &mut self.irq, &mut self.timers);
+        self.cdrom.tick(cycles, &mut self.irq);
+    }
+
+    /// Copy the text section of `exe` to RAM and clear its BSS
+    pub fn load_exe(&mut self, exe: &Exe) -> Result<(), BusError> {
+        let bss = (0..exe.bss_size).map(|i| (exe.bss_addr.wrapping_add(i), 0));
+        let text = exe
+            .text
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| (exe.text_addr.wrapping_add(i as u32), b));
+
+        for (addr, val) in text.chain(bss) {
+            match self.ram_offset(addr as usize) {
+                Some(offset) => self.ram.store8(offset, val),
+                None => {
+                    return Err(BusError::Unmapped {
+                        addr,
+                        width: 1,
+                        val: Some(val as u32),
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn tty(&self) -> &Tty {
+        &self.tty
+    }
+
+    pub fn tty_mut(&mut self) -> &mut Tty {
+        &mut self.tty
+    }
+
+    pub fn insert_disc(&mut self, disc: Disc) {
+        self.cdrom.insert_disc(disc);
+    }
+
+    pub fn disc(&self) -> Option<&Disc> {
+        self.cdrom.disc()
+    }
+
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge) {
+        self.cartridge = Some(cartridge);
+    }
+
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.cartridge.as_ref()
+    }
+
+    pub fn bios(&self) -> &Bios {
+        &self.bios
+    }
+
+    pub fn ram(&self) -> &Ram {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut Ram {
+        &mut self.ram
+    }
+
+    /// Read memory without side effects, `None` outside of RAM, the
+    /// scratchpad and the BIOS
+    pub fn peek8(&self, addr: usize) -> Option<u8> {
+        if let Some(offset) = self.ram_offset(addr) {
+            Some(self.ram.load8(offset))
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            Some(self.scratchpad.load8(offset))
+        } else if let Some(offset) = memory::EXPANSION_1.contains(addr) {
+            Some(self.cartridge.as_ref().map_or(0xff, |c| c.load8(offset)))
+        } else {
+            memory::BIOS
+                .contains(addr)
+                .map(|offset| self.bios.load8(offset))
+        }
+    }
+
+    /// Write memory without side effects, only RAM and the scratchpad are
+    /// writable
+    pub fn poke8(&mut self, addr: usize, val: u8) -> bool {
+        if let Some(offset) = self.ram_offset(addr) {
+            self.ram.store8(offset, val);
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            self.scratchpad.store8(offset, val);
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    pub fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+
+    /// Offset in RAM of `addr`, `None` outside of the memory part of the
+    /// RAM window
+    pub fn ram_offset(&self, addr: usize) -> Option<usize> {
+        match memory::RAM
+            .contains(addr)
+            .map(|offset| self.ram_window(offset))
+        {
+            Some(Window::Ram(offset)) => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// The 2MB of RAM are mirrored across the memory part of the window
+    fn ram_window(&self, offset: usize) -> Window {
+        const MB: usize = 1024 * 1024;
+
+        let (memory, high_z) = match (self.ram_size >> 9) & 7 {
+            0 => (MB, 0),
+            1 => (4 * MB, 0),
+            2 => (MB, MB),
+            3 => (4 * MB, 4 * MB),
+            4 => (2 * MB, 0),
+            6 => (2 * MB, 2 * MB),
+            _ => (8 * MB, 0),
+        };
+
+        if offset < memory {
+            Window::Ram(offset % consts::RAM_SIZE)
+        } else if offset < memory + high_z {
+            Window::HighZ
+        } else {
+            Window::Locked
+        }
+    }
+
+    pub fn ram_size(&self) -> u32 {
+        self.ram_size
+    }
+
+    pub fn cache_control(&self) -> u32 {
+        self.cache_control
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Approximate number of CPU cycles taken by a 32-bit access to `addr`
+    pub fn access_time(addr: usize) -> u32 {
+        if memory::RAM.contains(addr).is_some() {
+            5
+        } else if memory::SCRATCHPAD.contains_cached(addr).is_some() {
+            1
+        } else if memory::BIOS.contains(addr).is_some() {
+            // 8-bit wide ROM, four accesses per word
+            24
+        } else {
+            3
+        }
+    }
+
+    /// State of the interrupt line going to the CPU
+    pub fn irq_active(&self) -> bool {
+        self.irq.active()
+    }
+
+    pub fn irq_mut(&mut self) -> &mut Irq {
+        &mut self.irq
+    }
+
+    /// Error for an access to the locked part of the RAM window
+    fn locked(addr: usize, width: u32, val: Option<u32>) -> BusError {
+        let addr = addr as u32;
+
+        BusError::Unmapped { addr, width, val }
+    }
+
+    /// Error for an access no device handled
+    fn fault(addr: usize, width: u32, val: Option<u32>) -> BusError {
+        let mapped = memory::is_mapped(addr);
+        let addr = addr as u32;
+
+        match mapped {
+            true => BusError::UnhandledRegister { addr, width, val },
+            false => BusError::Unmapped { addr, width, val },
+        }
+    }
+
+    fn irq_reg(&self, offset: usize) -> u32 {
+        match offset {
+            0 => self.irq.status() as u32,
+            4 => self.irq.mask() as u32,
+            _ => 0,
+        }
+    }
+
+    fn set_irq_reg(&mut self, offset: usize, val: u32) {
+        match offset {
+            0 => self.irq.ack(val as u16),
+            4 => self.irq.set_mask(val as u16),
+            _ => (),
         }
     }
 
@@ -23,13 +277,12 @@ impl Bus {
         ((offset & 0x70) >> 4, offset & 0xf)
     }
 
-    fn dma_reg(&self, offset: usize) -> Result<u32, String> {
+    fn dma_reg(&self, addr: usize, offset: usize) -> Result<u32, BusError> {
         let (major, minor) = Self::get_major_minor(offset as u32);
-        let error = || {
-            format!(
-                "Unhandled DMA read at offset {:08x}, major: {}, minor: {}",
-                offset, major, minor
-            )
+        let error = || BusError::UnhandledRegister {
+            addr: addr as u32,
+            width: 4,
+            val: None,
         };
 
         match major {
@@ -50,13 +303,12 @@ impl Bus {
         }
     }
 
-    fn set_dma_reg(&mut self, offset: usize, val: u32) -> Result<(), String> {
+    fn set_dma_reg(&mut self, addr: usize, offset: usize, val: u32) -> Result<(), BusError> {
         let (major, minor) = Self::get_major_minor(offset as u32);
-        let error = || {
-            format!(
-                "Unhandled DMA write at offset {:08x} with val {:08x}, major: {}, minor: {}",
-                offset, val, major, minor
-            )
+        let error = || BusError::UnhandledRegister {
+            addr: addr as u32,
+            width: 4,
+            val: Some(val),
         };
 
         let active_port = match major {
@@ -67,7 +319,12 @@ impl Bus {
                 match minor {
                     0 => channel.set_base(val),
                     4 => channel.set_block_control(val),
-                    8 => channel.set_control(val),
+                    8 => channel
+                        .set_control(val)
+                        .map_err(|reason| BusError::InvalidDma {
+                            port: major as u8,
+                            reason,
+                        })?,
                     _ => return Err(error()),
                 }
 
@@ -80,7 +337,13 @@ impl Bus {
             7 => {
                 match minor {
                     0 => self.dma.control = val,
-                    4 => self.dma.set_interrupt(val),
+                    4 => {
+                        let prev = self.dma.irq();
+                        self.dma.set_interrupt(val);
+                        if !prev && self.dma.irq() {
+                            self.irq.assert(Interrupt::Dma);
+                        }
+                    }
                     _ => return Err(error()),
                 };
                 None
@@ -88,29 +351,40 @@ impl Bus {
             _ => return Err(error()),
         };
         if let Some(port) = active_port {
-            self.do_dma(port);
+            self.do_dma(port)?;
         }
         Ok(())
     }
 
-    fn do_dma(&mut self, port: Port) {
+    fn do_dma(&mut self, port: Port) -> Result<(), BusError> {
         match self.dma.channel(port).sync {
-            Sync::LinkedList => self.do_dma_linked_list(port),
-            _ => self.do_dma_block(port),
+            Sync::LinkedList => self.do_dma_linked_list(port)?,
+            _ => self.do_dma_block(port)?,
         };
+
+        if self.dma.transfer_done(port) {
+            self.irq.assert(Interrupt::Dma);
+        }
+
+        Ok(())
     }
 
-    fn do_dma_linked_list(&mut self, port: Port) {
+    fn do_dma_linked_list(&mut self, port: Port) -> Result<(), BusError> {
         let channel = self.dma.channel_mut(port);
 
         let mut addr = channel.base() & 0x1ffffc;
 
+        let invalid = |reason| BusError::InvalidDma {
+            port: port as u8,
+            reason,
+        };
+
         if channel.direction == Direction::ToRam {
-            panic!("Invalid DMA direction for linked list mode");
+            return Err(invalid("linked list transfer to RAM"));
         }
 
         if port != Port::Gpu {
-            panic!("Attempted linked list DMA on port {}", port as u8);
+            return Err(invalid("linked list transfer outside of the GPU port"));
         }
 
         loop {
@@ -119,7 +393,7 @@ impl Bus {
             while remsz > 0 {
                 addr = (addr.wrapping_add(4)) & 0x1ffffc;
                 let command = self.ram.load32(addr as usize);
-                println!("GPU command {:08x}", command);
+                self.gpu.gp0(command);
                 remsz -= 1;
             }
             if header & 0x800000 != 0 {
@@ -129,11 +403,18 @@ impl Bus {
             addr = header & 0x1ffffc;
         }
         channel.done();
+
+        Ok(())
     }
 
-    fn do_dma_block(&mut self, port: Port) {
+    fn do_dma_block(&mut self, port: Port) -> Result<(), BusError> {
         let channel = self.dma.channel_mut(port);
 
+        let invalid = |reason| BusError::InvalidDma {
+            port: port as u8,
+            reason,
+        };
+
         let step = match channel.step {
             Step::Increment => |addr: u32| addr.wrapping_add(4),
             Step::Decrement => |addr: u32| addr.wrapping_sub(4),
@@ -143,10 +424,11 @@ impl Bus {
 
         let mut remsz = match channel.transfer_size() {
             Some(n) => n,
-            None => panic!("Couldn't figure out DMA block transfer size"),
+            None => return Err(invalid("no block transfer size")),
         };
 
         while remsz > 0 {
+            // DMA only sees RAM, never the scratchpad
             let cur_addr = addr & 0x1ffffc;
 
             match channel.direction {
@@ -154,17 +436,19 @@ impl Bus {
                     let src_word = self.ram.load32(cur_addr as usize);
 
                     match port {
-                        Port::Gpu => println!("GPU data {:08x}", src_word),
-                        _ => panic!("Unhandled DMA destination port {}", port as u8),
+                        Port::Gpu => self.gpu.gp0(src_word),
+                        _ => return Err(invalid("unhandled destination port")),
                     };
                 }
                 Direction::ToRam => {
                     let src_word = match port {
+                        Port::Gpu => self.gpu.read(),
+                        Port::CdRom => self.cdrom.dma_read(),
                         Port::Otc => match remsz {
                             1 => 0xffffff,
                             _ => addr.wrapping_sub(4) & 0x1fffff,
                         },
-                        _ => panic!("Unhandled DMA source port {}", port as u8),
+                        _ => return Err(invalid("unhandled source port")),
                     };
                     self.ram.store32(cur_addr as usize, src_word);
                 }
@@ -173,61 +457,92 @@ impl Bus {
             remsz -= 1;
         }
         channel.done();
+
+        Ok(())
     }
 
-    pub fn load8(&self, addr: usize) -> Result<u8, String> {
+    pub fn load8(&mut self, addr: usize) -> Result<u8, BusError> {
         if let Some(offset) = memory::RAM.contains(addr) {
-            return Ok(self.ram.load8(offset));
+            return match self.ram_window(offset) {
+                Window::Ram(offset) => Ok(self.ram.load8(offset)),
+                Window::HighZ => Ok(0xff),
+                Window::Locked => Err(Self::locked(addr, 1, None)),
+            };
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            return Ok(self.scratchpad.load8(offset));
         } else if let Some(offset) = memory::BIOS.contains(addr) {
             return Ok(self.bios.load8(offset));
         } else if let Some(offset) = memory::EXPANSION_1.contains(addr) {
-            println!("load8 at addr {:08x} EXPANSION_1", addr);
-            return Ok(0xff);
+            return Ok(self.cartridge.as_ref().map_or(0xff, |c| c.load8(offset)));
+        } else if let Some(offset) = memory::CDROM.contains(addr) {
+            return Ok(self.cdrom.load(offset));
+        } else if let Some(tty::DUART_STATUS_A) = memory::EXPANSION_2.contains(addr) {
+            return Ok(tty::DUART_TX_READY);
         }
 
-        Err(format!("unhandled load8 at address {:08x}", addr))
+        Err(Self::fault(addr, 1, None))
     }
 
-    pub fn load16(&self, addr: usize) -> Result<u16, String> {
+    pub fn load16(&mut self, addr: usize) -> Result<u16, BusError> {
         if let Some(offset) = memory::SPU.contains(addr) {
             println!("Unhandled load16 from SPU register {:08x}", offset);
             return Ok(0);
         } else if let Some(offset) = memory::RAM.contains(addr) {
-            return Ok(self.ram.load16(offset));
+            return match self.ram_window(offset) {
+                Window::Ram(offset) => Ok(self.ram.load16(offset)),
+                Window::HighZ => Ok(0xffff),
+                Window::Locked => Err(Self::locked(addr, 2, None)),
+            };
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            return Ok(self.scratchpad.load16(offset));
+        } else if let Some(offset) = memory::EXPANSION_1.contains(addr) {
+            return Ok(self.cartridge.as_ref().map_or(0xffff, |c| c.load16(offset)));
         } else if let Some(offset) = memory::IRQ_CONTROL.contains(addr) {
-            println!("IRQ control load16 at {:04x}", addr);
-            return Ok(0);
+            return Ok(self.irq_reg(offset) as u16);
+        } else if let Some(offset) = memory::TIMERS.contains(addr) {
+            return Ok(self.timers.load(offset) as u16);
         }
 
-        Err(format!("Unhandled load16 at address {:08x}", addr))
+        Err(Self::fault(addr, 2, None))
     }
 
-    pub fn load32(&self, addr: usize) -> Result<u32, String> {
+    pub fn load32(&mut self, addr: usize) -> Result<u32, BusError> {
         if let Some(offset) = memory::RAM.contains(addr) {
-            return Ok(self.ram.load32(offset));
+            return match self.ram_window(offset) {
+                Window::Ram(offset) => Ok(self.ram.load32(offset)),
+                Window::HighZ => Ok(0xffff_ffff),
+                Window::Locked => Err(Self::locked(addr, 4, None)),
+            };
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            return Ok(self.scratchpad.load32(offset));
         } else if let Some(offset) = memory::BIOS.contains(addr) {
             return Ok(self.bios.load32(offset));
+        } else if let Some(offset) = memory::EXPANSION_1.contains(addr) {
+            return Ok(self
+                .cartridge
+                .as_ref()
+                .map_or(0xffff_ffff, |c| c.load32(offset)));
         } else if let Some(offset) = memory::IRQ_CONTROL.contains(addr) {
-            println!("IRQ Control read {:08x}", offset);
-            return Ok(0);
+            return Ok(self.irq_reg(offset));
+        } else if memory::CACHE_CONTROL.contains(addr).is_some() {
+            return Ok(self.cache_control);
+        } else if let Some(offset) = memory::MEM_CONTROL.contains(addr) {
+            return Ok(self.mem_control[offset / 4]);
+        } else if memory::RAM_SIZE.contains(addr).is_some() {
+            return Ok(self.ram_size);
         } else if let Some(offset) = memory::DMA.contains(addr) {
             println!("DMA read at: {:08x}", addr);
-            return self.dma_reg(offset);
+            return self.dma_reg(addr, offset);
         } else if let Some(offset) = memory::GPU.contains(addr) {
-            println!("GPU read at: {:08x}", addr);
-            return match offset {
-                4 => Ok(0x1c00_0000),
-                _ => Ok(0),
-            };
+            return Ok(self.gpu.load(offset));
         } else if let Some(offset) = memory::TIMERS.contains(addr) {
-            println!("TIMER register read at: {:08x}", addr);
-            return Ok(0);
+            return Ok(self.timers.load(offset));
         }
 
-        Err(format!("unhandled_load32_at_address_{:08x}", addr))
+        Err(Self::fault(addr, 4, None))
     }
 
-    pub fn store16(&mut self, addr: usize, val: u16) -> Result<(), String> {
+    pub fn store16(&mut self, addr: usize, val: u16) -> Result<(), BusError> {
         if let Some(offset) = memory::SPU.contains(addr) {
             println!(
                 "Unhandled write16 to SPU register {:x} with val {:04x}",
@@ -235,30 +550,50 @@ impl Bus {
             );
             return Ok(());
         } else if let Some(offset) = memory::TIMERS.contains(addr) {
-            println!(
-                "Unhandled write16 to timer register {:08x} with val {:04x}",
-                offset, val
-            );
+            self.timers.store(offset, val as u32);
             return Ok(());
         } else if let Some(offset) = memory::RAM.contains(addr) {
-            println!("Write of WORD at RAM {:08x} with val: {:04x}", offset, val);
-            self.ram.store16(offset, val);
+            match self.ram_window(offset) {
+                Window::Ram(offset) => {
+                    println!("Write of WORD at RAM {:08x} with val: {:04x}", offset, val);
+                    self.ram.store16(offset, val);
+                }
+                Window::HighZ => (),
+                Window::Locked => return Err(Self::locked(addr, 2, Some(val as u32))),
+            }
+            return Ok(());
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            self.scratchpad.store16(offset, val);
+            return Ok(());
+        } else if memory::EXPANSION_1.contains(addr).is_some() && self.cartridge.is_some() {
+            // The cartridge ROM ignores writes
             return Ok(());
         } else if let Some(offset) = memory::IRQ_CONTROL.contains(addr) {
-            println!("IRQ control store16: {:08x} <- {:04x}", addr, val);
+            self.set_irq_reg(offset, val as u32);
             return Ok(());
         }
 
-        Err(format!(
-            "unhandled store16 at addresss : Ox{:08x}. with val : {:016b} ",
-            addr, val
-        ))
+        Err(Self::fault(addr, 2, Some(val as u32)))
     }
 
-    pub fn store8(&mut self, addr: usize, val: u8) -> Result<(), String> {
+    pub fn store8(&mut self, addr: usize, val: u8) -> Result<(), BusError> {
         if let Some(offset) = memory::RAM.contains(addr) {
-            self.ram.store8(offset, val);
-            println!("Write of BYTE at RAM {:08x} with val: {:08b}", offset, val);
+            match self.ram_window(offset) {
+                Window::Ram(offset) => {
+                    self.ram.store8(offset, val);
+                    println!("Write of BYTE at RAM {:08x} with val: {:08b}", offset, val);
+                }
+                Window::HighZ => (),
+                Window::Locked => return Err(Self::locked(addr, 1, Some(val as u32))),
+            }
+            return Ok(());
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            self.scratchpad.store8(offset, val);
+            return Ok(());
+        } else if memory::EXPANSION_1.contains(addr).is_some() && self.cartridge.is_some() {
+            return Ok(());
+        } else if let Some(tty::DUART_TX_A) = memory::EXPANSION_2.contains(addr) {
+            self.tty.putchar(val);
             return Ok(());
         } else if let Some(offset) = memory::EXPANSION_2.contains(addr) {
             println!(
@@ -266,62 +601,58 @@ impl Bus {
                 val, offset
             );
             return Ok(());
+        } else if let Some(offset) = memory::CDROM.contains(addr) {
+            self.cdrom.store(offset, val, &mut self.irq);
+            return Ok(());
         }
 
-        Err(format!(
-            "unhandled store8 into address {:08x} with val {:08b}",
-            addr, val
-        ))
+        Err(Self::fault(addr, 1, Some(val as u32)))
     }
 
-    pub fn store32(&mut self, addr: usize, val: u32) -> Result<(), String> {
+    pub fn store32(&mut self, addr: usize, val: u32) -> Result<(), BusError> {
         if let Some(offset) = memory::RAM.contains(addr) {
-            self.ram.store32(offset, val);
+            match self.ram_window(offset) {
+                Window::Ram(offset) => self.ram.store32(offset, val),
+                Window::HighZ => (),
+                Window::Locked => return Err(Self::locked(addr, 4, Some(val))),
+            }
+            return Ok(());
+        } else if let Some(offset) = memory::SCRATCHPAD.contains_cached(addr) {
+            self.scratchpad.store32(offset, val);
+            return Ok(());
+        } else if memory::EXPANSION_1.contains(addr).is_some() && self.cartridge.is_some() {
             return Ok(());
         } else if let Some(offset) = memory::MEM_CONTROL.contains(addr) {
             match offset {
-                0 => {
-                    if val != 0x1f000000 {
-                        return Err(format!("bad_expansion_1_base_address:_0x{:08x}", val));
-                    }
-                }
-                4 => {
-                    if val != 0x1f802000 {
-                        return Err(format!("bad_expansion_2_base_address:_0x{:08x}", val));
-                    }
-                }
-                _ => println!("Unhandled_write_to_MEM_CONTROL 0x{:08x}", val),
+                // Relocating the expansion regions isn't supported
+                0 if val != 0x1f000000 => return Err(Self::fault(addr, 4, Some(val))),
+                4 if val != 0x1f802000 => return Err(Self::fault(addr, 4, Some(val))),
+                _ => self.mem_control[offset / 4] = val,
             }
             return Ok(());
-        } else if let Some(offset) = memory::RAM_SIZE.contains(addr) {
-            println!("ram_size_store at addr {:08x}", offset);
+        } else if memory::RAM_SIZE.contains(addr).is_some() {
+            self.ram_size = val;
             return Ok(());
-        } else if let Some(offset) = memory::CACHE_CONTROL.contains(addr) {
-            println!("cache_control_store at addr {:08x}", offset);
+        } else if memory::CACHE_CONTROL.contains(addr).is_some() {
+            self.cache_control = val;
             return Ok(());
         } else if let Some(offset) = memory::SYS_CONTROL.contains(addr) {
             println!("SYS_CONTROL__store at addr {:08x}", offset);
             return Ok(());
         } else if let Some(offset) = memory::IRQ_CONTROL.contains(addr) {
-            println!("IRQ control: {:x} <- {:08x}", addr, val);
+            self.set_irq_reg(offset, val);
             return Ok(());
         } else if let Some(offset) = memory::DMA.contains(addr) {
             println!("DMA write at {:08x} with val {:08x}", addr, val);
-            return self.set_dma_reg(offset, val);
+            return self.set_dma_reg(addr, offset, val);
         } else if let Some(offset) = memory::GPU.contains(addr) {
-            println!("GPU write at {:08x} with val {:08x}", addr, val);
+            self.gpu.store(offset, val);
             return Ok(());
         } else if let Some(offset) = memory::TIMERS.contains(addr) {
-            println!(
-                "Unhandled store32 to timer register at {:08x} with val {:08x}",
-                addr, val
-            );
+            self.timers.store(offset, val);
             return Ok(());
         }
 
-        Err(format!(
-            "unhandled_store32_into_address{:08x} with val {:08x}",
-            addr, val
-        ))
+        Err(Self::fault(addr, 4, Some(val)))
     }
 }
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+/// Header the BIOS looks for before running a cartridge
+pub const LICENSE: &[u8] = b"Licensed by Sony Computer Entertainment Inc.";
+
+/// Offsets of the pre-boot entry point and of its license string
+const PRE_BOOT_ENTRY: usize = 0x80;
+const PRE_BOOT_LICENSE: usize = 0x84;
+
+/// ROM plugged into the parallel port, mapped to Expansion 1
+pub struct Cartridge {
+    data: Vec<u8>,
+}
+
+impl Cartridge {
+    pub fn open(path: &Path) -> Result<Cartridge, String> {
+        let data =
+            std::fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+
+        Ok(Cartridge::from_bytes(data))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Cartridge {
+        Cartridge { data }
+    }
+
+    /// Reads past the end of the image see the open bus
+    pub fn load8(&self, offset: usize) -> u8 {
+        self.data.get(offset).copied().unwrap_or(0xff)
+    }
+
+    pub fn load16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.load8(offset), self.load8(offset + 1)])
+    }
+
+    pub fn load32(&self, offset: usize) -> u32 {
+        u32::from_le_bytes([0, 1, 2, 3].map(|i| self.load8(offset + i)))
+    }
+
+    /// Address the BIOS calls early in the boot, if the header is licensed
+    pub fn pre_boot_entry(&self) -> Option<u32> {
+        let license = self
+            .data
+            .get(PRE_BOOT_LICENSE..PRE_BOOT_LICENSE + LICENSE.len())?;
+
+        match license == LICENSE {
+            true => Some(self.load32(PRE_BOOT_ENTRY)),
+            false => None,
+        }
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+
+use crate::libs::disc::{self, Disc, Msf, Region};
+use crate::libs::irq::{Interrupt, Irq};
+use crate::libs::scheduler::Scheduler;
+
+const CPU_CLOCK_HZ: u64 = 33_868_800;
+
+/// Delay between a command write and its first response
+const COMMAND_DELAY: u64 = 50_401;
+/// Init takes a bit longer to acknowledge
+const INIT_DELAY: u64 = 81_102;
+/// Delay between an acknowledge and the next queued response
+const ACK_DELAY: u64 = 2_000;
+/// Delay of the second response of GetID and Init
+const ID_DELAY: u64 = 33_868;
+/// Seek time used by SeekL and before the first sector of a read
+const SEEK_DELAY: u64 = 180_000;
+
+/// Sector data sizes selected by bit 5 of the mode
+const DATA_SIZE: usize = 0x800;
+const WHOLE_SECTOR_SIZE: usize = 0x924;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Event {
+    /// Command execution and first response
+    Command,
+    /// Second response of the last command
+    Complete(u8),
+    /// Next sector of a ReadN/ReadS or Play
+    Sector,
+    /// Try to deliver a queued response once the interrupt is acknowledged
+    Deliver,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Seeking,
+    Reading,
+    Playing,
+}
+
+struct Response {
+    int: u8,
+    data: Vec<u8>,
+}
+
+/// CD-ROM controller, registers at 0x1f801800
+pub struct CdRom {
+    disc: Option<Disc>,
+    scheduler: Scheduler<Event>,
+
+    index: u8,
+    params: VecDeque<u8>,
+    response: VecDeque<u8>,
+    /// Responses waiting for the current interrupt to be acknowledged
+    pending: VecDeque<Response>,
+    irq_enable: u8,
+    irq_flags: u8,
+    /// Command waiting for its first response
+    command: Option<u8>,
+
+    mode: u8,
+    state: State,
+    motor_on: bool,
+    muted: bool,
+    seek_target: Msf,
+    seek_pending: bool,
+    position: Msf,
+
+    /// Last sector read, loaded in the data FIFO on request
+    sector: Vec<u8>,
+    data: Vec<u8>,
+    data_index: usize,
+}
+
+impl CdRom {
+    pub fn new() -> CdRom {
+        CdRom {
+            disc: None,
+            scheduler: Scheduler::new(),
+            index: 0,
+            params: VecDeque::new(),
+            response: VecDeque::new(),
+            pending: VecDeque::new(),
+            irq_enable: 0,
+            irq_flags: 0,
+            command: None,
+            mode: 0,
+            state: State::Idle,
+            motor_on: false,
+            muted: false,
+            seek_target: Msf::new(0, 2, 0),
+            seek_pending: false,
+            position: Msf::new(0, 2, 0),
+            sector: Vec::new(),
+            data: Vec::new(),
+            data_index: 0,
+        }
+    }
+
+    pub fn insert_disc(&mut self, disc: Disc) {
+        self.disc = Some(disc);
+        self.motor_on = true;
+    }
+
+    pub fn disc(&self) -> Option<&Disc> {
+        self.disc.as_ref()
+    }
+
+    pub fn tick(&mut self, cycles: u32, irq: &mut Irq) {
+        self.scheduler.advance(cycles);
+
+        while let Some(event) = self.scheduler.pop() {
+            match event {
+                Event::Command => self.execute(),
+                Event::Complete(command) => self.complete(command),
+                Event::Sector => self.read_sector(),
+                Event::Deliver => (),
+            }
+
+            self.deliver(irq);
+        }
+    }
+
+    pub fn load(&mut self, offset: usize) -> u8 {
+        match offset {
+            0 => self.status(),
+            1 => self.response.pop_front().unwrap_or(0),
+            2 => self.read_data(),
+            3 => match self.index & 1 {
+                0 => self.irq_enable | 0xe0,
+                _ => self.irq_flags | 0xe0,
+            },
+            _ => 0,
+        }
+    }
+
+    pub fn store(&mut self, offset: usize, val: u8, irq: &mut Irq) {
+        match (offset, self.index) {
+            (0, _) => self.index = val & 3,
+            (1, 0) => self.start_command(val),
+            (2, 0) if self.params.len() < 16 => self.params.push_back(val),
+            (2, 1) => {
+                self.irq_enable = val & 0x1f;
+                self.update_irq(irq);
+            }
+            (3, 0) => self.request(val),
+            (3, 1) => self.ack(val),
+            // Sound map and audio volume registers
+            _ => (),
+        }
+    }
+
+    /// Read a word from the data FIFO for DMA channel 3
+    pub fn dma_read(&mut self) -> u32 {
+        (0..4).fold(0, |word, i| word | ((self.read_data() as u32) << (i * 8)))
+    }
+
+    fn status(&self) -> u8 {
+        let mut r = self.index;
+
+        r |= (self.params.is_empty() as u8) << 3;
+        r |= ((self.params.len() < 16) as u8) << 4;
+        r |= (!self.response.is_empty() as u8) << 5;
+        r |= ((self.data_index < self.data.len()) as u8) << 6;
+        r |= (self.command.is_some() as u8) << 7;
+
+        r
+    }
+
+    /// Drive status byte returned by most commands
+    fn stat(&self) -> u8 {
+        let mut r = 0;
+
+        r |= (self.motor_on as u8) << 1;
+        r |= ((self.disc.is_none()) as u8) << 4;
+        r |= ((self.state == State::Reading) as u8) << 5;
+        r |= ((self.state == State::Seeking) as u8) << 6;
+        r |= ((self.state == State::Playing) as u8) << 7;
+
+        r
+    }
+
+    fn read_data(&mut self) -> u8 {
+        match self.data.get(self.data_index) {
+            Some(&b) => {
+                self.data_index += 1;
+                b
+            }
+            // Reading past the end repeats the last byte
+            None => self.data.last().copied().unwrap_or(0),
+        }
+    }
+
+    fn request(&mut self, val: u8) {
+        if val & 0x80 != 0 {
+            if self.data_index >= self.data.len() {
+                self.data = self.sector.clone();
+                self.data_index = 0;
+            }
+        } else {
+            self.data.clear();
+            self.data_index = 0;
+        }
+    }
+
+    fn ack(&mut self, val: u8) {
+        self.irq_flags &= !(val & 0x1f);
+
+        if val & 0x40 != 0 {
+            self.params.clear();
+        }
+
+        if self.irq_flags == 0 && !self.pending.is_empty() {
+            self.scheduler.schedule(ACK_DELAY, Event::Deliver);
+        }
+    }
+
+    fn update_irq(&mut self, irq: &mut Irq) {
+        if self.irq_flags & self.irq_enable != 0 {
+            irq.assert(Interrupt::CdRom);
+        }
+    }
+
+    /// Move the next queued response to the response FIFO, once the previous
+    /// interrupt has been acknowledged
+    fn deliver(&mut self, irq: &mut Irq) {
+        if self.irq_flags != 0 {
+            return;
+        }
+
+        if let Some(response) = self.pending.pop_front() {
+            self.response = response.data.into();
+            self.irq_flags = response.int;
+            self.update_irq(irq);
+        }
+    }
+
+    fn respond(&mut self, int: u8, data: Vec<u8>) {
+        self.pending.push_back(Response { int, data });
+    }
+
+    fn start_command(&mut self, command: u8) {
+        // A new command replaces one that hasn't been acknowledged yet
+        self.scheduler.cancel(Event::Command);
+        self.command = Some(command);
+
+        let delay = match command {
+            0x0a => INIT_DELAY,
+            _ => COMMAND_DELAY,
+        };
+
+        self.scheduler.schedule(delay, Event::Command);
+    }
+
+    fn sector_period(&self) -> u64 {
+        let speed = match self.mode & 0x80 != 0 {
+            true => 2,
+            false => 1,
+        };
+
+        CPU_CLOCK_HZ / (disc::SECTORS_PER_SECOND as u64 * speed)
+    }
+
+    fn execute(&mut self) {
+        let Some(command) = self.command.take() else {
+            return;
+        };
+
+        let params: Vec<u8> = self.params.drain(..).collect();
+
+        let Some(count) = param_count(command) else {
+            println!("Unhandled CD-ROM command {:02x} {:x?}", command, params);
+            self.error(0x40);
+            return;
+        };
+
+        if !count.contains(&params.len()) {
+            self.error(0x20);
+            return;
+        }
+
+        if needs_disc(command) && self.disc.is_none() {
+            self.error(0x80);
+            return;
+        }
+
+        match command {
+            0x01 => self.respond(3, vec![self.stat()]),
+            0x02 => {
+                self.seek_target = Msf::from_bcd(params[0], params[1], params[2]);
+                self.seek_pending = true;
+                self.respond(3, vec![self.stat()]);
+            }
+            0x03 => {
+                self.stop_reading();
+                self.seek();
+                self.state = State::Playing;
+                self.respond(3, vec![self.stat()]);
+                self.scheduler.schedule(self.sector_period(), Event::Sector);
+            }
+            0x06 | 0x1b => {
+                self.stop_reading();
+                self.respond(3, vec![self.stat()]);
+                self.seek();
+                self.state = State::Reading;
+                self.scheduler
+                    .schedule(SEEK_DELAY + self.sector_period(), Event::Sector);
+            }
+            0x08 => {
+                self.stop_reading();
+                self.respond(3, vec![self.stat()]);
+                self.motor_on = false;
+                self.scheduler.schedule(ID_DELAY, Event::Complete(command));
+            }
+            0x09 => {
+                self.respond(3, vec![self.stat()]);
+
+                // Pausing takes about a sector when the drive is busy
+                let delay = match self.state {
+                    State::Idle => ID_DELAY,
+                    _ => self.sector_period(),
+                };
+
+                self.stop_reading();
+                self.scheduler.schedule(delay, Event::Complete(command));
+            }
+            0x0a => {
+                self.stop_reading();
+                self.mode = 0;
+                self.motor_on = self.disc.is_some();
+                self.respond(3, vec![self.stat()]);
+                self.scheduler.schedule(ID_DELAY, Event::Complete(command));
+            }
+            0x0b => {
+                self.muted = true;
+                self.respond(3, vec![self.stat()]);
+            }
+            0x0c => {
+                self.muted = false;
+                self.respond(3, vec![self.stat()]);
+            }
+            0x0e => {
+                self.mode = params[0];
+                self.respond(3, vec![self.stat()]);
+            }
+            0x13 => {
+                let last = self.disc.as_ref().map_or(1, |d| d.tracks().len() as u8);
+                self.respond(3, vec![self.stat(), 0x01, disc::to_bcd(last)]);
+            }
+            0x14 => self.get_td(disc::from_bcd(params[0])),
+            0x15 | 0x16 => {
+                self.stop_reading();
+                self.respond(3, vec![self.stat()]);
+                self.state = State::Seeking;
+                self.scheduler
+                    .schedule(SEEK_DELAY, Event::Complete(command));
+            }
+            0x19 => self.test(params[0]),
+            0x1a => {
+                self.respond(3, vec![self.stat()]);
+                self.scheduler.schedule(ID_DELAY, Event::Complete(command));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Second response of the commands that have one
+    fn complete(&mut self, command: u8) {
+        match command {
+            0x1a => self.get_id(),
+            0x15 | 0x16 => {
+                self.seek();
+                self.state = State::Idle;
+                self.respond(2, vec![self.stat()]);
+            }
+            _ => self.respond(2, vec![self.stat()]),
+        }
+    }
+
+    fn error(&mut self, code: u8) {
+        self.respond(5, vec![self.stat() | 1, code]);
+    }
+
+    fn seek(&mut self) {
+        if self.seek_pending {
+            self.position = self.seek_target;
+            self.seek_pending = false;
+        }
+    }
+
+    fn stop_reading(&mut self) {
+        self.scheduler.cancel(Event::Sector);
+        self.state = State::Idle;
+    }
+
+    fn get_td(&mut self, track: u8) {
+        let Some(disc) = &self.disc else {
+            return self.error(0x80);
+        };
+
+        let start = match track {
+            0 => Some(disc.lead_out()),
+            n => disc.track(n).map(|t| t.start),
+        };
+
+        match start {
+            Some(msf) => {
+                let [m, s, _] = msf.to_bcd();
+                self.respond(3, vec![self.stat(), m, s]);
+            }
+            None => self.error(0x10),
+        }
+    }
+
+    fn test(&mut self, sub: u8) {
+        match sub {
+            // Controller version, 94/09/19 C0 for the SCPH-1001
+            0x20 => self.respond(3, vec![0x94, 0x09, 0x19, 0xc0]),
+            _ => {
+                println!("Unhandled CD-ROM test command {:02x}", sub);
+                self.error(0x10);
+            }
+        }
+    }
+
+    fn get_id(&mut self) {
+        let Some(disc) = &self.disc else {
+            return self.respond(5, vec![0x08, 0x40, 0, 0, 0, 0, 0, 0]);
+        };
+
+        let region = match disc.region() {
+            Some(Region::Japan) => b'I',
+            Some(Region::NorthAmerica) => b'A',
+            Some(Region::Europe) => b'E',
+            None => {
+                // Unlicensed disc
+                return self.respond(5, vec![self.stat() | 1, 0x80, 0, 0, 0, 0, 0, 0]);
+            }
+        };
+
+        self.respond(
+            2,
+            vec![self.stat(), 0x00, 0x20, 0x00, b'S', b'C', b'E', region],
+        );
+    }
+
+    fn read_sector(&mut self) {
+        let period = self.sector_period();
+        let position = self.position;
+
+        self.position = Msf::from_sector_index(position.sector_index() + 1);
+        self.scheduler.schedule(period, Event::Sector);
+
+        if self.state == State::Playing {
+            // CD-DA isn't played back, the head just moves along
+            return;
+        }
+
+        let Some(sector) = self.disc.as_ref().and_then(|d| d.sector(position)) else {
+            self.stop_reading();
+            return self.error(0x04);
+        };
+
+        self.sector = match self.mode & 0x20 != 0 {
+            true => sector[12..12 + WHOLE_SECTOR_SIZE].to_vec(),
+            false => sector[24..24 + DATA_SIZE].to_vec(),
+        };
+
+        // Only the latest sector matters, drop the older ones that weren't
+        // acknowledged in time
+        self.pending.retain(|r| r.int != 1);
+        self.respond(1, vec![self.stat()]);
+    }
+}
+
+/// Number of parameters `command` takes, `None` if it isn't emulated
+fn param_count(command: u8) -> Option<RangeInclusive<usize>> {
+    let count = match command {
+        0x02 => 3..=3,
+        // Play takes an optional track
+        0x03 => 0..=1,
+        0x0e | 0x14 | 0x19 => 1..=1,
+        0x01 | 0x06 | 0x08..=0x0c | 0x13 | 0x15 | 0x16 | 0x1a | 0x1b => 0..=0,
+        _ => return None,
+    };
+
+    Some(count)
+}
+
+/// Commands rejected when the drive is empty
+fn needs_disc(command: u8) -> bool {
+    matches!(command, 0x06 | 0x13 | 0x15 | 0x16 | 0x1b)
+}
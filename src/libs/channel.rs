@@ -56,6 +56,7 @@ impl Channel {
         self.enable && trigger
     }
 
+    #[allow(dead_code)]
     pub fn block_control(&self) -> u32 {
         let bs = self.block_size as u32;
         let bc = self.block_count as u32;
@@ -78,7 +79,7 @@ impl Channel {
     pub fn control(&self) -> u32 {
         let mut r = 0;
 
-        r |= (self.direction as u32) << 0;
+        r |= self.direction as u32;
         r |= (self.step as u32) << 1;
         r |= (self.chop as u32) << 8;
         r |= (self.sync as u32) << 9;
@@ -91,7 +92,8 @@ impl Channel {
         r
     }
 
-    pub fn set_control(&mut self, val: u32) {
+    /// Fails on the reserved sync mode
+    pub fn set_control(&mut self, val: u32) -> Result<(), &'static str> {
         self.direction = match val & 1 != 0 {
             true => Direction::FromRam,
             false => Direction::ToRam,
@@ -108,7 +110,7 @@ impl Channel {
             0 => Sync::Manual,
             1 => Sync::Request,
             2 => Sync::LinkedList,
-            n => panic!("Unknown DMA sync mode {}", n),
+            _ => return Err("reserved sync mode 3"),
         };
 
         self.chop_dma_sz = ((val >> 16) & 7) as u8;
@@ -118,6 +120,8 @@ impl Channel {
         self.trigger = (val >> 28) & 1 != 0;
 
         self.dummy = ((val >> 29) & 3) as u8;
+
+        Ok(())
     }
 }
 
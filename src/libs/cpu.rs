@@ -1,8 +1,18 @@
 use crate::consts;
 use crate::libs::bus::Bus;
+use crate::libs::disasm;
+use crate::libs::error::{BusError, EmuError};
+use crate::libs::exe::{self, Exe};
+use crate::libs::gte::Gte;
+use crate::libs::hle::Hle;
+use crate::libs::icache::{self, ICache};
+use crate::libs::kernel::KernelTracer;
 use crate::libs::map::opcode::Instruction;
+use crate::libs::trace::Tracer;
+use crate::libs::watch::{WatchHit, Watchpoint};
 use std::fmt;
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
     bus: Bus,
     pc: u32, // Program Counter (PC)
@@ -17,8 +27,42 @@ pub struct CPU {
     current_pc: u32,
     cause: u32,
     epc: u32,
+    /// Address of the last misaligned access
+    badvaddr: u32,
+    /// Breakpoint on execute address and its mask
+    bpc: u32,
+    bpcm: u32,
+    /// Breakpoint on data access address and its mask
+    bda: u32,
+    bdam: u32,
+    /// Breakpoint control
+    dcic: u32,
+    /// Target of the last jump or taken branch
+    jumpdest: u32,
+    /// DCIC status bits of a data breakpoint hit by the current instruction
+    data_break: u32,
     branch: bool,
     delay_slot: bool,
+    gte: Gte,
+    icache: ICache,
+    /// CPU cycles taken by the instruction being executed
+    cycles: u32,
+    /// Executable started in place of the shell
+    exe: Option<Exe>,
+    kernel_tracer: Option<KernelTracer>,
+    /// Kernel emulation, replaces the ROM code with the HLE BIOS
+    hle: Option<Hle>,
+    tracer: Option<Tracer>,
+    /// Checked on every load and store, instruction fetches excluded
+    watchpoints: Vec<Watchpoint>,
+    /// First watchpoint hit since the last `take_watch_hit`
+    watch_hit: Option<WatchHit>,
+    /// First error of the current instruction
+    fault: Option<EmuError>,
+    /// Bus error raised by the current instruction
+    bus_exception: Option<Exception>,
+    /// Report unmapped accesses as errors instead of raising bus errors
+    strict: bool,
 }
 
 impl fmt::Display for CPU {
@@ -28,35 +72,41 @@ impl fmt::Display for CPU {
             "{{
     program_counter: {:08x},
     next_program_counter: {:08x},
-    opcode: {:08x},
+    opcode: {:08x} ({}),
     status_register: {:08x},
     hi: {:08x},
     lo: {:08x},
     current_pc {:08x},
     cause: {:08x},
     epc: {:08x},
+    badvaddr: {:08x},
     branch: {},
     delay_slot: {}
 }}",
             self.pc,
             self.next_pc,
             self.opcode.0,
+            disasm::disassemble(self.opcode, Some(self.current_pc)),
             self.sr,
             self.hi,
             self.lo,
             self.current_pc,
             self.cause,
             self.epc,
+            self.badvaddr,
             self.branch,
             self.delay_slot
         )
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 enum Exception {
+    Interrupt = 0x0,
     LoadAddressError = 0x4,
     StoreAddressError = 0x5,
+    InstructionBusError = 0x6,
+    DataBusError = 0x7,
     SysCall = 0x8,
     Break = 0x9,
     IllegalInstruction = 0xa,
@@ -64,37 +114,132 @@ enum Exception {
     Overflow = 0xc,
 }
 
+/// Value of loads the bus couldn't complete
+const OPEN_BUS: u32 = 0xffff_ffff;
+
+/// COP0 processor ID
+const PRID: u32 = 0x0000_0002;
+
+// DCIC status bits
+const DCIC_HIT: u32 = 1 << 0;
+const DCIC_CODE_HIT: u32 = 1 << 1;
+const DCIC_DATA_HIT: u32 = 1 << 2;
+const DCIC_READ_HIT: u32 = 1 << 3;
+const DCIC_WRITE_HIT: u32 = 1 << 4;
+
+// DCIC enable bits, a breakpoint needs its bit and both master enables
+const DCIC_CODE: u32 = 1 << 24;
+const DCIC_DATA: u32 = 1 << 25;
+const DCIC_READ: u32 = 1 << 26;
+const DCIC_WRITE: u32 = 1 << 27;
+const DCIC_MASTER: u32 = 1 << 30;
+const DCIC_SUPER_MASTER: u32 = 1 << 31;
+
+const DCIC_WRITABLE: u32 = 0xff80_f03f;
+
 impl CPU {
-    pub fn run_next_opcode(&mut self) {
+    /// Run one instruction. Unmapped accesses raise a bus error exception,
+    /// unless in strict mode. Other bus errors and unimplemented opcodes
+    /// don't stop the instruction: failed loads read `OPEN_BUS`, failed
+    /// stores are dropped and the first error is returned once the step
+    /// completes, the caller decides whether to go on.
+    pub fn run_next_opcode(&mut self) -> Result<(), EmuError> {
         let (reg, val) = self.load;
         self.set_r(reg, val);
 
         self.load = (0, 0);
+        self.data_break = 0;
+
+        self.intercept();
 
         self.current_pc = self.pc;
+        self.cycles = 0;
+
+        self.delay_slot = self.branch;
+        self.branch = false;
+
+        if self.code_breakpoint() {
+            self.debug_exception(DCIC_CODE_HIT);
+            self.r = self.out_r;
+            self.bus.tick(1);
+            return Ok(());
+        }
 
         if !Self::check_alignment(self.current_pc as usize, 4) {
-            self.exception(Exception::LoadAddressError);
-            return;
+            self.address_error(Exception::LoadAddressError, self.current_pc);
+            self.r = self.out_r;
+            self.bus.tick(1);
+            return Ok(());
         }
 
-        self.opcode = Instruction(self.load32(self.pc as usize));
+        self.opcode = Instruction(self.fetch(self.pc as usize));
         self.pc = self.next_pc;
         self.next_pc = self.pc.wrapping_add(4);
 
-        self.delay_slot = self.branch;
-        self.branch = false;
+        let traced = self
+            .tracer
+            .as_mut()
+            .is_some_and(|t| t.wants(self.current_pc));
 
-        self.decode_and_execute(self.opcode);
+        let interrupt = self.irq_pending();
+
+        if interrupt {
+            // The GTE command the interrupt lands on still runs, the BIOS
+            // handler skips over it when returning
+            if self.gte_command(self.opcode) {
+                self.op_cop2(self.opcode);
+            }
+
+            self.bus_exception = None;
+            self.exception(Exception::Interrupt);
+        } else if let Some(cause) = self.bus_exception.take() {
+            // The fetch failed
+            self.exception(cause);
+        } else {
+            self.decode_and_execute(self.opcode);
+
+            if let Some(cause) = self.bus_exception.take() {
+                // The failed load doesn't reach the register
+                self.load = (0, 0);
+                self.exception(cause);
+            } else if self.data_break != 0 {
+                // The instruction runs again once the handler returns
+                self.load = (0, 0);
+                self.debug_exception(self.data_break);
+            } else if self.branch {
+                self.jumpdest = self.next_pc;
+            }
+        }
+
+        if traced {
+            if let Some(tracer) = &mut self.tracer {
+                tracer.record(
+                    self.current_pc,
+                    self.opcode,
+                    interrupt,
+                    &self.r,
+                    &self.out_r,
+                    self.load,
+                );
+            }
+        }
 
         self.r = self.out_r;
+
+        self.bus.tick(self.cycles);
+
+        match self.fault.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
     pub fn new(bus: Bus) -> Self {
         let registers: [u32; 32] = [0; 32];
         let start = consts::BIOS_START as u32;
+        let hle = bus.bios().is_hle().then(Hle::new);
         Self {
-            bus: bus,
+            bus,
             pc: start, // Endereço inicial do BIOS do PS1
             next_pc: start.wrapping_add(4),
             load: (0, 0),
@@ -107,52 +252,452 @@ impl CPU {
             current_pc: 0,
             cause: 0,
             epc: 0,
+            badvaddr: 0,
+            bpc: 0,
+            bpcm: 0,
+            bda: 0,
+            bdam: 0,
+            dcic: 0,
+            jumpdest: 0,
+            data_break: 0,
             branch: false,
             delay_slot: false,
+            gte: Gte::new(),
+            icache: ICache::new(),
+            cycles: 0,
+            exe: None,
+            kernel_tracer: None,
+            hle,
+            tracer: None,
+            watchpoints: Vec::new(),
+            watch_hit: None,
+            fault: None,
+            bus_exception: None,
+            strict: false,
         }
     }
 
-    fn check_alignment(addr: usize, alignment: usize) -> bool {
-        addr % alignment == 0
+    /// Run `exe` once the BIOS jumps to the shell, instead of the shell
+    pub fn side_load(&mut self, exe: Exe) {
+        self.exe = Some(exe);
     }
 
-    fn load32(&self, addr: usize) -> u32 {
-        return match self.bus.load32(addr) {
-            Ok(val) => val,
-            Err(string) => panic!("{}", string),
-        };
+    /// Strict mode reports accesses to unmapped addresses as errors from
+    /// `run_next_opcode` instead of raising bus error exceptions
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Record every executed instruction
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    /// Log the kernel calls and their return value
+    pub fn set_kernel_tracer(&mut self, tracer: Option<KernelTracer>) {
+        self.kernel_tracer = tracer;
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
     }
 
-    fn load16(&self, addr: usize) -> u16 {
-        return match self.bus.load16(addr) {
-            Ok(val) => val,
-            Err(string) => panic!("{}", string),
+    /// Returns false when there was no such watchpoint
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        match self.watchpoints.iter().position(|w| w == watchpoint) {
+            Some(index) => {
+                self.watchpoints.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// Hooks on the BIOS entry points, run before the instruction at `pc`
+    fn intercept(&mut self) {
+        let pc = self.pc & 0x1fff_ffff;
+        let function = self.r[9];
+
+        self.trace_kernel();
+
+        if let Some(mut hle) = self.hle.take() {
+            let handled = hle.intercept(self);
+            self.hle = Some(hle);
+
+            let handled = handled.unwrap_or_else(|e| {
+                self.fault(e);
+                true
+            });
+
+            if handled {
+                // The kernel may have returned to the caller already
+                if self.pc & 0x1fff_ffff != pc {
+                    self.trace_kernel();
+                }
+                return;
+            }
+        }
+
+        match (pc, function) {
+            // std_out_putchar
+            (0xa0, 0x3c) | (0xb0, 0x3d) => {
+                let c = self.r[4] as u8;
+                self.bus.tty_mut().putchar(c);
+            }
+            _ => (),
+        }
+
+        if self.pc == exe::SHELL_ENTRY {
+            if let Some(exe) = self.exe.take() {
+                if let Err(e) = self.start_exe(exe) {
+                    self.fault(e.into());
+                }
+            }
+        }
+    }
+
+    fn trace_kernel(&mut self) {
+        if let Some(tracer) = &mut self.kernel_tracer {
+            // out_r includes the load that just completed
+            tracer.step(self.pc, &self.out_r);
+        }
+    }
+
+    /// Load `exe` and jump to its entry point
+    pub fn start_exe(&mut self, exe: Exe) -> Result<(), BusError> {
+        self.bus.load_exe(&exe)?;
+
+        self.set_r(28, exe.gp);
+
+        if exe.sp != 0 {
+            self.set_r(29, exe.sp);
+            self.set_r(30, exe.sp);
+        }
+
+        self.r = self.out_r;
+
+        // The kernel flushes the cache before jumping to the executable
+        self.icache.invalidate_all();
+
+        self.pc = exe.pc;
+        self.next_pc = self.pc.wrapping_add(4);
+
+        Ok(())
+    }
+
+    /// Executable waiting for the shell to start, if any
+    pub fn take_exe(&mut self) -> Option<Exe> {
+        self.exe.take()
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    /// Continue execution at `addr`
+    pub fn jump(&mut self, addr: u32) {
+        self.pc = addr;
+        self.next_pc = addr.wrapping_add(4);
+    }
+
+    pub fn reg(&self, index: usize) -> u32 {
+        self.r[index]
+    }
+
+    /// Write a register immediately, bypassing the load delay
+    pub fn set_reg(&mut self, index: usize, val: u32) {
+        self.set_r(index, val);
+        self.r[index] = self.out_r[index];
+    }
+
+    pub fn hi(&self) -> u32 {
+        self.hi
+    }
+
+    pub fn set_hi(&mut self, val: u32) {
+        self.hi = val;
+    }
+
+    pub fn lo(&self) -> u32 {
+        self.lo
+    }
+
+    pub fn set_lo(&mut self, val: u32) {
+        self.lo = val;
+    }
+
+    pub fn sr(&self) -> u32 {
+        self.sr
+    }
+
+    pub fn set_sr(&mut self, val: u32) {
+        self.sr = val;
+    }
+
+    pub fn epc(&self) -> u32 {
+        self.epc
+    }
+
+    pub fn set_epc(&mut self, val: u32) {
+        self.epc = val;
+    }
+
+    /// Cause register with the external interrupt line in bit 10
+    pub fn cause(&self) -> u32 {
+        self.cause | ((self.bus.irq_active() as u32) << 10)
+    }
+
+    pub fn badvaddr(&self) -> u32 {
+        self.badvaddr
+    }
+
+    /// COP0 register `n`, `None` for the reserved ones
+    pub fn cop0(&self, n: usize) -> Option<u32> {
+        let v = match n {
+            3 => self.bpc,
+            5 => self.bda,
+            6 => self.jumpdest,
+            7 => self.dcic,
+            8 => self.badvaddr,
+            9 => self.bdam,
+            11 => self.bpcm,
+            12 => self.sr,
+            13 => self.cause(),
+            14 => self.epc,
+            15 => PRID,
+            // Unused, reads return garbage
+            16..=31 => 0,
+            _ => return None,
         };
+
+        Some(v)
+    }
+
+    /// Only the software interrupt bits are writable
+    pub fn set_cause(&mut self, val: u32) {
+        self.cause &= !0x300;
+        self.cause |= val & 0x300;
+    }
+
+    fn irq_pending(&self) -> bool {
+        let pending = (self.cause() & self.sr) & 0x700;
+
+        self.sr & 1 != 0 && pending != 0
+    }
+
+    fn check_alignment(addr: usize, alignment: usize) -> bool {
+        addr.is_multiple_of(alignment)
+    }
+
+    /// Debugger watchpoints and COP0 data breakpoints. Returns false when a
+    /// breakpoint hit cancels the access, the instruction runs again once the
+    /// handler returns
+    fn access(&mut self, addr: usize, len: u32, write: bool) -> bool {
+        self.watch(addr, len, write);
+        self.data_breakpoint(addr, write);
+
+        self.data_break == 0
+    }
+
+    fn watch(&mut self, addr: usize, len: u32, write: bool) {
+        if self.watch_hit.is_some() {
+            return;
+        }
+
+        let addr = addr as u32;
+
+        if let Some(&watchpoint) = self
+            .watchpoints
+            .iter()
+            .find(|w| w.matches(addr, len, write))
+        {
+            self.watch_hit = Some(WatchHit {
+                watchpoint,
+                addr,
+                write,
+            });
+        }
     }
 
-    fn load8(&self, addr: usize) -> u8 {
-        return match self.bus.load8(addr) {
-            Ok(val) => val,
-            Err(string) => panic!("{}", string),
+    /// Keep the first error of the instruction
+    fn fault(&mut self, e: EmuError) {
+        self.fault.get_or_insert(e);
+    }
+
+    /// Unmapped accesses raise a bus error exception like on the hardware,
+    /// unless in strict mode
+    fn bus_fault(&mut self, e: BusError, cause: Exception) {
+        match e {
+            BusError::Unmapped { .. } if !self.strict => {
+                self.bus_exception.get_or_insert(cause);
+            }
+            _ => self.fault(e.into()),
+        }
+    }
+
+    fn unimplemented(&mut self, i: Instruction) {
+        self.fault(EmuError::UnimplementedOpcode {
+            pc: self.current_pc,
+            opcode: i.0,
+        });
+    }
+
+    fn fetch(&mut self, addr: usize) -> u32 {
+        let pc = addr as u32;
+        let cached = self.bus.cache_control() & icache::ENABLE != 0 && ICache::cached(pc);
+
+        if cached {
+            if let Some(word) = self.icache.fetch(pc) {
+                self.cycles += 1;
+                return word;
+            }
+        }
+
+        self.cycles += Bus::access_time(addr);
+
+        let word = match self.bus.load32(addr) {
+            Ok(word) => word,
+            Err(e) => {
+                self.bus_fault(e, Exception::InstructionBusError);
+                return OPEN_BUS;
+            }
         };
+
+        if cached {
+            // A miss fills the line up to its end
+            self.icache.fill(pc, word);
+
+            for next in ICache::fill_range(pc).skip(1) {
+                match self.bus.load32(next as usize) {
+                    Ok(word) => self.icache.fill(next, word),
+                    Err(e) => {
+                        // The rest of the line stays invalid
+                        self.fault(e.into());
+                        break;
+                    }
+                }
+
+                self.cycles += 1;
+            }
+        }
+
+        word
+    }
+
+    fn load32(&mut self, addr: usize) -> u32 {
+        self.cycles += Bus::access_time(addr);
+
+        if !self.access(addr, 4, false) {
+            return OPEN_BUS;
+        }
+
+        self.bus.load32(addr).unwrap_or_else(|e| {
+            self.bus_fault(e, Exception::DataBusError);
+            OPEN_BUS
+        })
+    }
+
+    fn load16(&mut self, addr: usize) -> u16 {
+        self.cycles += Bus::access_time(addr);
+
+        if !self.access(addr, 2, false) {
+            return OPEN_BUS as u16;
+        }
+
+        self.bus.load16(addr).unwrap_or_else(|e| {
+            self.bus_fault(e, Exception::DataBusError);
+            OPEN_BUS as u16
+        })
+    }
+
+    fn load8(&mut self, addr: usize) -> u8 {
+        self.cycles += Bus::access_time(addr);
+
+        if !self.access(addr, 1, false) {
+            return OPEN_BUS as u8;
+        }
+
+        self.bus.load8(addr).unwrap_or_else(|e| {
+            self.bus_fault(e, Exception::DataBusError);
+            OPEN_BUS as u8
+        })
     }
 
     fn store8(&mut self, addr: usize, val: u8) {
-        self.bus
-            .store8(addr, val)
-            .unwrap_or_else(|string| panic!("{}", string));
+        if !self.access(addr, 1, true) {
+            return;
+        }
+
+        if self.isolated_store(addr, val as u32) {
+            return;
+        }
+
+        if let Err(e) = self.bus.store8(addr, val) {
+            self.bus_fault(e, Exception::DataBusError);
+        }
     }
 
     fn store16(&mut self, addr: usize, val: u16) {
-        self.bus
-            .store16(addr, val)
-            .unwrap_or_else(|string| panic!("{}", string));
+        if !self.access(addr, 2, true) {
+            return;
+        }
+
+        if self.isolated_store(addr, val as u32) {
+            return;
+        }
+
+        if let Err(e) = self.bus.store16(addr, val) {
+            self.bus_fault(e, Exception::DataBusError);
+        }
     }
 
     fn store32(&mut self, addr: usize, val: u32) {
-        self.bus
-            .store32(addr, val)
-            .unwrap_or_else(|string| panic!("{}", string));
+        if !self.access(addr, 4, true) {
+            return;
+        }
+
+        if self.isolated_store(addr, val) {
+            return;
+        }
+
+        if let Err(e) = self.bus.store32(addr, val) {
+            self.bus_fault(e, Exception::DataBusError);
+        }
+    }
+
+    /// Stores go to the I-cache while it is isolated from memory
+    fn isolated_store(&mut self, addr: usize, val: u32) -> bool {
+        if self.sr & 0x10000 == 0 {
+            return false;
+        }
+
+        let control = self.bus.cache_control();
+        self.icache.isolated_store(control, addr as u32, val);
+
+        true
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+
+    /// Debugger write to memory, the I-cache drops its copy of the word so
+    /// patched code runs
+    pub fn poke8(&mut self, addr: u32, val: u8) -> bool {
+        self.icache.invalidate(addr);
+        self.bus.poke8(addr as usize, val)
     }
 
     pub fn decode_and_execute(&mut self, i: Instruction) {
@@ -221,11 +766,11 @@ impl CPU {
             0x2e => self.op_swr(i.imm_se(), i.rt(), i.rs()),
             0x30 => self.exception(Exception::CoprocessorError), // lwc0
             0x31 => self.exception(Exception::CoprocessorError), // lwc1
-            0x32 => panic!("unhandled GTE LWC2:  {:08x}", i.0),  // lwc2
+            0x32 => self.op_lwc2(i.imm_se(), i.rt(), i.rs()),
             0x33 => self.exception(Exception::CoprocessorError), // lwc3
             0x38 => self.exception(Exception::CoprocessorError), // swc0
             0x39 => self.exception(Exception::CoprocessorError), // swc1
-            0x3a => panic!("unhandled GTE SWC2:  {:08x}", i.0),  // swc2
+            0x3a => self.op_swc2(i.imm_se(), i.rt(), i.rs()),
             0x3b => self.exception(Exception::CoprocessorError), // swc3
             _ => self.op_illegal(i),
         }
@@ -236,14 +781,16 @@ impl CPU {
 
         let handler = match self.sr & (1 << 22) != 0 {
             true => 0xbfc00180,
-            false => 0x80000000,
+            false => 0x80000080,
         };
 
         let mode = self.sr & 0x3f;
         self.sr &= !0x3f;
         self.sr |= (mode << 2) & 0x3f;
 
-        self.cause = (cause as u32) << 2;
+        // Keep the software interrupt bits
+        self.cause &= 0x300;
+        self.cause |= (cause as u32) << 2;
 
         self.epc = self.current_pc;
 
@@ -256,8 +803,55 @@ impl CPU {
         self.next_pc = self.pc.wrapping_add(4);
     }
 
+    /// Hardware breakpoint hit, `status` are the DCIC bits reporting it
+    fn debug_exception(&mut self, status: u32) {
+        self.dcic |= DCIC_HIT | status;
+        self.exception(Exception::Break);
+
+        // Debug exceptions have their own vector
+        self.pc = match self.sr & (1 << 22) != 0 {
+            true => 0xbfc00140,
+            false => 0x80000040,
+        };
+        self.next_pc = self.pc.wrapping_add(4);
+    }
+
+    fn breakpoints_enabled(&self, kind: u32) -> bool {
+        let enable = DCIC_SUPER_MASTER | DCIC_MASTER | kind;
+
+        self.dcic & enable == enable
+    }
+
+    fn code_breakpoint(&self) -> bool {
+        self.breakpoints_enabled(DCIC_CODE) && (self.current_pc ^ self.bpc) & self.bpcm == 0
+    }
+
+    fn data_breakpoint(&mut self, addr: usize, write: bool) {
+        if self.dcic & DCIC_SUPER_MASTER == 0 {
+            return;
+        }
+
+        let (kind, status) = match write {
+            true => (DCIC_WRITE, DCIC_WRITE_HIT),
+            false => (DCIC_READ, DCIC_READ_HIT),
+        };
+
+        if self.breakpoints_enabled(DCIC_DATA | kind) && (addr as u32 ^ self.bda) & self.bdam == 0 {
+            self.data_break |= DCIC_DATA_HIT | status;
+        }
+    }
+
+    /// Misaligned fetch, load or store at `addr`
+    fn address_error(&mut self, cause: Exception, addr: u32) {
+        self.badvaddr = addr;
+        self.exception(cause);
+    }
+
     fn op_illegal(&mut self, i: Instruction) {
-        println!("Illegal instruction {:08x}", i.0);
+        println!(
+            "Illegal instruction {:08x} ({}) at {:08x}",
+            i.0, i, self.current_pc
+        );
         self.exception(Exception::IllegalInstruction);
     }
 
@@ -302,7 +896,7 @@ impl CPU {
 
     fn op_sllv(&mut self, rt: usize, rs: usize, rd: usize) {
         let v = self.r[rt] << (self.r[rs] & 0x1f);
-        self.set_r(rd, v as u32);
+        self.set_r(rd, v);
     }
 
     fn op_slt(&mut self, rt: usize, rs: usize, rd: usize) {
@@ -505,20 +1099,60 @@ impl CPU {
             0b00000 => self.op_mfc0(i.rt(), i.rd()),
             0b00100 => self.op_mtc0(i.rt(), i.rd()),
             0x10 => self.op_rfe(i),
-            _ => panic!(
-                "Unhandled cop0 instruction: {:08x}\n CPU state: {}",
-                i.0, self
-            ),
+            _ => self.unimplemented(i),
         }
     }
 
+    fn gte_command(&self, i: Instruction) -> bool {
+        i.primary() == 0x12 && i.rs() & 0x10 != 0 && self.sr & (1 << 30) != 0
+    }
+
     fn op_cop2(&mut self, i: Instruction) {
-        panic!("unhandled GTE instruction: {:08x}", i.0);
+        if self.sr & (1 << 30) == 0 {
+            self.exception(Exception::CoprocessorError);
+            return;
+        }
+
+        if i.rs() & 0x10 != 0 {
+            if !self.gte.command(i.0) {
+                self.unimplemented(i);
+            }
+            return;
+        }
+
+        match i.rs() {
+            0b00000 => self.op_mfc2(i.rt(), i.rd()),
+            0b00010 => self.op_cfc2(i.rt(), i.rd()),
+            0b00100 => self.op_mtc2(i.rt(), i.rd()),
+            0b00110 => self.op_ctc2(i.rt(), i.rd()),
+            _ => self.unimplemented(i),
+        }
+    }
+
+    fn op_mfc2(&mut self, rt: usize, rd: usize) {
+        let v = self.gte.data(rd);
+        self.load = (rt, v);
+    }
+
+    fn op_cfc2(&mut self, rt: usize, rd: usize) {
+        let v = self.gte.control(rd);
+        self.load = (rt, v);
+    }
+
+    fn op_mtc2(&mut self, rt: usize, rd: usize) {
+        let v = self.r[rt];
+        self.gte.set_data(rd, v);
+    }
+
+    fn op_ctc2(&mut self, rt: usize, rd: usize) {
+        let v = self.r[rt];
+        self.gte.set_control(rd, v);
     }
 
     fn op_rfe(&mut self, i: Instruction) {
         if i.secondary() != 0b010000 {
-            panic!("Invalid cop0 instruction at rfe {:06b}", i.secondary());
+            self.unimplemented(i);
+            return;
         }
         let mode = self.sr & 0x3f;
         self.sr &= !0x3f;
@@ -526,31 +1160,25 @@ impl CPU {
     }
 
     fn op_mfc0(&mut self, rt: usize, rd: usize) {
-        let v = match rd {
-            12 => self.sr,
-            13 => self.cause,
-            14 => self.epc,
-            _ => panic!("Unhandled read from cop0r{}", rd),
-        };
-        self.load = (rt, v);
+        match self.cop0(rd) {
+            Some(v) => self.load = (rt, v),
+            None => self.exception(Exception::IllegalInstruction),
+        }
     }
 
     fn op_mtc0(&mut self, rt: usize, rd: usize) {
         let v = self.r[rt];
 
         match rd {
-            3 | 5 | 6 | 7 | 9 | 11 => {
-                if v != 0 {
-                    panic!("Unhandled write to cop0 {:}", rd);
-                }
-            }
+            3 => self.bpc = v,
+            5 => self.bda = v,
+            7 => self.dcic = v & DCIC_WRITABLE,
+            9 => self.bdam = v,
+            11 => self.bpcm = v,
             12 => self.sr = v,
-            13 => {
-                if v != 0 {
-                    panic!("Unhandled write to cop0 {:}", rd);
-                }
-            }
-            n => panic!("Unhandled cop0 register: {:08x}", n),
+            13 => self.set_cause(v),
+            // The other registers are read only or don't exist
+            _ => (),
         }
     }
 
@@ -601,11 +1229,6 @@ impl CPU {
     }
 
     fn op_sb(&mut self, imm_se: u32, rt: usize, rs: usize) {
-        if self.sr & 0x10000 != 0 {
-            // Cache is isolated, ignore write
-            println!("Ignoring store while cache is isolated");
-            return;
-        }
         let v = (self.r[rt] & 0xff) as u8;
         let i = self.r[rs].wrapping_add(imm_se) as usize;
 
@@ -631,7 +1254,7 @@ impl CPU {
             let v = self.load16(addr) as i16;
             self.load = (rt, v as u32);
         } else {
-            self.exception(Exception::LoadAddressError);
+            self.address_error(Exception::LoadAddressError, addr as u32);
         }
     }
 
@@ -642,22 +1265,18 @@ impl CPU {
             let v = self.load16(addr);
             self.load = (rt, v as u32);
         } else {
-            self.exception(Exception::LoadAddressError);
+            self.address_error(Exception::LoadAddressError, addr as u32);
         }
     }
 
     fn op_sh(&mut self, imm_se: u32, rt: usize, rs: usize) {
-        if self.sr & 0x10000 != 0 {
-            println!("Ignoring store while cache is isolated");
-            return;
-        }
         let v = (self.r[rt] & 0xffff) as u16;
         let i = self.r[rs].wrapping_add(imm_se) as usize;
 
         if Self::check_alignment(i, 2) {
             self.store16(i, v);
         } else {
-            self.exception(Exception::StoreAddressError);
+            self.address_error(Exception::StoreAddressError, i as u32);
         }
     }
 
@@ -673,7 +1292,7 @@ impl CPU {
             let v = self.load32(addr);
             self.load = (rt, v);
         } else {
-            self.exception(Exception::StoreAddressError);
+            self.address_error(Exception::LoadAddressError, addr as u32);
         }
     }
 
@@ -689,7 +1308,7 @@ impl CPU {
             0 => (cur_v & 0x00ff_ffff) | (aligned_word << 24),
             1 => (cur_v & 0x0000_ffff) | (aligned_word << 16),
             2 => (cur_v & 0x0000_00ff) | (aligned_word << 8),
-            3 => (cur_v & 0x0000_0000) | aligned_word,
+            3 => aligned_word,
             _ => unreachable!(),
         };
 
@@ -705,7 +1324,7 @@ impl CPU {
         let aligned_word = self.load32(aligned_addr as usize);
 
         let v = match addr & 3 {
-            0 => (cur_v & 0x0000_0000) | aligned_word,
+            0 => aligned_word,
             1 => (cur_v & 0xff00_0000) | (aligned_word >> 8),
             2 => (cur_v & 0xffff_0000) | (aligned_word >> 16),
             3 => (cur_v & 0xffff_ff00) | (aligned_word >> 24),
@@ -717,18 +1336,48 @@ impl CPU {
 
     // Store Word
     fn op_sw(&mut self, imm_se: u32, rt: usize, rs: usize) {
-        if self.sr & 0x10000 != 0 {
-            println!("ignoring store while cache is isolated");
+        let addr = self.r[rs].wrapping_add(imm_se) as usize;
+        let v = self.r[rt];
+
+        if Self::check_alignment(addr, 4) {
+            self.store32(addr, v);
+        } else {
+            self.address_error(Exception::StoreAddressError, addr as u32);
+        }
+    }
+
+    fn op_lwc2(&mut self, imm_se: u32, rt: usize, rs: usize) {
+        if self.sr & (1 << 30) == 0 {
+            self.exception(Exception::CoprocessorError);
             return;
         }
 
         let addr = self.r[rs].wrapping_add(imm_se) as usize;
-        let v = self.r[rt];
+
+        if Self::check_alignment(addr, 4) {
+            let v = self.load32(addr);
+
+            if self.data_break == 0 && self.bus_exception.is_none() {
+                self.gte.set_data(rt, v);
+            }
+        } else {
+            self.address_error(Exception::LoadAddressError, addr as u32);
+        }
+    }
+
+    fn op_swc2(&mut self, imm_se: u32, rt: usize, rs: usize) {
+        if self.sr & (1 << 30) == 0 {
+            self.exception(Exception::CoprocessorError);
+            return;
+        }
+
+        let addr = self.r[rs].wrapping_add(imm_se) as usize;
+        let v = self.gte.data(rt);
 
         if Self::check_alignment(addr, 4) {
             self.store32(addr, v);
         } else {
-            self.exception(Exception::StoreAddressError);
+            self.address_error(Exception::StoreAddressError, addr as u32);
         }
     }
 
@@ -737,7 +1386,8 @@ impl CPU {
         let v = self.r[rt];
 
         let aligned_addr = (addr & !3) as usize;
-        let cur_mem = self.load32(aligned_addr);
+        // Only the store counts as an access for breakpoints and watchpoints
+        let cur_mem = self.bus.load32(aligned_addr).unwrap_or(OPEN_BUS);
 
         let mem = match addr & 3 {
             0 => (cur_mem & 0xffff_ff00) | (v >> 24),
@@ -755,7 +1405,7 @@ impl CPU {
         let v = self.r[rt];
 
         let aligned_addr = (addr & !3) as usize;
-        let cur_mem = self.load32(aligned_addr);
+        let cur_mem = self.bus.load32(aligned_addr).unwrap_or(OPEN_BUS);
 
         let mem = match addr & 3 {
             0 => cur_mem | v,
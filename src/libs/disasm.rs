@@ -0,0 +1,262 @@
+use std::fmt;
+
+use crate::libs::map::opcode::Instruction;
+
+pub const REGISTERS: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+    "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp",
+    "ra",
+];
+
+const GTE_DATA: [&str; 32] = [
+    "vxy0", "vz0", "vxy1", "vz1", "vxy2", "vz2", "rgbc", "otz", "ir0", "ir1", "ir2", "ir3", "sxy0",
+    "sxy1", "sxy2", "sxyp", "sz0", "sz1", "sz2", "sz3", "rgb0", "rgb1", "rgb2", "res1", "mac0",
+    "mac1", "mac2", "mac3", "irgb", "orgb", "lzcs", "lzcr",
+];
+
+const GTE_CONTROL: [&str; 32] = [
+    "rt11rt12", "rt13rt21", "rt22rt23", "rt31rt32", "rt33", "trx", "try", "trz", "l11l12",
+    "l13l21", "l22l23", "l31l32", "l33", "rbk", "gbk", "bbk", "lr1lr2", "lr3lg1", "lg2lg3",
+    "lb1lb2", "lb3", "rfc", "gfc", "bfc", "ofx", "ofy", "h", "dqa", "dqb", "zsf3", "zsf4", "flag",
+];
+
+fn cop0_register(n: usize) -> String {
+    let name = match n {
+        3 => "bpc",
+        5 => "bda",
+        6 => "jumpdest",
+        7 => "dcic",
+        8 => "badvaddr",
+        9 => "bdam",
+        11 => "bpcm",
+        12 => "sr",
+        13 => "cause",
+        14 => "epc",
+        15 => "prid",
+        _ => return format!("cop0r{}", n),
+    };
+
+    name.to_string()
+}
+
+fn gte_command(command: u32) -> Option<&'static str> {
+    let name = match command & 0x3f {
+        0x01 => "rtps",
+        0x06 => "nclip",
+        0x0c => "op",
+        0x10 => "dpcs",
+        0x11 => "intpl",
+        0x12 => "mvmva",
+        0x13 => "ncds",
+        0x14 => "cdp",
+        0x16 => "ncdt",
+        0x1b => "nccs",
+        0x1c => "cc",
+        0x1e => "ncs",
+        0x20 => "nct",
+        0x28 => "sqr",
+        0x29 => "dcpl",
+        0x2a => "dpct",
+        0x2d => "avsz3",
+        0x2e => "avsz4",
+        0x30 => "rtpt",
+        0x3d => "gpf",
+        0x3e => "gpl",
+        0x3f => "ncct",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Signed hexadecimal immediate
+fn signed(val: u32) -> String {
+    let val = val as i32;
+
+    match val < 0 {
+        true => format!("-0x{:x}", val.unsigned_abs()),
+        false => format!("0x{:x}", val),
+    }
+}
+
+/// Disassemble `i`. Branch and jump targets are absolute when the address of
+/// the instruction is known, relative to it otherwise.
+pub fn disassemble(i: Instruction, pc: Option<u32>) -> String {
+    let (mnemonic, operands) = decode(i, pc);
+
+    match operands.is_empty() {
+        true => mnemonic.to_string(),
+        false => format!("{:<7} {}", mnemonic, operands),
+    }
+}
+
+fn decode(i: Instruction, pc: Option<u32>) -> (&'static str, String) {
+    let r = |n: usize| REGISTERS[n];
+    let (rs, rt, rd) = (r(i.rs()), r(i.rt()), r(i.rd()));
+
+    let three = |m| (m, format!("{}, {}, {}", rd, rs, rt));
+    let shift = |m| (m, format!("{}, {}, {}", rd, rt, i.imm5()));
+    let shiftv = |m| (m, format!("{}, {}, {}", rd, rt, rs));
+    let imm_se = |m| (m, format!("{}, {}, {}", rt, rs, signed(i.imm_se())));
+    let imm = |m| (m, format!("{}, {}, 0x{:x}", rt, rs, i.imm()));
+    let memory = |m, reg: &str| (m, format!("{}, {}({})", reg, signed(i.imm_se()), rs));
+
+    let target = || {
+        let offset = (i.imm_se() << 2).wrapping_add(4);
+
+        match pc {
+            Some(pc) => format!("0x{:08x}", pc.wrapping_add(offset)),
+            None => match offset as i32 {
+                o if o < 0 => format!("pc-0x{:x}", o.unsigned_abs()),
+                o => format!("pc+0x{:x}", o),
+            },
+        }
+    };
+
+    match i.primary() {
+        0x00 => match i.secondary() {
+            0x00 if i.0 == 0 => ("nop", String::new()),
+            0x00 => shift("sll"),
+            0x02 => shift("srl"),
+            0x03 => shift("sra"),
+            0x04 => shiftv("sllv"),
+            0x06 => shiftv("srlv"),
+            0x07 => shiftv("srav"),
+            0x08 => ("jr", rs.to_string()),
+            0x09 if i.rd() == 31 => ("jalr", rs.to_string()),
+            0x09 => ("jalr", format!("{}, {}", rd, rs)),
+            0x0c => ("syscall", code(i)),
+            0x0d => ("break", code(i)),
+            0x10 => ("mfhi", rd.to_string()),
+            0x11 => ("mthi", rs.to_string()),
+            0x12 => ("mflo", rd.to_string()),
+            0x13 => ("mtlo", rs.to_string()),
+            0x18 => ("mult", format!("{}, {}", rs, rt)),
+            0x19 => ("multu", format!("{}, {}", rs, rt)),
+            0x1a => ("div", format!("{}, {}", rs, rt)),
+            0x1b => ("divu", format!("{}, {}", rs, rt)),
+            0x21 | 0x25 if i.rt() == 0 => ("move", format!("{}, {}", rd, rs)),
+            0x21 | 0x25 if i.rs() == 0 => ("move", format!("{}, {}", rd, rt)),
+            0x20 => three("add"),
+            0x21 => three("addu"),
+            0x22 => three("sub"),
+            0x23 => three("subu"),
+            0x24 => three("and"),
+            0x25 => three("or"),
+            0x26 => three("xor"),
+            0x27 => three("nor"),
+            0x2a => three("slt"),
+            0x2b => three("sltu"),
+            _ => word(i),
+        },
+        0x01 => {
+            let link = i.rt() >> 1 == 0b1000;
+            let mnemonic = match (i.rt() & 1 != 0, link) {
+                (false, false) => "bltz",
+                (true, false) => "bgez",
+                (false, true) => "bltzal",
+                (true, true) if i.rs() == 0 => return ("bal", target()),
+                (true, true) => "bgezal",
+            };
+
+            (mnemonic, format!("{}, {}", rs, target()))
+        }
+        0x02 | 0x03 => {
+            let mnemonic = if i.primary() == 0x02 { "j" } else { "jal" };
+            let addr = i.imm_jmp() << 2;
+            let addr = pc.map_or(addr, |pc| (pc.wrapping_add(4) & 0xf000_0000) | addr);
+
+            (mnemonic, format!("0x{:08x}", addr))
+        }
+        0x04 if i.rs() == 0 && i.rt() == 0 => ("b", target()),
+        0x04 if i.rt() == 0 => ("beqz", format!("{}, {}", rs, target())),
+        0x04 => ("beq", format!("{}, {}, {}", rs, rt, target())),
+        0x05 if i.rt() == 0 => ("bnez", format!("{}, {}", rs, target())),
+        0x05 => ("bne", format!("{}, {}, {}", rs, rt, target())),
+        0x06 => ("blez", format!("{}, {}", rs, target())),
+        0x07 => ("bgtz", format!("{}, {}", rs, target())),
+        0x08 => imm_se("addi"),
+        0x09 if i.rs() == 0 => ("li", format!("{}, {}", rt, signed(i.imm_se()))),
+        0x09 => imm_se("addiu"),
+        0x0a => imm_se("slti"),
+        0x0b => imm_se("sltiu"),
+        0x0c => imm("andi"),
+        0x0d if i.rs() == 0 => ("li", format!("{}, 0x{:x}", rt, i.imm())),
+        0x0d => imm("ori"),
+        0x0e => imm("xori"),
+        0x0f => ("lui", format!("{}, 0x{:x}", rt, i.imm())),
+        0x10 => match i.rs() {
+            0b00000 => ("mfc0", format!("{}, {}", rt, cop0_register(i.rd()))),
+            0b00100 => ("mtc0", format!("{}, {}", rt, cop0_register(i.rd()))),
+            0x10 if i.secondary() == 0b010000 => ("rfe", String::new()),
+            _ => word(i),
+        },
+        0x12 if i.rs() & 0x10 != 0 => match gte_command(i.0) {
+            Some(name) => (name, gte_flags(i.0)),
+            None => word(i),
+        },
+        0x12 => match i.rs() {
+            0b00000 => ("mfc2", format!("{}, {}", rt, GTE_DATA[i.rd()])),
+            0b00010 => ("cfc2", format!("{}, {}", rt, GTE_CONTROL[i.rd()])),
+            0b00100 => ("mtc2", format!("{}, {}", rt, GTE_DATA[i.rd()])),
+            0b00110 => ("ctc2", format!("{}, {}", rt, GTE_CONTROL[i.rd()])),
+            _ => word(i),
+        },
+        0x20 => memory("lb", rt),
+        0x21 => memory("lh", rt),
+        0x22 => memory("lwl", rt),
+        0x23 => memory("lw", rt),
+        0x24 => memory("lbu", rt),
+        0x25 => memory("lhu", rt),
+        0x26 => memory("lwr", rt),
+        0x28 => memory("sb", rt),
+        0x29 => memory("sh", rt),
+        0x2a => memory("swl", rt),
+        0x2b => memory("sw", rt),
+        0x2e => memory("swr", rt),
+        0x32 => memory("lwc2", GTE_DATA[i.rt()]),
+        0x3a => memory("swc2", GTE_DATA[i.rt()]),
+        _ => word(i),
+    }
+}
+
+/// Code field of syscall and break, only shown when set
+fn code(i: Instruction) -> String {
+    match (i.0 >> 6) & 0xf_ffff {
+        0 => String::new(),
+        code => format!("0x{:x}", code),
+    }
+}
+
+/// Shift (sf) and saturation (lm) flags of a GTE command, plus the operands
+/// of MVMVA
+fn gte_flags(command: u32) -> String {
+    let mut flags = Vec::new();
+
+    if command & (1 << 19) != 0 {
+        flags.push("sf".to_string());
+    }
+
+    if command & 0x3f == 0x12 {
+        flags.push(format!("mx={}", (command >> 17) & 3));
+        flags.push(format!("v={}", (command >> 15) & 3));
+        flags.push(format!("cv={}", (command >> 13) & 3));
+    }
+
+    if command & (1 << 10) != 0 {
+        flags.push("lm".to_string());
+    }
+
+    flags.join(", ")
+}
+
+/// Undecodable words are shown as data
+fn word(i: Instruction) -> (&'static str, String) {
+    (".word", format!("0x{:08x}", i.0))
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", disassemble(*self, None))
+    }
+}
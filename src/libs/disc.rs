@@ -0,0 +1,438 @@
+use std::path::Path;
+
+/// Size of a raw CD sector, including the sync pattern, header and ECC
+pub const SECTOR_SIZE: usize = 2352;
+
+/// Number of sectors per second of audio
+pub const SECTORS_PER_SECOND: u32 = 75;
+
+/// The first track starts after a 2 second pregap
+const PREGAP: u32 = 2 * SECTORS_PER_SECOND;
+
+/// Returned for pregap sectors that aren't stored in the image
+static SILENCE: [u8; SECTOR_SIZE] = [0; SECTOR_SIZE];
+
+/// Position on the disc in minutes, seconds and frames (sectors)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Msf {
+    pub m: u8,
+    pub s: u8,
+    pub f: u8,
+}
+
+impl Msf {
+    pub fn new(m: u8, s: u8, f: u8) -> Msf {
+        Msf { m, s, f }
+    }
+
+    pub fn from_bcd(m: u8, s: u8, f: u8) -> Msf {
+        Msf::new(from_bcd(m), from_bcd(s), from_bcd(f))
+    }
+
+    /// Absolute sector index, 00:00:00 being sector 0
+    pub fn sector_index(self) -> u32 {
+        (self.m as u32 * 60 + self.s as u32) * SECTORS_PER_SECOND + self.f as u32
+    }
+
+    pub fn from_sector_index(index: u32) -> Msf {
+        let f = index % SECTORS_PER_SECOND;
+        let s = (index / SECTORS_PER_SECOND) % 60;
+        let m = index / SECTORS_PER_SECOND / 60;
+
+        Msf::new(m as u8, s as u8, f as u8)
+    }
+
+    /// Logical block address, 00:02:00 being LBA 0
+    pub fn from_lba(lba: u32) -> Msf {
+        Msf::from_sector_index(lba + PREGAP)
+    }
+
+    pub fn lba(self) -> Option<u32> {
+        self.sector_index().checked_sub(PREGAP)
+    }
+
+    /// Parse a "mm:ss:ff" CUE sheet timestamp
+    fn parse(s: &str) -> Option<Msf> {
+        let mut fields = s.split(':').map(|f| f.parse::<u8>().ok());
+
+        let m = fields.next()??;
+        let s = fields.next()??;
+        let f = fields.next()??;
+
+        match fields.next().is_none() && s < 60 && f < 75 {
+            true => Some(Msf::new(m, s, f)),
+            false => None,
+        }
+    }
+
+    pub fn to_bcd(self) -> [u8; 3] {
+        [to_bcd(self.m), to_bcd(self.s), to_bcd(self.f)]
+    }
+}
+
+pub fn from_bcd(val: u8) -> u8 {
+    (val >> 4) * 10 + (val & 0xf)
+}
+
+pub fn to_bcd(val: u8) -> u8 {
+    ((val / 10) << 4) | (val % 10)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TrackType {
+    /// Data tracks with 2352 byte sectors
+    Mode1,
+    Mode2,
+    Audio,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Track {
+    pub number: u8,
+    pub kind: TrackType,
+    /// First sector of the track, pregap excluded
+    pub start: Msf,
+    /// Length in sectors
+    pub length: u32,
+    /// Offset of the first sector in the image data
+    pub offset: usize,
+    /// Pregap sectors before `start` that are stored in the image (INDEX 00)
+    pub pregap: u32,
+    /// Pregap sectors before those that aren't stored in the image (PREGAP)
+    pub silent_pregap: u32,
+}
+
+/// Console region, read from the license string of the disc
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Region {
+    Japan,
+    NorthAmerica,
+    Europe,
+}
+
+/// A CD image made of raw 2352 byte sectors
+pub struct Disc {
+    data: Vec<u8>,
+    tracks: Vec<Track>,
+}
+
+impl Disc {
+    /// Single data track image, as produced by most dumping tools for
+    /// games without audio tracks
+    pub fn from_bin(data: Vec<u8>) -> Result<Disc, String> {
+        if data.is_empty() || !data.len().is_multiple_of(SECTOR_SIZE) {
+            return Err(format!(
+                "Disc image size {} is not a multiple of {}",
+                data.len(),
+                SECTOR_SIZE
+            ));
+        }
+
+        let track = Track {
+            number: 1,
+            kind: TrackType::Mode2,
+            start: Msf::from_sector_index(PREGAP),
+            length: (data.len() / SECTOR_SIZE) as u32,
+            offset: 0,
+            pregap: 0,
+            silent_pregap: 0,
+        };
+
+        Ok(Disc {
+            data,
+            tracks: vec![track],
+        })
+    }
+
+    /// Open a CUE sheet or a single track BIN image
+    pub fn open(path: &Path) -> Result<Disc, String> {
+        let read = |path: &Path| {
+            std::fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))
+        };
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("cue") => {
+                let cue = String::from_utf8_lossy(&read(path)?).into_owned();
+                let dir = path.parent().unwrap_or(Path::new(""));
+
+                Disc::from_cue(&cue, |file| read(&dir.join(file)))
+            }
+            _ => Disc::from_bin(read(path)?),
+        }
+    }
+
+    /// Parse a CUE sheet, `read_file` loads the files it references
+    pub fn from_cue<F>(cue: &str, mut read_file: F) -> Result<Disc, String>
+    where
+        F: FnMut(&str) -> Result<Vec<u8>, String>,
+    {
+        let sheet = CueSheet::parse(cue)?;
+
+        let mut data = Vec::new();
+        // Offset and sector count of each file
+        let mut files = Vec::new();
+
+        for name in &sheet.files {
+            let contents = read_file(name)?;
+
+            if !contents.len().is_multiple_of(SECTOR_SIZE) {
+                return Err(format!(
+                    "{} size {} is not a multiple of {}",
+                    name,
+                    contents.len(),
+                    SECTOR_SIZE
+                ));
+            }
+
+            files.push((data.len(), (contents.len() / SECTOR_SIZE) as u32));
+            data.extend(contents);
+        }
+
+        let mut tracks: Vec<Track> = Vec::new();
+        // Absolute position of the current file on the disc
+        let mut file_base = PREGAP;
+        let mut file_index = 0;
+        let mut silent_total = 0;
+
+        for (i, entry) in sheet.tracks.iter().enumerate() {
+            while file_index < entry.file {
+                file_base += files[file_index].1;
+                file_index += 1;
+            }
+
+            let (file_offset, file_sectors) = files[entry.file];
+
+            let index1 = entry
+                .index1
+                .ok_or_else(|| format!("Track {} has no INDEX 01", entry.number))?;
+            let index0 = entry.index0.unwrap_or(index1);
+
+            // The end of the track is the start of the next one in the same
+            // file, or the end of the file
+            let end = match sheet.tracks.get(i + 1) {
+                Some(next) if next.file == entry.file => {
+                    next.index0.or(next.index1).unwrap_or(file_sectors)
+                }
+                _ => file_sectors,
+            };
+
+            if index0 > index1 || index1 > end || end > file_sectors {
+                return Err(format!("Invalid indices for track {}", entry.number));
+            }
+
+            silent_total += entry.pregap;
+
+            tracks.push(Track {
+                number: entry.number,
+                kind: entry.kind,
+                start: Msf::from_sector_index(file_base + silent_total + index1),
+                length: end - index1,
+                offset: file_offset + index1 as usize * SECTOR_SIZE,
+                pregap: index1 - index0,
+                silent_pregap: entry.pregap,
+            });
+        }
+
+        if tracks.is_empty() {
+            return Err("CUE sheet has no tracks".to_string());
+        }
+
+        Ok(Disc { data, tracks })
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn track(&self, number: u8) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.number == number)
+    }
+
+    /// Position right after the last track
+    pub fn lead_out(&self) -> Msf {
+        let last = self
+            .tracks
+            .last()
+            .map_or(PREGAP, |t| t.start.sector_index() + t.length);
+
+        Msf::from_sector_index(last)
+    }
+
+    /// Raw 2352 byte sector at `msf`, pregaps included
+    pub fn sector(&self, msf: Msf) -> Option<&[u8]> {
+        let index = msf.sector_index();
+
+        let track = self.tracks.iter().find(|t| {
+            let start = t.start.sector_index();
+            let first = start - t.pregap - t.silent_pregap;
+
+            index >= first && index < start + t.length
+        })?;
+
+        let start = track.start.sector_index();
+
+        if index + track.pregap < start {
+            return Some(&SILENCE);
+        }
+
+        let offset =
+            track.offset as isize + (index as isize - start as isize) * SECTOR_SIZE as isize;
+        let offset = offset as usize;
+
+        self.data.get(offset..offset + SECTOR_SIZE)
+    }
+
+    pub fn sector_lba(&self, lba: u32) -> Option<&[u8]> {
+        self.sector(Msf::from_lba(lba))
+    }
+
+    /// The license string is stored in the system area, sector 4 of the
+    /// first track
+    pub fn region(&self) -> Option<Region> {
+        let first = self.tracks.first()?;
+        let sector = self.sector(Msf::from_sector_index(first.start.sector_index() + 4))?;
+        let license = &sector[24..24 + 0x50];
+
+        let contains = |s: &[u8]| license.windows(s.len()).any(|w| w == s);
+
+        if contains(b"Inc.") {
+            Some(Region::Japan)
+        } else if contains(b"Amer") {
+            Some(Region::NorthAmerica)
+        } else if contains(b"Euro") {
+            Some(Region::Europe)
+        } else {
+            None
+        }
+    }
+}
+
+/// Track entry of a CUE sheet, positions are relative to its file
+struct CueTrack {
+    number: u8,
+    kind: TrackType,
+    file: usize,
+    index0: Option<u32>,
+    index1: Option<u32>,
+    pregap: u32,
+}
+
+struct CueSheet {
+    files: Vec<String>,
+    tracks: Vec<CueTrack>,
+}
+
+impl CueSheet {
+    fn parse(cue: &str) -> Result<CueSheet, String> {
+        let mut sheet = CueSheet {
+            files: Vec::new(),
+            tracks: Vec::new(),
+        };
+
+        for (line_number, line) in cue.lines().enumerate() {
+            let error = |msg: &str| format!("CUE line {}: {}", line_number + 1, msg);
+            let tokens = tokenize(line);
+
+            let Some(command) = tokens.first() else {
+                continue;
+            };
+
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => {
+                    let name = tokens.get(1).ok_or_else(|| error("missing file name"))?;
+
+                    match tokens.get(2).map(|t| t.to_ascii_uppercase()) {
+                        Some(kind) if kind == "BINARY" => (),
+                        _ => return Err(error("only BINARY files are supported")),
+                    }
+
+                    sheet.files.push(name.clone());
+                }
+                "TRACK" => {
+                    let file = match sheet.files.len() {
+                        0 => return Err(error("TRACK before FILE")),
+                        n => n - 1,
+                    };
+
+                    let number = tokens
+                        .get(1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(|| error("invalid track number"))?;
+
+                    let kind = match tokens.get(2).map(|t| t.to_ascii_uppercase()).as_deref() {
+                        Some("MODE1/2352") => TrackType::Mode1,
+                        Some("MODE2/2352") => TrackType::Mode2,
+                        Some("AUDIO") => TrackType::Audio,
+                        _ => return Err(error("unsupported track type")),
+                    };
+
+                    sheet.tracks.push(CueTrack {
+                        number,
+                        kind,
+                        file,
+                        index0: None,
+                        index1: None,
+                        pregap: 0,
+                    });
+                }
+                "INDEX" | "PREGAP" => {
+                    let track = sheet
+                        .tracks
+                        .last_mut()
+                        .ok_or_else(|| error("INDEX before TRACK"))?;
+
+                    let is_pregap = command.eq_ignore_ascii_case("PREGAP");
+
+                    let (number, msf) = match is_pregap {
+                        true => (None, tokens.get(1)),
+                        false => (tokens.get(1), tokens.get(2)),
+                    };
+
+                    let msf = msf
+                        .and_then(|msf| Msf::parse(msf))
+                        .ok_or_else(|| error("invalid timestamp"))?
+                        .sector_index();
+
+                    match number.map(|n| n.parse::<u8>()) {
+                        None => track.pregap = msf,
+                        Some(Ok(0)) => track.index0 = Some(msf),
+                        Some(Ok(1)) => track.index1 = Some(msf),
+                        // Subindices aren't used by the drive
+                        Some(Ok(_)) => (),
+                        Some(Err(_)) => return Err(error("invalid index")),
+                    }
+                }
+                // Metadata
+                _ => (),
+            }
+        }
+
+        Ok(sheet)
+    }
+}
+
+/// Split a CUE sheet line in whitespace separated tokens, double quotes
+/// group tokens containing spaces
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in line.trim().chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
@@ -23,7 +23,7 @@ impl Dma {
         }
     }
 
-    fn irq(&self) -> bool {
+    pub fn irq(&self) -> bool {
         let channel_irq = self.channel_irq_flags & self.channel_irq_en;
         self.force_irq || (self.irq_en && channel_irq != 0)
     }
@@ -45,10 +45,23 @@ impl Dma {
         self.force_irq = (val >> 15) & 1 != 0;
         self.channel_irq_en = ((val >> 16) & 0x7f) as u8;
         self.irq_en = (val >> 23) & 1 != 0;
-        let ack = ((val >> 24) & 0x3f) as u8;
+        let ack = ((val >> 24) & 0x7f) as u8;
         self.channel_irq_flags &= !ack;
     }
 
+    /// Flag the end of a transfer on `port`. Returns true if this raised the
+    /// DMA interrupt line.
+    pub fn transfer_done(&mut self, port: Port) -> bool {
+        let prev = self.irq();
+        let bit = 1 << (port as u8);
+
+        if self.channel_irq_en & bit != 0 {
+            self.channel_irq_flags |= bit;
+        }
+
+        !prev && self.irq()
+    }
+
     pub fn channel(&self, port: Port) -> &Channel {
         &self.channels[port as usize]
     }
@@ -0,0 +1,99 @@
+use std::fmt;
+
+use crate::libs::disasm;
+use crate::libs::map::opcode::Instruction;
+
+/// Access the bus couldn't complete
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BusError {
+    /// No device at `addr`. `val` is the value of a store.
+    Unmapped {
+        addr: u32,
+        width: u32,
+        val: Option<u32>,
+    },
+    /// The device at `addr` doesn't emulate this register or access width
+    UnhandledRegister {
+        addr: u32,
+        width: u32,
+        val: Option<u32>,
+    },
+    /// DMA transfer set up in a way that isn't emulated
+    InvalidDma { port: u8, reason: &'static str },
+}
+
+/// Reason the emulator couldn't run an instruction faithfully
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmuError {
+    Bus(BusError),
+    UnimplementedOpcode {
+        pc: u32,
+        opcode: u32,
+    },
+    /// The HLE BIOS has no handler for exception `code` raised at `epc`
+    UnhandledException {
+        epc: u32,
+        code: u32,
+    },
+    /// The HLE BIOS can't go on from `pc`
+    Kernel {
+        pc: u32,
+        reason: &'static str,
+    },
+}
+
+impl From<BusError> for EmuError {
+    fn from(e: BusError) -> EmuError {
+        EmuError::Bus(e)
+    }
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let access =
+            |f: &mut fmt::Formatter, what, addr: u32, width: u32, val: Option<u32>| match val {
+                Some(val) => write!(
+                    f,
+                    "{} store{} at {:08x} with val {:08x}",
+                    what,
+                    width * 8,
+                    addr,
+                    val
+                ),
+                None => write!(f, "{} load{} at {:08x}", what, width * 8, addr),
+            };
+
+        match *self {
+            BusError::Unmapped { addr, width, val } => access(f, "Unmapped", addr, width, val),
+            BusError::UnhandledRegister { addr, width, val } => {
+                access(f, "Unhandled", addr, width, val)
+            }
+            BusError::InvalidDma { port, reason } => {
+                write!(f, "Invalid DMA on port {}: {}", port, reason)
+            }
+        }
+    }
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmuError::Bus(e) => write!(f, "{}", e),
+            EmuError::UnimplementedOpcode { pc, opcode } => write!(
+                f,
+                "Unimplemented opcode {:08x} ({}) at {:08x}",
+                opcode,
+                disasm::disassemble(Instruction(opcode), Some(pc)),
+                pc
+            ),
+            EmuError::UnhandledException { epc, code } => write!(
+                f,
+                "Unhandled exception {:x} at {:08x} in the HLE BIOS",
+                code, epc
+            ),
+            EmuError::Kernel { pc, reason } => {
+                write!(f, "HLE BIOS at {:08x}: {}", pc, reason)
+            }
+        }
+    }
+}
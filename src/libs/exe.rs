@@ -0,0 +1,60 @@
+use std::path::Path;
+
+/// Size of the header preceding the text section
+const HEADER_SIZE: usize = 0x800;
+
+/// Address of the shell in RAM, the BIOS jumps there once the kernel is set
+/// up. Side-loaded executables replace it.
+pub const SHELL_ENTRY: u32 = 0x8003_0000;
+
+/// PS-X EXE executable
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exe {
+    pub pc: u32,
+    pub gp: u32,
+    /// Load address of the text section
+    pub text_addr: u32,
+    pub text: Vec<u8>,
+    /// Region cleared before running the executable
+    pub bss_addr: u32,
+    pub bss_size: u32,
+    /// Initial stack and frame pointer, 0 leaves the registers unchanged
+    pub sp: u32,
+}
+
+impl Exe {
+    pub fn open(path: &Path) -> Result<Exe, String> {
+        let data =
+            std::fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+
+        Exe::parse(&data)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Exe, String> {
+        if data.len() < HEADER_SIZE || &data[..8] != b"PS-X EXE" {
+            return Err("Not a PS-X EXE file".to_string());
+        }
+
+        let word = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+        let text_size = word(0x1c) as usize;
+        let text = data
+            .get(HEADER_SIZE..HEADER_SIZE + text_size)
+            .ok_or_else(|| format!("Text section of {} bytes is truncated", text_size))?;
+
+        let sp = match word(0x30) {
+            0 => 0,
+            base => base.wrapping_add(word(0x34)),
+        };
+
+        Ok(Exe {
+            pc: word(0x10),
+            gp: word(0x14),
+            text_addr: word(0x18),
+            text: text.to_vec(),
+            bss_addr: word(0x28),
+            bss_size: word(0x2c),
+            sp,
+        })
+    }
+}
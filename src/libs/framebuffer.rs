@@ -0,0 +1,202 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::libs::gpu::{DisplayDepth, Gpu, VRAM_HEIGHT, VRAM_WIDTH};
+
+/// Part of VRAM captured by a dump
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DumpArea {
+    /// The display area configured through GP1
+    Display,
+    /// The whole 1024x512 VRAM as 15-bit pixels
+    Vram,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Result<ImageFormat, String> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("png") => Ok(ImageFormat::Png),
+            _ => Err(format!("Unknown image format for {}", path.display())),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// 24-bit RGB image
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+impl Image {
+    /// Convert a region of VRAM to RGB
+    pub fn capture(gpu: &Gpu, area: DumpArea) -> Image {
+        let vram = gpu.vram();
+
+        let (x, y, width, height, depth) = match area {
+            DumpArea::Vram => (0, 0, VRAM_WIDTH, VRAM_HEIGHT, DisplayDepth::D15Bits),
+            DumpArea::Display => {
+                let display = gpu.display_area();
+
+                (
+                    display.x,
+                    display.y,
+                    display.width,
+                    display.height,
+                    display.depth,
+                )
+            }
+        };
+
+        let mut rgb = Vec::with_capacity(width * height * 3);
+
+        for row in 0..height {
+            let line = ((y + row) % VRAM_HEIGHT) * VRAM_WIDTH;
+
+            match depth {
+                DisplayDepth::D15Bits => {
+                    for col in 0..width {
+                        let pixel = vram[line + (x + col) % VRAM_WIDTH];
+
+                        rgb.extend([pixel, pixel >> 5, pixel >> 10].map(|c| {
+                            let c = (c & 0x1f) as u8;
+                            (c << 3) | (c >> 2)
+                        }));
+                    }
+                }
+                DisplayDepth::D24Bits => {
+                    // Pixels are packed as 3 bytes across the 16-bit VRAM
+                    // halfwords
+                    let byte = |offset: usize| {
+                        let pixel = vram[line + (x + offset / 2) % VRAM_WIDTH];
+                        (pixel >> ((offset & 1) * 8)) as u8
+                    };
+
+                    for col in 0..width {
+                        rgb.extend([0, 1, 2].map(|i| byte(col * 3 + i)));
+                    }
+                }
+            }
+        }
+
+        Image { width, height, rgb }
+    }
+
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.rgb)
+    }
+
+    /// Write a PNG with uncompressed deflate blocks, good enough for
+    /// regression screenshots without pulling in a compression library
+    pub fn write_png<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+        png_chunk(out, b"IHDR", &header)?;
+
+        // Each scanline starts with its filter type, 0 for none
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for line in self.rgb.chunks(self.width * 3).take(self.height) {
+            raw.push(0);
+            raw.extend(line);
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(0xffff).peekable();
+
+        if blocks.peek().is_none() {
+            zlib.extend([1, 0, 0, 0xff, 0xff]);
+        }
+
+        while let Some(block) = blocks.next() {
+            let last = blocks.peek().is_none() as u8;
+            let len = block.len() as u16;
+
+            zlib.push(last);
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend(block);
+        }
+
+        zlib.extend(adler32(&raw).to_be_bytes());
+        png_chunk(out, b"IDAT", &zlib)?;
+
+        png_chunk(out, b"IEND", &[])
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let format = ImageFormat::from_path(path)?;
+        let error = |e: std::io::Error| format!("Couldn't write {}: {}", path.display(), e);
+
+        let mut out = BufWriter::new(File::create(path).map_err(error)?);
+
+        match format {
+            ImageFormat::Ppm => self.write_ppm(&mut out),
+            ImageFormat::Png => self.write_png(&mut out),
+        }
+        .and_then(|_| out.flush())
+        .map_err(error)
+    }
+}
+
+/// Capture `area` and write it to `path`, the format is picked from the
+/// file extension
+pub fn dump(gpu: &Gpu, area: DumpArea, path: &Path) -> Result<(), String> {
+    Image::capture(gpu, area).save(path)
+}
+
+fn png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn crc32<'a>(data: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
@@ -0,0 +1,489 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::libs::cpu::CPU;
+use crate::libs::watch::{Access, Watchpoint};
+
+/// Registers in the order of the `g` packet: the GPRs, sr, lo, hi,
+/// badvaddr, cause, pc, the 32 FPU registers with fcsr and fir, then epc
+const REGISTER_COUNT: usize = 73;
+const EPC: usize = 72;
+
+/// Instructions run between two checks for a break request from the client
+const INTERRUPT_CHECK: u32 = 0x10000;
+
+/// What the emulator should do after `GdbStub::poll`
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Running,
+    /// The client went away, keep running without the stub
+    Detached,
+    /// The client asked to stop the emulator
+    Killed,
+}
+
+enum Command {
+    Reply(String),
+    Resume { step: bool },
+    Detach,
+    Kill,
+}
+
+/// GDB remote serial protocol server. The target description makes
+/// `gdb-multiarch` pick the R3000 on its own:
+///
+/// `gdb-multiarch -ex "target remote localhost:<port>"`
+pub struct GdbStub {
+    stream: TcpStream,
+    input: VecDeque<u8>,
+    breakpoints: Vec<u32>,
+    /// Waiting for commands from the client
+    halted: bool,
+    /// Stop after the next instruction
+    stepping: bool,
+    /// A breakpoint on the address execution resumes from doesn't fire
+    resume_pc: Option<u32>,
+    last_stop: String,
+    polls: u32,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> GdbStub {
+        // Packets are small and latency matters more than throughput
+        let _ = stream.set_nodelay(true);
+
+        GdbStub {
+            stream,
+            input: VecDeque::new(),
+            breakpoints: Vec::new(),
+            halted: true,
+            stepping: false,
+            resume_pc: None,
+            last_stop: "S05".to_string(),
+            polls: 0,
+        }
+    }
+
+    /// Wait for a client on localhost
+    pub fn listen(port: u16) -> Result<GdbStub, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Couldn't listen on port {}: {}", port, e))?;
+
+        println!("Waiting for GDB on 127.0.0.1:{}", port);
+
+        let (stream, addr) = listener
+            .accept()
+            .map_err(|e| format!("Couldn't accept the GDB connection: {}", e))?;
+
+        println!("GDB connected from {}", addr);
+
+        Ok(GdbStub::new(stream))
+    }
+
+    /// Run before every instruction. Returns once the CPU may execute the
+    /// next instruction, serving the client while it is halted.
+    pub fn poll(&mut self, cpu: &mut CPU) -> Status {
+        match self.serve(cpu) {
+            Ok(status) => status,
+            Err(e) => {
+                println!("GDB connection lost: {}", e);
+                Status::Detached
+            }
+        }
+    }
+
+    fn serve(&mut self, cpu: &mut CPU) -> io::Result<Status> {
+        if !self.halted {
+            match self.stop_reason(cpu)? {
+                Some(reason) => {
+                    self.halted = true;
+                    self.send(&reason)?;
+                    self.last_stop = reason;
+                }
+                None => return Ok(Status::Running),
+            }
+        }
+
+        loop {
+            let packet = self.read_packet()?;
+
+            match self.command(cpu, &packet) {
+                Command::Reply(reply) => self.send(&reply)?,
+                Command::Resume { step } => {
+                    self.halted = false;
+                    self.stepping = step;
+                    self.resume_pc = Some(cpu.pc());
+                    // Watchpoints hit while halted don't count
+                    cpu.take_watch_hit();
+                    return Ok(Status::Running);
+                }
+                Command::Detach => {
+                    self.send("OK")?;
+                    cpu.take_watch_hit();
+                    return Ok(Status::Detached);
+                }
+                Command::Kill => return Ok(Status::Killed),
+            }
+        }
+    }
+
+    fn stop_reason(&mut self, cpu: &mut CPU) -> io::Result<Option<String>> {
+        if let Some(hit) = cpu.take_watch_hit() {
+            let kind = match hit.watchpoint.access {
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+                Access::ReadWrite => "awatch",
+            };
+
+            return Ok(Some(format!("T05{}:{:08x};", kind, hit.addr)));
+        }
+
+        if self.stepping {
+            return Ok(Some("S05".to_string()));
+        }
+
+        let pc = cpu.pc();
+
+        if self.resume_pc.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            return Ok(Some("S05".to_string()));
+        }
+
+        self.polls = self.polls.wrapping_add(1);
+
+        if self.polls.is_multiple_of(INTERRUPT_CHECK) && self.interrupted()? {
+            return Ok(Some("S02".to_string()));
+        }
+
+        Ok(None)
+    }
+
+    fn command(&mut self, cpu: &mut CPU, packet: &str) -> Command {
+        let reply = |s: &str| Command::Reply(s.to_string());
+        let (kind, args) = packet.split_at(packet.len().min(1));
+
+        match kind {
+            "?" => Command::Reply(self.last_stop.clone()),
+            "g" => Command::Reply(
+                (0..REGISTER_COUNT)
+                    .map(|n| hex32(register(cpu, n)))
+                    .collect(),
+            ),
+            "G" => {
+                for (n, chunk) in args.as_bytes().chunks(8).enumerate() {
+                    match std::str::from_utf8(chunk).ok().and_then(parse_hex32) {
+                        Some(val) => set_register(cpu, n, val),
+                        None => return reply("E01"),
+                    }
+                }
+                reply("OK")
+            }
+            "p" => match parse_hex(args) {
+                Some(n) if (n as usize) < REGISTER_COUNT => {
+                    Command::Reply(hex32(register(cpu, n as usize)))
+                }
+                _ => reply("E01"),
+            },
+            "P" => match args.split_once('=') {
+                Some((n, val)) => match (parse_hex(n), parse_hex32(val)) {
+                    (Some(n), Some(val)) if (n as usize) < REGISTER_COUNT => {
+                        set_register(cpu, n as usize, val);
+                        reply("OK")
+                    }
+                    _ => reply("E01"),
+                },
+                None => reply("E01"),
+            },
+            "m" => match parse_range(args) {
+                Some((addr, len)) => {
+                    let data: Vec<u8> = (0..len)
+                        .map_while(|i| cpu.bus().peek8(addr.wrapping_add(i) as usize))
+                        .collect();
+
+                    // A partial read is fine as long as something was read
+                    match data.is_empty() && len > 0 {
+                        true => reply("E01"),
+                        false => {
+                            Command::Reply(data.iter().map(|b| format!("{:02x}", b)).collect())
+                        }
+                    }
+                }
+                None => reply("E01"),
+            },
+            "M" => match args.split_once(':') {
+                Some((range, data)) => match (parse_range(range), parse_bytes(data)) {
+                    (Some((addr, len)), Some(bytes)) if bytes.len() == len as usize => {
+                        let written = bytes
+                            .iter()
+                            .enumerate()
+                            .all(|(i, &b)| cpu.poke8(addr.wrapping_add(i as u32), b));
+
+                        reply(if written { "OK" } else { "E01" })
+                    }
+                    _ => reply("E01"),
+                },
+                None => reply("E01"),
+            },
+            "c" | "s" => {
+                if let Some(addr) = parse_hex(args) {
+                    cpu.jump(addr);
+                }
+
+                Command::Resume { step: kind == "s" }
+            }
+            "Z" | "z" => self.breakpoint(cpu, kind == "Z", args),
+            "D" => Command::Detach,
+            "k" => Command::Kill,
+            "H" => reply("OK"),
+            "q" => query(args),
+            _ => reply(""),
+        }
+    }
+
+    /// `Z`/`z` packets: `type,addr,kind`
+    fn breakpoint(&mut self, cpu: &mut CPU, insert: bool, args: &str) -> Command {
+        let fields: Vec<&str> = args.split(',').collect();
+
+        let (kind, addr, len) = match fields[..] {
+            [kind, addr, len] => match (parse_hex(addr), parse_hex(len)) {
+                (Some(addr), Some(len)) => (kind, addr, len),
+                _ => return Command::Reply("E01".to_string()),
+            },
+            _ => return Command::Reply("E01".to_string()),
+        };
+
+        let access = match kind {
+            // Software and hardware breakpoints are the same thing here
+            "0" | "1" => {
+                match insert {
+                    true if !self.breakpoints.contains(&addr) => self.breakpoints.push(addr),
+                    true => (),
+                    false => self.breakpoints.retain(|&a| a != addr),
+                }
+
+                return Command::Reply("OK".to_string());
+            }
+            "2" => Access::Write,
+            "3" => Access::Read,
+            "4" => Access::ReadWrite,
+            _ => return Command::Reply(String::new()),
+        };
+
+        let watchpoint = Watchpoint { addr, len, access };
+
+        match insert {
+            true => cpu.add_watchpoint(watchpoint),
+            false => {
+                cpu.remove_watchpoint(&watchpoint);
+            }
+        }
+
+        Command::Reply("OK".to_string())
+    }
+
+    /// Check for the break character sent by the client on Ctrl-C
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut buf = [0u8; 64];
+
+        self.stream.set_nonblocking(true)?;
+        let read = self.stream.read(&mut buf);
+        self.stream.set_nonblocking(false)?;
+
+        match read {
+            Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                let interrupted = buf[..n].contains(&0x03);
+                self.input.extend(buf[..n].iter().filter(|&&b| b != 0x03));
+                Ok(interrupted)
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        if self.input.is_empty() {
+            let mut buf = [0u8; 1024];
+
+            match self.stream.read(&mut buf)? {
+                0 => return Err(ErrorKind::UnexpectedEof.into()),
+                n => self.input.extend(&buf[..n]),
+            }
+        }
+
+        Ok(self.input.pop_front().unwrap())
+    }
+
+    /// `$<data>#<checksum>`, anything between packets is skipped
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            while self.byte()? != b'$' {}
+
+            let mut data = Vec::new();
+
+            loop {
+                match self.byte()? {
+                    b'#' => break,
+                    b => data.push(b),
+                }
+            }
+
+            let checksum = [self.byte()?, self.byte()?];
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok());
+
+            if expected == Some(checksum8(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(String::from_utf8_lossy(&data).into_owned());
+            }
+
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum8(data.as_bytes()));
+
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+
+            // Wait for the acknowledgement, resend on `-`
+            loop {
+                match self.byte()? {
+                    b'+' => return Ok(()),
+                    b'-' => break,
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+fn query(args: &str) -> Command {
+    let reply = if args.starts_with("Supported") {
+        "PacketSize=1000;qXfer:features:read+".to_string()
+    } else if args == "Attached" {
+        "1".to_string()
+    } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        match parse_range(range) {
+            Some((offset, len)) => {
+                let xml = target_xml();
+                let start = (offset as usize).min(xml.len());
+                let end = (start + len as usize).min(xml.len());
+                let more = if end < xml.len() { 'm' } else { 'l' };
+
+                format!("{}{}", more, &xml[start..end])
+            }
+            None => "E01".to_string(),
+        }
+    } else {
+        String::new()
+    };
+
+    Command::Reply(reply)
+}
+
+/// Target description with the standard MIPS features, plus EPC which GDB
+/// doesn't know about otherwise
+fn target_xml() -> String {
+    let reg = |name: &str, regnum: usize, extra: &str| {
+        format!(
+            "<reg name=\"{}\" bitsize=\"32\" regnum=\"{}\"{}/>",
+            name, regnum, extra
+        )
+    };
+
+    let gprs: String = (0..32).map(|n| reg(&format!("r{}", n), n, "")).collect();
+    let fprs: String = (0..32)
+        .map(|n| reg(&format!("f{}", n), 38 + n, " type=\"ieee_single\""))
+        .collect();
+
+    format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><architecture>mips:3000</architecture>\
+         <feature name=\"org.gnu.gdb.mips.cpu\">{}{}{}{}</feature>\
+         <feature name=\"org.gnu.gdb.mips.cp0\">{}{}{}</feature>\
+         <feature name=\"org.gnu.gdb.mips.fpu\">{}{}{}</feature>\
+         <feature name=\"org.psx.cop0\">{}</feature></target>",
+        gprs,
+        reg("lo", 33, ""),
+        reg("hi", 34, ""),
+        reg("pc", 37, " type=\"code_ptr\""),
+        reg("status", 32, ""),
+        reg("badvaddr", 35, ""),
+        reg("cause", 36, ""),
+        fprs,
+        reg("fcsr", 70, " group=\"float\""),
+        reg("fir", 71, " group=\"float\""),
+        reg("epc", EPC, " type=\"code_ptr\""),
+    )
+}
+
+fn register(cpu: &CPU, n: usize) -> u32 {
+    match n {
+        0..=31 => cpu.reg(n),
+        32 => cpu.sr(),
+        33 => cpu.lo(),
+        34 => cpu.hi(),
+        35 => cpu.badvaddr(),
+        36 => cpu.cause(),
+        37 => cpu.pc(),
+        EPC => cpu.epc(),
+        // The missing FPU
+        _ => 0,
+    }
+}
+
+fn set_register(cpu: &mut CPU, n: usize, val: u32) {
+    match n {
+        0..=31 => cpu.set_reg(n, val),
+        32 => cpu.set_sr(val),
+        33 => cpu.set_lo(val),
+        34 => cpu.set_hi(val),
+        36 => cpu.set_cause(val),
+        // Rewriting the same pc would cancel a pending branch
+        37 if val != cpu.pc() => cpu.jump(val),
+        EPC => cpu.set_epc(val),
+        // badvaddr and the missing FPU
+        _ => (),
+    }
+}
+
+fn checksum8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Registers are sent in target byte order
+fn hex32(val: u32) -> String {
+    format!("{:08x}", val.swap_bytes())
+}
+
+fn parse_hex32(s: &str) -> Option<u32> {
+    match s.len() {
+        8 => u32::from_str_radix(s, 16).ok().map(u32::swap_bytes),
+        _ => None,
+    }
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    u32::from_str_radix(s, 16).ok()
+}
+
+/// `addr,length`
+fn parse_range(s: &str) -> Option<(u32, u32)> {
+    let (addr, len) = s.split_once(',')?;
+
+    Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+fn parse_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
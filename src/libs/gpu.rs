@@ -0,0 +1,1131 @@
+use crate::libs::irq::{Interrupt, Irq};
+use crate::libs::rasterizer::{Rasterizer, Texture, TextureWindow, Vertex};
+use crate::libs::timers::Timers;
+
+pub const VRAM_WIDTH: usize = 1024;
+pub const VRAM_HEIGHT: usize = 512;
+
+pub struct Gpu {
+    /// 1024x512 pixels of 16 bits
+    vram: Vec<u16>,
+
+    // Draw mode, GP0(E1h)
+    page_base_x: u8,
+    page_base_y: u8,
+    semi_transparency: u8,
+    texture_depth: TextureDepth,
+    dithering: bool,
+    draw_to_display: bool,
+    texture_disable: bool,
+    rectangle_texture_x_flip: bool,
+    rectangle_texture_y_flip: bool,
+
+    // Texture window, GP0(E2h), in units of 8 pixels
+    texture_window_x_mask: u8,
+    texture_window_y_mask: u8,
+    texture_window_x_offset: u8,
+    texture_window_y_offset: u8,
+
+    // Drawing area, GP0(E3h) and GP0(E4h), inclusive
+    drawing_area_left: u16,
+    drawing_area_top: u16,
+    drawing_area_right: u16,
+    drawing_area_bottom: u16,
+
+    // Drawing offset, GP0(E5h)
+    drawing_x_offset: i16,
+    drawing_y_offset: i16,
+
+    // Mask bit setting, GP0(E6h)
+    force_set_mask_bit: bool,
+    preserve_masked_pixels: bool,
+
+    // Display control, GP1
+    texture_disable_allowed: bool,
+    display_disabled: bool,
+    interrupt: bool,
+    dma_direction: DmaDirection,
+    display_vram_x_start: u16,
+    display_vram_y_start: u16,
+    display_horiz_start: u16,
+    display_horiz_end: u16,
+    display_line_start: u16,
+    display_line_end: u16,
+    hres: HorizontalRes,
+    vres: VerticalRes,
+    vmode: VMode,
+    display_depth: DisplayDepth,
+    interlaced: bool,
+    reverse_flag: bool,
+
+    // GP0 command state
+    gp0_command: [u32; 12],
+    gp0_command_len: usize,
+    gp0_words_remaining: usize,
+    gp0_mode: Gp0Mode,
+    /// Polyline being drawn, it ends with a 0x5xxx5xxx terminator word
+    polyline: Option<Polyline>,
+    /// Active CPU to VRAM or VRAM to CPU transfer
+    transfer: VramTransfer,
+    gpuread: u32,
+
+    // Video timing, in GPU clock cycles
+    /// Fractional GPU clock cycles in 1/7 units, the GPU runs at roughly
+    /// 11/7 times the CPU clock
+    clock_frac: u32,
+    /// Remainder of the GPU clock divided by the dot clock divider
+    dot_frac: u16,
+    line_position: u16,
+    line: u16,
+    field: Field,
+    in_vblank: bool,
+    frame: u64,
+}
+
+impl Gpu {
+    pub fn new() -> Gpu {
+        let mut gpu = Gpu {
+            vram: vec![0; VRAM_WIDTH * VRAM_HEIGHT],
+            page_base_x: 0,
+            page_base_y: 0,
+            semi_transparency: 0,
+            texture_depth: TextureDepth::T4Bit,
+            dithering: false,
+            draw_to_display: false,
+            texture_disable: false,
+            rectangle_texture_x_flip: false,
+            rectangle_texture_y_flip: false,
+            texture_window_x_mask: 0,
+            texture_window_y_mask: 0,
+            texture_window_x_offset: 0,
+            texture_window_y_offset: 0,
+            drawing_area_left: 0,
+            drawing_area_top: 0,
+            drawing_area_right: 0,
+            drawing_area_bottom: 0,
+            drawing_x_offset: 0,
+            drawing_y_offset: 0,
+            force_set_mask_bit: false,
+            preserve_masked_pixels: false,
+            texture_disable_allowed: false,
+            display_disabled: true,
+            interrupt: false,
+            dma_direction: DmaDirection::Off,
+            display_vram_x_start: 0,
+            display_vram_y_start: 0,
+            display_horiz_start: 0x200,
+            display_horiz_end: 0xc00,
+            display_line_start: 0x10,
+            display_line_end: 0x100,
+            hres: HorizontalRes::from_fields(0, 0),
+            vres: VerticalRes::Y240Lines,
+            vmode: VMode::Ntsc,
+            display_depth: DisplayDepth::D15Bits,
+            interlaced: false,
+            reverse_flag: false,
+            gp0_command: [0; 12],
+            gp0_command_len: 0,
+            gp0_words_remaining: 0,
+            gp0_mode: Gp0Mode::Command,
+            polyline: None,
+            transfer: VramTransfer::new(),
+            gpuread: 0,
+            clock_frac: 0,
+            dot_frac: 0,
+            line_position: 0,
+            line: 0,
+            field: Field::Top,
+            in_vblank: false,
+            frame: 0,
+        };
+
+        gpu.gp1_reset();
+
+        gpu
+    }
+
+    pub fn vram(&self) -> &[u16] {
+        &self.vram
+    }
+
+    /// Number of frames started since reset
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Region of VRAM sent to the video output, as configured through GP1
+    pub fn display_area(&self) -> DisplayArea {
+        let divider = self.hres.dot_clock_divider();
+        let range = self
+            .display_horiz_end
+            .saturating_sub(self.display_horiz_start);
+
+        // The visible width is rounded to a multiple of 4 pixels
+        let width = match range / divider {
+            0 => self.hres.width(),
+            dots => ((dots + 2) & !3).min(self.hres.width()),
+        };
+
+        let mut height = self
+            .display_line_end
+            .saturating_sub(self.display_line_start);
+        if self.interlaced && self.vres == VerticalRes::Y480Lines {
+            height *= 2;
+        }
+
+        DisplayArea {
+            x: self.display_vram_x_start as usize,
+            y: self.display_vram_y_start as usize,
+            width: width as usize,
+            height: height as usize,
+            depth: self.display_depth,
+        }
+    }
+
+    pub fn load(&mut self, offset: usize) -> u32 {
+        match offset {
+            0 => self.read(),
+            4 => self.status(),
+            _ => 0,
+        }
+    }
+
+    pub fn store(&mut self, offset: usize, val: u32) {
+        match offset {
+            0 => self.gp0(val),
+            4 => self.gp1(val),
+            _ => (),
+        }
+    }
+
+    /// GPUSTAT register
+    pub fn status(&self) -> u32 {
+        let mut r = 0u32;
+
+        r |= self.page_base_x as u32;
+        r |= (self.page_base_y as u32) << 4;
+        r |= (self.semi_transparency as u32) << 5;
+        r |= (self.texture_depth as u32) << 7;
+        r |= (self.dithering as u32) << 9;
+        r |= (self.draw_to_display as u32) << 10;
+        r |= (self.force_set_mask_bit as u32) << 11;
+        r |= (self.preserve_masked_pixels as u32) << 12;
+        r |= (self.interlace_field() as u32) << 13;
+        r |= (self.reverse_flag as u32) << 14;
+        r |= (self.texture_disable as u32) << 15;
+        r |= self.hres.into_status();
+        r |= (self.vres as u32) << 19;
+        r |= (self.vmode as u32) << 20;
+        r |= (self.display_depth as u32) << 21;
+        r |= (self.interlaced as u32) << 22;
+        r |= (self.display_disabled as u32) << 23;
+        r |= (self.interrupt as u32) << 24;
+
+        let ready_to_receive_command = self.gp0_mode == Gp0Mode::Command;
+        let ready_to_send_vram = self.transfer.reading;
+        // Commands are executed as soon as they are complete so we can
+        // always take more data
+        let ready_to_receive_dma = true;
+
+        r |= (ready_to_receive_command as u32) << 26;
+        r |= (ready_to_send_vram as u32) << 27;
+        r |= (ready_to_receive_dma as u32) << 28;
+        r |= (self.dma_direction as u32) << 29;
+        r |= (self.odd_line() as u32) << 31;
+
+        let dma_request = match self.dma_direction {
+            DmaDirection::Off => false,
+            DmaDirection::Fifo => true,
+            DmaDirection::CpuToGp0 => ready_to_receive_dma,
+            DmaDirection::VramToCpu => ready_to_send_vram,
+        };
+
+        r |= (dma_request as u32) << 25;
+
+        r
+    }
+
+    /// GPUREAD register
+    pub fn read(&mut self) -> u32 {
+        if !self.transfer.reading {
+            return self.gpuread;
+        }
+
+        let lo = self.vram_transfer_read() as u32;
+        let hi = self.vram_transfer_read() as u32;
+
+        self.gpuread = lo | (hi << 16);
+        self.gpuread
+    }
+
+    /// Advance the video timing by `cycles` CPU clock cycles, generating the
+    /// blanking signals and dot clock used by the timers
+    pub fn tick(&mut self, cycles: u32, irq: &mut Irq, timers: &mut Timers) {
+        self.clock_frac += cycles * 11;
+
+        let mut clocks = self.clock_frac / 7;
+        self.clock_frac %= 7;
+
+        let line_len = self.vmode.line_length();
+
+        while clocks > 0 {
+            // Switching to the shorter PAL line can leave us past its end
+            if self.line_position >= line_len {
+                self.line_position = 0;
+                self.next_line(irq, timers);
+            }
+
+            let edges = [self.display_horiz_start, self.display_horiz_end, line_len];
+            let next_edge = edges
+                .iter()
+                .filter(|&&e| e > self.line_position && e <= line_len)
+                .min()
+                .copied()
+                .unwrap_or(line_len);
+
+            let step = clocks.min((next_edge - self.line_position) as u32) as u16;
+
+            clocks -= step as u32;
+            self.line_position += step;
+
+            let divider = self.hres.dot_clock_divider();
+            self.dot_frac += step;
+            timers.dot_clock((self.dot_frac / divider) as u32, irq);
+            self.dot_frac %= divider;
+
+            if self.line_position >= line_len {
+                self.line_position = 0;
+                self.next_line(irq, timers);
+            }
+
+            let in_hblank = self.line_position < self.display_horiz_start
+                || self.line_position >= self.display_horiz_end;
+
+            timers.set_hblank(in_hblank, irq);
+        }
+    }
+
+    fn next_line(&mut self, irq: &mut Irq, timers: &mut Timers) {
+        self.line += 1;
+
+        if self.line >= self.vmode.lines_per_frame() {
+            self.line = 0;
+            self.frame += 1;
+
+            self.field = match self.interlaced && self.field == Field::Top {
+                true => Field::Bottom,
+                false => Field::Top,
+            };
+        }
+
+        let in_vblank = self.line < self.display_line_start || self.line >= self.display_line_end;
+
+        if in_vblank && !self.in_vblank {
+            irq.assert(Interrupt::VBlank);
+        }
+
+        self.in_vblank = in_vblank;
+        timers.set_vblank(in_vblank);
+    }
+
+    fn interlace_field(&self) -> bool {
+        // Always set when interlacing is disabled
+        !self.interlaced || self.field == Field::Bottom
+    }
+
+    /// Bit 31 of GPUSTAT: the line being displayed in 480 line interlaced
+    /// mode, otherwise toggles every scanline. Always 0 during vblank.
+    fn odd_line(&self) -> bool {
+        if self.in_vblank {
+            return false;
+        }
+
+        match (self.interlaced, self.vres) {
+            (true, VerticalRes::Y480Lines) => self.field == Field::Bottom,
+            _ => self.line & 1 != 0,
+        }
+    }
+
+    /// Handle a word written to GP0, either a command or data for a
+    /// CPU to VRAM transfer
+    pub fn gp0(&mut self, val: u32) {
+        if self.gp0_mode == Gp0Mode::ImageLoad {
+            self.vram_transfer_write(val as u16);
+            self.vram_transfer_write((val >> 16) as u16);
+            return;
+        }
+
+        if self.polyline.is_some() {
+            self.gp0_polyline(val);
+            return;
+        }
+
+        if self.gp0_words_remaining == 0 {
+            self.gp0_command_len = 0;
+            self.gp0_words_remaining = Self::gp0_command_length(val);
+        }
+
+        self.gp0_command[self.gp0_command_len] = val;
+        self.gp0_command_len += 1;
+        self.gp0_words_remaining -= 1;
+
+        if self.gp0_words_remaining == 0 {
+            self.gp0_execute();
+        }
+    }
+
+    /// Number of words taken by the GP0 command starting with `val`
+    fn gp0_command_length(val: u32) -> usize {
+        let opcode = val >> 24;
+
+        match opcode {
+            0x02 => 3,
+            0x20..=0x3f => {
+                let quad = opcode & 0x08 != 0;
+                let textured = opcode & 0x04 != 0;
+                let gouraud = opcode & 0x10 != 0;
+
+                let vertices = if quad { 4 } else { 3 };
+                let per_vertex = 1 + textured as usize + gouraud as usize;
+
+                // The first vertex color is in the command word
+                1 + vertices * per_vertex - gouraud as usize
+            }
+            // Polylines read their vertices until the terminator word
+            0x40..=0x5f => {
+                let gouraud = opcode & 0x10 != 0;
+
+                match gouraud {
+                    true => 4,
+                    false => 3,
+                }
+            }
+            0x60..=0x7f => {
+                let textured = opcode & 0x04 != 0;
+                let variable_size = (opcode >> 3) & 3 == 0;
+
+                2 + textured as usize + variable_size as usize
+            }
+            0x80..=0x9f => 4,
+            // VRAM transfers, followed by the pixel data
+            0xa0..=0xdf => 3,
+            _ => 1,
+        }
+    }
+
+    fn gp0_execute(&mut self) {
+        let opcode = self.gp0_command[0] >> 24;
+
+        match opcode {
+            0x00 => (),
+            0x01 => (), // Clear texture cache
+            0x02 => self.gp0_fill_rect(),
+            0x03..=0x1e => (),
+            0x1f => self.interrupt = true,
+            0x20..=0x3f => self.gp0_polygon(),
+            0x40..=0x5f => self.gp0_line(),
+            0x60..=0x7f => self.gp0_rect(),
+            0x80..=0x9f => self.gp0_copy_rect(),
+            0xa0..=0xbf => self.gp0_image_load(),
+            0xc0..=0xdf => self.gp0_image_store(),
+            0xe1 => self.gp0_draw_mode(self.gp0_command[0]),
+            0xe2 => self.gp0_texture_window(),
+            0xe3 => self.gp0_drawing_area_top_left(),
+            0xe4 => self.gp0_drawing_area_bottom_right(),
+            0xe5 => self.gp0_drawing_offset(),
+            0xe6 => self.gp0_mask_bit_setting(),
+            _ => println!("Unhandled GP0 command {:08x}", self.gp0_command[0]),
+        }
+    }
+
+    fn gp0_polygon(&mut self) {
+        let opcode = self.gp0_command[0] >> 24;
+        let raw = opcode & 0x01 != 0;
+        let semi = opcode & 0x02 != 0;
+        let textured = opcode & 0x04 != 0;
+        let quad = opcode & 0x08 != 0;
+        let gouraud = opcode & 0x10 != 0;
+
+        let count = if quad { 4 } else { 3 };
+
+        let mut vertices = [Vertex::new(0, 0, [0; 3]); 4];
+        let mut uvs = [0u32; 4];
+        let mut color = self.gp0_command[0];
+        let mut index = 1;
+
+        for i in 0..count {
+            if gouraud && i > 0 {
+                color = self.gp0_command[index];
+                index += 1;
+            }
+
+            let pos = self.gp0_command[index];
+            index += 1;
+
+            if textured {
+                uvs[i] = self.gp0_command[index];
+                index += 1;
+            }
+
+            vertices[i] = self.vertex(pos, color, uvs[i]);
+        }
+
+        // The texture page of textured polygons replaces the draw mode
+        if textured {
+            self.set_texpage(uvs[1] >> 16);
+        }
+
+        let texture = match textured && !self.texture_disable {
+            true => Some(self.texture(uvs[0] >> 16, raw)),
+            false => None,
+        };
+
+        let mut rasterizer = self.rasterizer();
+
+        match quad {
+            true => rasterizer.quad(vertices, texture, gouraud, semi),
+            false => rasterizer.triangle(
+                [vertices[0], vertices[1], vertices[2]],
+                texture,
+                gouraud,
+                semi,
+            ),
+        }
+    }
+
+    fn gp0_line(&mut self) {
+        let opcode = self.gp0_command[0] >> 24;
+        let semi = opcode & 0x02 != 0;
+        let gouraud = opcode & 0x10 != 0;
+
+        let a = self.vertex(self.gp0_command[1], self.gp0_command[0], 0);
+        let b = match gouraud {
+            true => self.vertex(self.gp0_command[3], self.gp0_command[2], 0),
+            false => self.vertex(self.gp0_command[2], self.gp0_command[0], 0),
+        };
+
+        self.rasterizer().line(a, b, gouraud, semi);
+
+        if opcode & 0x08 != 0 {
+            self.polyline = Some(Polyline {
+                last: b,
+                gouraud,
+                semi,
+                color: None,
+            });
+        }
+    }
+
+    /// Following vertices of a polyline. With gouraud shading each vertex is
+    /// preceded by its color.
+    fn gp0_polyline(&mut self, val: u32) {
+        let Some(mut polyline) = self.polyline.take() else {
+            return;
+        };
+
+        if val & 0xf000_f000 == 0x5000_5000 {
+            return;
+        }
+
+        if polyline.gouraud && polyline.color.is_none() {
+            polyline.color = Some(val);
+            self.polyline = Some(polyline);
+            return;
+        }
+
+        let color = match polyline.color.take() {
+            Some(color) => color,
+            None => self.gp0_command[0],
+        };
+
+        let next = self.vertex(val, color, 0);
+
+        self.rasterizer()
+            .line(polyline.last, next, polyline.gouraud, polyline.semi);
+
+        polyline.last = next;
+        self.polyline = Some(polyline);
+    }
+
+    fn gp0_rect(&mut self) {
+        let opcode = self.gp0_command[0] >> 24;
+        let raw = opcode & 0x01 != 0;
+        let semi = opcode & 0x02 != 0;
+        let textured = opcode & 0x04 != 0;
+
+        let uv = match textured {
+            true => self.gp0_command[2],
+            false => 0,
+        };
+
+        let origin = self.vertex(self.gp0_command[1], self.gp0_command[0], uv);
+
+        let (width, height) = match (opcode >> 3) & 3 {
+            0 => {
+                let size = self.gp0_command[self.gp0_command_len - 1];
+
+                ((size & 0x3ff) as i32, ((size >> 16) & 0x1ff) as i32)
+            }
+            1 => (1, 1),
+            2 => (8, 8),
+            _ => (16, 16),
+        };
+
+        let texture = match textured && !self.texture_disable {
+            true => Some(self.texture(uv >> 16, raw)),
+            false => None,
+        };
+
+        let flip_x = self.rectangle_texture_x_flip;
+        let flip_y = self.rectangle_texture_y_flip;
+
+        self.rasterizer()
+            .rectangle(origin, width, height, texture, semi, flip_x, flip_y);
+    }
+
+    /// Decode a vertex position, with the drawing offset applied
+    fn vertex(&self, pos: u32, color: u32, uv: u32) -> Vertex {
+        // Signed 11-bit coordinates
+        let x = ((pos << 21) as i32) >> 21;
+        let y = ((pos << 5) as i32) >> 21;
+
+        Vertex {
+            x: x + self.drawing_x_offset as i32,
+            y: y + self.drawing_y_offset as i32,
+            color: [color as u8, (color >> 8) as u8, (color >> 16) as u8],
+            u: uv as u8,
+            v: (uv >> 8) as u8,
+        }
+    }
+
+    /// Texture from the current texture page and the CLUT attribute of a
+    /// primitive
+    fn texture(&self, clut: u32, raw: bool) -> Texture {
+        Texture {
+            page_x: self.page_base_x as usize * 64,
+            page_y: self.page_base_y as usize * 256,
+            depth: self.texture_depth,
+            clut_x: (clut & 0x3f) as usize * 16,
+            clut_y: ((clut >> 6) & 0x1ff) as usize,
+            raw,
+        }
+    }
+
+    fn rasterizer(&mut self) -> Rasterizer<'_> {
+        let mut rasterizer = Rasterizer::new(&mut self.vram);
+
+        rasterizer.left = self.drawing_area_left as i32;
+        rasterizer.top = self.drawing_area_top as i32;
+        rasterizer.right = self.drawing_area_right as i32;
+        rasterizer.bottom = self.drawing_area_bottom as i32;
+        rasterizer.dithering = self.dithering;
+        rasterizer.semi_transparency = self.semi_transparency;
+        rasterizer.force_set_mask_bit = self.force_set_mask_bit;
+        rasterizer.preserve_masked_pixels = self.preserve_masked_pixels;
+        rasterizer.window = TextureWindow {
+            mask_x: self.texture_window_x_mask,
+            mask_y: self.texture_window_y_mask,
+            offset_x: self.texture_window_x_offset,
+            offset_y: self.texture_window_y_offset,
+        };
+
+        rasterizer
+    }
+
+    fn gp0_draw_mode(&mut self, val: u32) {
+        self.set_texpage(val & 0x7ff);
+
+        self.dithering = (val >> 9) & 1 != 0;
+        self.draw_to_display = (val >> 10) & 1 != 0;
+        self.rectangle_texture_x_flip = (val >> 12) & 1 != 0;
+        self.rectangle_texture_y_flip = (val >> 13) & 1 != 0;
+    }
+
+    /// Texture page attribute, shared between GP0(E1h) and the textured
+    /// polygons
+    fn set_texpage(&mut self, val: u32) {
+        self.page_base_x = (val & 0xf) as u8;
+        self.page_base_y = ((val >> 4) & 1) as u8;
+        self.semi_transparency = ((val >> 5) & 3) as u8;
+        self.texture_depth = TextureDepth::from_field((val >> 7) & 3);
+
+        self.texture_disable = self.texture_disable_allowed && (val >> 11) & 1 != 0;
+    }
+
+    fn gp0_texture_window(&mut self) {
+        let val = self.gp0_command[0];
+
+        self.texture_window_x_mask = (val & 0x1f) as u8;
+        self.texture_window_y_mask = ((val >> 5) & 0x1f) as u8;
+        self.texture_window_x_offset = ((val >> 10) & 0x1f) as u8;
+        self.texture_window_y_offset = ((val >> 15) & 0x1f) as u8;
+    }
+
+    fn gp0_drawing_area_top_left(&mut self) {
+        let val = self.gp0_command[0];
+
+        self.drawing_area_left = (val & 0x3ff) as u16;
+        self.drawing_area_top = ((val >> 10) & 0x1ff) as u16;
+    }
+
+    fn gp0_drawing_area_bottom_right(&mut self) {
+        let val = self.gp0_command[0];
+
+        self.drawing_area_right = (val & 0x3ff) as u16;
+        self.drawing_area_bottom = ((val >> 10) & 0x1ff) as u16;
+    }
+
+    fn gp0_drawing_offset(&mut self) {
+        let val = self.gp0_command[0];
+
+        // Signed 11-bit values
+        let x = ((val & 0x7ff) << 5) as i16 >> 5;
+        let y = (((val >> 11) & 0x7ff) << 5) as i16 >> 5;
+
+        self.drawing_x_offset = x;
+        self.drawing_y_offset = y;
+    }
+
+    fn gp0_mask_bit_setting(&mut self) {
+        let val = self.gp0_command[0];
+
+        self.force_set_mask_bit = val & 1 != 0;
+        self.preserve_masked_pixels = (val >> 1) & 1 != 0;
+    }
+
+    /// Fill a rectangle with a solid color. Ignores the drawing area and
+    /// the mask settings.
+    fn gp0_fill_rect(&mut self) {
+        let color = Self::rgb24_to_rgb15(self.gp0_command[0]);
+        let pos = self.gp0_command[1];
+        let size = self.gp0_command[2];
+
+        let x = (pos & 0x3f0) as usize;
+        let y = ((pos >> 16) & 0x1ff) as usize;
+        let w = (((size & 0x3ff) + 0xf) & !0xf) as usize;
+        let h = ((size >> 16) & 0x1ff) as usize;
+
+        for dy in 0..h {
+            let row = ((y + dy) % VRAM_HEIGHT) * VRAM_WIDTH;
+
+            for dx in 0..w {
+                self.vram[row + (x + dx) % VRAM_WIDTH] = color;
+            }
+        }
+    }
+
+    fn gp0_copy_rect(&mut self) {
+        let src = self.gp0_command[1];
+        let dst = self.gp0_command[2];
+        let (w, h) = Self::transfer_size(self.gp0_command[3]);
+
+        let src_x = (src & 0x3ff) as usize;
+        let src_y = ((src >> 16) & 0x1ff) as usize;
+        let dst_x = (dst & 0x3ff) as usize;
+        let dst_y = ((dst >> 16) & 0x1ff) as usize;
+
+        for dy in 0..h {
+            for dx in 0..w {
+                let src_index = Self::vram_index(src_x + dx, src_y + dy);
+                let pixel = self.vram[src_index];
+
+                self.write_masked_pixel(dst_x + dx, dst_y + dy, pixel);
+            }
+        }
+    }
+
+    fn gp0_image_load(&mut self) {
+        self.transfer = VramTransfer::start(self.gp0_command[1], self.gp0_command[2], false);
+        self.gp0_mode = Gp0Mode::ImageLoad;
+    }
+
+    fn gp0_image_store(&mut self) {
+        self.transfer = VramTransfer::start(self.gp0_command[1], self.gp0_command[2], true);
+    }
+
+    fn transfer_size(val: u32) -> (usize, usize) {
+        let w = ((val & 0xffff).wrapping_sub(1) & 0x3ff) + 1;
+        let h = ((val >> 16).wrapping_sub(1) & 0x1ff) + 1;
+
+        (w as usize, h as usize)
+    }
+
+    fn vram_index(x: usize, y: usize) -> usize {
+        (y % VRAM_HEIGHT) * VRAM_WIDTH + (x % VRAM_WIDTH)
+    }
+
+    /// Write a pixel honoring the mask bit settings of GP0(E6h)
+    fn write_masked_pixel(&mut self, x: usize, y: usize, pixel: u16) {
+        let index = Self::vram_index(x, y);
+
+        if self.preserve_masked_pixels && self.vram[index] & 0x8000 != 0 {
+            return;
+        }
+
+        let mask = (self.force_set_mask_bit as u16) << 15;
+
+        self.vram[index] = pixel | mask;
+    }
+
+    fn vram_transfer_write(&mut self, pixel: u16) {
+        if self.transfer.remaining == 0 {
+            return;
+        }
+
+        // The padding halfword is consumed but not written
+        if self.transfer.pixels > 0 {
+            let (x, y) = self.transfer.position();
+            self.write_masked_pixel(x, y, pixel);
+        }
+
+        if self.transfer.advance() {
+            self.gp0_mode = Gp0Mode::Command;
+        }
+    }
+
+    fn vram_transfer_read(&mut self) -> u16 {
+        if self.transfer.remaining == 0 {
+            return 0;
+        }
+
+        let pixel = match self.transfer.pixels {
+            0 => 0,
+            _ => {
+                let (x, y) = self.transfer.position();
+                self.vram[Self::vram_index(x, y)]
+            }
+        };
+
+        if self.transfer.advance() {
+            self.transfer.reading = false;
+        }
+
+        pixel
+    }
+
+    fn rgb24_to_rgb15(val: u32) -> u16 {
+        let r = ((val >> 3) & 0x1f) as u16;
+        let g = ((val >> 11) & 0x1f) as u16;
+        let b = ((val >> 19) & 0x1f) as u16;
+
+        r | (g << 5) | (b << 10)
+    }
+
+    /// Handle a word written to GP1
+    pub fn gp1(&mut self, val: u32) {
+        let opcode = (val >> 24) & 0x3f;
+
+        match opcode {
+            0x00 => self.gp1_reset(),
+            0x01 => self.gp1_reset_command_buffer(),
+            0x02 => self.interrupt = false,
+            0x03 => self.display_disabled = val & 1 != 0,
+            0x04 => self.dma_direction = DmaDirection::from_field(val & 3),
+            0x05 => {
+                self.display_vram_x_start = (val & 0x3fe) as u16;
+                self.display_vram_y_start = ((val >> 10) & 0x1ff) as u16;
+            }
+            0x06 => {
+                self.display_horiz_start = (val & 0xfff) as u16;
+                self.display_horiz_end = ((val >> 12) & 0xfff) as u16;
+            }
+            0x07 => {
+                self.display_line_start = (val & 0x3ff) as u16;
+                self.display_line_end = ((val >> 10) & 0x3ff) as u16;
+            }
+            0x08 => self.gp1_display_mode(val),
+            0x09 => self.texture_disable_allowed = val & 1 != 0,
+            0x10..=0x1f => self.gp1_get_info(val),
+            _ => println!("Unhandled GP1 command {:08x}", val),
+        }
+    }
+
+    fn gp1_reset(&mut self) {
+        self.gp1_reset_command_buffer();
+
+        self.interrupt = false;
+        self.gp0_draw_mode(0);
+        self.gp0_command[0] = 0;
+        self.gp0_texture_window();
+        self.gp0_drawing_area_top_left();
+        self.gp0_drawing_area_bottom_right();
+        self.gp0_drawing_offset();
+        self.gp0_mask_bit_setting();
+
+        self.texture_disable_allowed = false;
+        self.display_disabled = true;
+        self.dma_direction = DmaDirection::Off;
+        self.display_vram_x_start = 0;
+        self.display_vram_y_start = 0;
+        self.display_horiz_start = 0x200;
+        self.display_horiz_end = 0xc00;
+        self.display_line_start = 0x10;
+        self.display_line_end = 0x100;
+        self.gp1_display_mode(0);
+    }
+
+    fn gp1_reset_command_buffer(&mut self) {
+        self.gp0_command_len = 0;
+        self.gp0_words_remaining = 0;
+        self.gp0_mode = Gp0Mode::Command;
+        self.polyline = None;
+        self.transfer = VramTransfer::new();
+    }
+
+    fn gp1_display_mode(&mut self, val: u32) {
+        let hr1 = (val & 3) as u8;
+        let hr2 = ((val >> 6) & 1) as u8;
+
+        self.hres = HorizontalRes::from_fields(hr1, hr2);
+        self.vres = match (val >> 2) & 1 != 0 {
+            true => VerticalRes::Y480Lines,
+            false => VerticalRes::Y240Lines,
+        };
+        self.vmode = match (val >> 3) & 1 != 0 {
+            true => VMode::Pal,
+            false => VMode::Ntsc,
+        };
+        self.display_depth = match (val >> 4) & 1 != 0 {
+            true => DisplayDepth::D24Bits,
+            false => DisplayDepth::D15Bits,
+        };
+        self.interlaced = (val >> 5) & 1 != 0;
+        self.reverse_flag = (val >> 7) & 1 != 0;
+    }
+
+    fn gp1_get_info(&mut self, val: u32) {
+        self.gpuread = match val & 0xf {
+            2 => {
+                (self.texture_window_x_mask as u32)
+                    | ((self.texture_window_y_mask as u32) << 5)
+                    | ((self.texture_window_x_offset as u32) << 10)
+                    | ((self.texture_window_y_offset as u32) << 15)
+            }
+            3 => (self.drawing_area_left as u32) | ((self.drawing_area_top as u32) << 10),
+            4 => (self.drawing_area_right as u32) | ((self.drawing_area_bottom as u32) << 10),
+            5 => {
+                let x = (self.drawing_x_offset as u32) & 0x7ff;
+                let y = (self.drawing_y_offset as u32) & 0x7ff;
+
+                x | (y << 11)
+            }
+            // GPU version
+            7 => 2,
+            8 => 0,
+            _ => return,
+        };
+    }
+}
+
+struct Polyline {
+    last: Vertex,
+    gouraud: bool,
+    semi: bool,
+    /// Color word received for the next vertex of a gouraud polyline
+    color: Option<u32>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Gp0Mode {
+    Command,
+    /// Receiving pixel data for a CPU to VRAM transfer
+    ImageLoad,
+}
+
+/// State of a rectangular transfer between VRAM and the CPU
+struct VramTransfer {
+    x: usize,
+    y: usize,
+    width: usize,
+    cur_x: usize,
+    cur_y: usize,
+    /// Halfwords left, including the padding of odd sized transfers
+    remaining: usize,
+    /// Pixels of the rectangle left
+    pixels: usize,
+    /// VRAM to CPU transfer waiting to be read through GPUREAD
+    reading: bool,
+}
+
+impl VramTransfer {
+    fn new() -> VramTransfer {
+        VramTransfer {
+            x: 0,
+            y: 0,
+            width: 0,
+            cur_x: 0,
+            cur_y: 0,
+            remaining: 0,
+            pixels: 0,
+            reading: false,
+        }
+    }
+
+    fn start(pos: u32, size: u32, reading: bool) -> VramTransfer {
+        let (width, height) = Gpu::transfer_size(size);
+
+        VramTransfer {
+            x: (pos & 0x3ff) as usize,
+            y: ((pos >> 16) & 0x1ff) as usize,
+            width,
+            cur_x: 0,
+            cur_y: 0,
+            // Transfers are done in 32-bit words, an odd number of pixels
+            // is padded with an extra halfword
+            remaining: (width * height + 1) & !1,
+            pixels: width * height,
+            reading,
+        }
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.x + self.cur_x, self.y + self.cur_y)
+    }
+
+    /// Move to the next pixel, returns true once the transfer is done
+    fn advance(&mut self) -> bool {
+        self.remaining -= 1;
+
+        if self.pixels > 0 {
+            self.pixels -= 1;
+            self.cur_x += 1;
+
+            if self.cur_x == self.width {
+                self.cur_x = 0;
+                self.cur_y += 1;
+            }
+        }
+
+        self.remaining == 0
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextureDepth {
+    T4Bit = 0,
+    T8Bit = 1,
+    T15Bit = 2,
+}
+
+impl TextureDepth {
+    fn from_field(field: u32) -> TextureDepth {
+        match field {
+            0 => TextureDepth::T4Bit,
+            1 => TextureDepth::T8Bit,
+            // 3 is reserved and behaves like 15 bits
+            _ => TextureDepth::T15Bit,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Field {
+    Top = 1,
+    Bottom = 0,
+}
+
+/// Horizontal resolution, stored as the two GP1(08h) fields
+#[derive(Copy, Clone, PartialEq)]
+pub struct HorizontalRes(u8);
+
+impl HorizontalRes {
+    fn from_fields(hr1: u8, hr2: u8) -> HorizontalRes {
+        HorizontalRes((hr2 & 1) | ((hr1 & 3) << 1))
+    }
+
+    fn into_status(self) -> u32 {
+        (self.0 as u32) << 16
+    }
+
+    /// Number of GPU clock cycles per displayed pixel
+    fn dot_clock_divider(self) -> u16 {
+        match self.0 & 1 != 0 {
+            // 368 pixels
+            true => 7,
+            false => match self.0 >> 1 {
+                0 => 10,
+                1 => 8,
+                2 => 5,
+                _ => 4,
+            },
+        }
+    }
+
+    /// Horizontal resolution in pixels
+    pub fn width(self) -> u16 {
+        match self.0 & 1 != 0 {
+            true => 368,
+            false => match self.0 >> 1 {
+                0 => 256,
+                1 => 320,
+                2 => 512,
+                _ => 640,
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum VerticalRes {
+    Y240Lines = 0,
+    Y480Lines = 1,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum VMode {
+    Ntsc = 0,
+    Pal = 1,
+}
+
+impl VMode {
+    /// Length of a scanline in GPU clock cycles
+    fn line_length(self) -> u16 {
+        match self {
+            VMode::Ntsc => 3413,
+            VMode::Pal => 3406,
+        }
+    }
+
+    fn lines_per_frame(self) -> u16 {
+        match self {
+            VMode::Ntsc => 263,
+            VMode::Pal => 314,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplayArea {
+    /// Top-left corner in VRAM
+    pub x: usize,
+    pub y: usize,
+    /// Size in output pixels
+    pub width: usize,
+    pub height: usize,
+    pub depth: DisplayDepth,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DisplayDepth {
+    D15Bits = 0,
+    D24Bits = 1,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum DmaDirection {
+    Off = 0,
+    Fifo = 1,
+    CpuToGp0 = 2,
+    VramToCpu = 3,
+}
+
+impl DmaDirection {
+    fn from_field(field: u32) -> DmaDirection {
+        match field {
+            0 => DmaDirection::Off,
+            1 => DmaDirection::Fifo,
+            2 => DmaDirection::CpuToGp0,
+            _ => DmaDirection::VramToCpu,
+        }
+    }
+}
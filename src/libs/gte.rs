@@ -0,0 +1,861 @@
+/// Geometry Transformation Engine (coprocessor 2)
+pub struct Gte {
+    // Control registers
+    /// Rotation matrix
+    rt: [[i16; 3]; 3],
+    /// Translation vector
+    tr: [i32; 3],
+    /// Light source matrix
+    l: [[i16; 3]; 3],
+    /// Background color
+    bk: [i32; 3],
+    /// Light color matrix
+    lr: [[i16; 3]; 3],
+    /// Far color
+    fc: [i32; 3],
+    ofx: i32,
+    ofy: i32,
+    /// Projection plane distance
+    h: u16,
+    /// Depth queuing coefficient and offset
+    dqa: i16,
+    dqb: i32,
+    /// Average Z scale factors
+    zsf3: i16,
+    zsf4: i16,
+    flag: u32,
+
+    // Data registers
+    /// Input vectors V0-V2. The last entry holds [IR1, IR2, IR3] when MVMVA
+    /// uses them as its vector operand
+    v: [[i16; 3]; 4],
+    rgbc: [u8; 4],
+    otz: u16,
+    ir: [i16; 4],
+    /// Screen XY FIFO
+    sxy: [(i16, i16); 3],
+    /// Screen Z FIFO
+    sz: [u16; 4],
+    /// Color FIFO
+    rgb: [[u8; 4]; 3],
+    res1: u32,
+    mac: [i32; 4],
+    lzcs: u32,
+}
+
+#[derive(Copy, Clone)]
+enum Matrix {
+    Rotation = 0,
+    Light = 1,
+    Color = 2,
+    Invalid = 3,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ControlVector {
+    Translation = 0,
+    BackgroundColor = 1,
+    FarColor = 2,
+    Zero = 3,
+}
+
+/// Decoded fields of a GTE command word
+#[derive(Copy, Clone)]
+struct Command(u32);
+
+impl Command {
+    fn opcode(&self) -> u32 {
+        self.0 & 0x3f
+    }
+
+    /// Fraction bits dropped from MAC results (the "sf" bit)
+    fn shift(&self) -> u32 {
+        match (self.0 >> 19) & 1 != 0 {
+            true => 12,
+            false => 0,
+        }
+    }
+
+    /// Saturate IR1-3 to 0..0x7fff instead of -0x8000..0x7fff
+    fn lm(&self) -> bool {
+        (self.0 >> 10) & 1 != 0
+    }
+
+    fn matrix(&self) -> Matrix {
+        match (self.0 >> 17) & 3 {
+            0 => Matrix::Rotation,
+            1 => Matrix::Light,
+            2 => Matrix::Color,
+            _ => Matrix::Invalid,
+        }
+    }
+
+    fn vector_index(&self) -> usize {
+        ((self.0 >> 15) & 3) as usize
+    }
+
+    fn control_vector(&self) -> ControlVector {
+        match (self.0 >> 13) & 3 {
+            0 => ControlVector::Translation,
+            1 => ControlVector::BackgroundColor,
+            2 => ControlVector::FarColor,
+            _ => ControlVector::Zero,
+        }
+    }
+}
+
+/// Unsigned Newton-Raphson reciprocal table used by the RTPS/RTPT divider
+const UNR_TABLE: [u8; 0x101] = {
+    let mut table = [0; 0x101];
+    let mut i = 0;
+
+    while i < table.len() {
+        let v = (0x40000 / (i as i32 + 0x100) + 1) / 2 - 0x101;
+        table[i] = if v > 0 { v as u8 } else { 0 };
+        i += 1;
+    }
+
+    table
+};
+
+impl Gte {
+    pub fn new() -> Gte {
+        Gte {
+            rt: [[0; 3]; 3],
+            tr: [0; 3],
+            l: [[0; 3]; 3],
+            bk: [0; 3],
+            lr: [[0; 3]; 3],
+            fc: [0; 3],
+            ofx: 0,
+            ofy: 0,
+            h: 0,
+            dqa: 0,
+            dqb: 0,
+            zsf3: 0,
+            zsf4: 0,
+            flag: 0,
+            v: [[0; 3]; 4],
+            rgbc: [0; 4],
+            otz: 0,
+            ir: [0; 4],
+            sxy: [(0, 0); 3],
+            sz: [0; 4],
+            rgb: [[0; 4]; 3],
+            res1: 0,
+            mac: [0; 4],
+            lzcs: 0,
+        }
+    }
+
+    /// Read data register `reg` (MFC2/SWC2)
+    pub fn data(&self, reg: usize) -> u32 {
+        let pack = |a: i16, b: i16| (a as u16 as u32) | ((b as u16 as u32) << 16);
+
+        match reg {
+            0 | 2 | 4 => {
+                let v = self.v[reg >> 1];
+                pack(v[0], v[1])
+            }
+            1 | 3 | 5 => self.v[reg >> 1][2] as u32,
+            6 => u32::from_le_bytes(self.rgbc),
+            7 => self.otz as u32,
+            8..=11 => self.ir[reg - 8] as u32,
+            12..=14 => {
+                let (x, y) = self.sxy[reg - 12];
+                pack(x, y)
+            }
+            // SXYP mirrors SXY2 on read
+            15 => {
+                let (x, y) = self.sxy[2];
+                pack(x, y)
+            }
+            16..=19 => self.sz[reg - 16] as u32,
+            20..=22 => u32::from_le_bytes(self.rgb[reg - 20]),
+            23 => self.res1,
+            24..=27 => self.mac[reg - 24] as u32,
+            28 | 29 => {
+                let c = |ir: i16| (ir >> 7).clamp(0, 0x1f) as u32;
+
+                c(self.ir[1]) | (c(self.ir[2]) << 5) | (c(self.ir[3]) << 10)
+            }
+            30 => self.lzcs,
+            31 => self.lzcr(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write data register `reg` (MTC2/LWC2)
+    pub fn set_data(&mut self, reg: usize, val: u32) {
+        let lo = val as i16;
+        let hi = (val >> 16) as i16;
+
+        match reg {
+            0 | 2 | 4 => {
+                let v = &mut self.v[reg >> 1];
+                v[0] = lo;
+                v[1] = hi;
+            }
+            1 | 3 | 5 => self.v[reg >> 1][2] = lo,
+            6 => self.rgbc = val.to_le_bytes(),
+            7 => self.otz = val as u16,
+            8..=11 => self.ir[reg - 8] = lo,
+            12..=14 => self.sxy[reg - 12] = (lo, hi),
+            15 => self.push_sxy(lo, hi),
+            16..=19 => self.sz[reg - 16] = val as u16,
+            20..=22 => self.rgb[reg - 20] = val.to_le_bytes(),
+            23 => self.res1 = val,
+            24..=27 => self.mac[reg - 24] = val as i32,
+            28 => {
+                self.ir[1] = ((val & 0x1f) << 7) as i16;
+                self.ir[2] = (((val >> 5) & 0x1f) << 7) as i16;
+                self.ir[3] = (((val >> 10) & 0x1f) << 7) as i16;
+            }
+            // ORGB and LZCR are read-only
+            29 | 31 => (),
+            30 => self.lzcs = val,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Read control register `reg` (CFC2)
+    pub fn control(&self, reg: usize) -> u32 {
+        let pack = |a: i16, b: i16| (a as u16 as u32) | ((b as u16 as u32) << 16);
+
+        let matrix = |m: &[[i16; 3]; 3], reg: usize| match reg {
+            0 => pack(m[0][0], m[0][1]),
+            1 => pack(m[0][2], m[1][0]),
+            2 => pack(m[1][1], m[1][2]),
+            3 => pack(m[2][0], m[2][1]),
+            4 => m[2][2] as u32,
+            _ => unreachable!(),
+        };
+
+        match reg {
+            0..=4 => matrix(&self.rt, reg),
+            5..=7 => self.tr[reg - 5] as u32,
+            8..=12 => matrix(&self.l, reg - 8),
+            13..=15 => self.bk[reg - 13] as u32,
+            16..=20 => matrix(&self.lr, reg - 16),
+            21..=23 => self.fc[reg - 21] as u32,
+            24 => self.ofx as u32,
+            25 => self.ofy as u32,
+            // H is unsigned but reads back sign-extended
+            26 => self.h as i16 as u32,
+            27 => self.dqa as u32,
+            28 => self.dqb as u32,
+            29 => self.zsf3 as u32,
+            30 => self.zsf4 as u32,
+            31 => self.flag,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write control register `reg` (CTC2)
+    pub fn set_control(&mut self, reg: usize, val: u32) {
+        let lo = val as i16;
+        let hi = (val >> 16) as i16;
+
+        let matrix = |m: &mut [[i16; 3]; 3], reg: usize| match reg {
+            0 => {
+                m[0][0] = lo;
+                m[0][1] = hi;
+            }
+            1 => {
+                m[0][2] = lo;
+                m[1][0] = hi;
+            }
+            2 => {
+                m[1][1] = lo;
+                m[1][2] = hi;
+            }
+            3 => {
+                m[2][0] = lo;
+                m[2][1] = hi;
+            }
+            4 => m[2][2] = lo,
+            _ => unreachable!(),
+        };
+
+        match reg {
+            0..=4 => matrix(&mut self.rt, reg),
+            5..=7 => self.tr[reg - 5] = val as i32,
+            8..=12 => matrix(&mut self.l, reg - 8),
+            13..=15 => self.bk[reg - 13] = val as i32,
+            16..=20 => matrix(&mut self.lr, reg - 16),
+            21..=23 => self.fc[reg - 21] = val as i32,
+            24 => self.ofx = val as i32,
+            25 => self.ofy = val as i32,
+            26 => self.h = val as u16,
+            27 => self.dqa = lo,
+            28 => self.dqb = val as i32,
+            29 => self.zsf3 = lo,
+            30 => self.zsf4 = lo,
+            31 => {
+                self.flag = val & 0x7fff_f000;
+                self.update_flag_error();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Execute the GTE command encoded in the low 25 bits of a COP2
+    /// instruction, returns false for unknown commands
+    pub fn command(&mut self, command: u32) -> bool {
+        let c = Command(command);
+
+        self.flag = 0;
+
+        match c.opcode() {
+            0x01 => self.cmd_rtps(c),
+            0x06 => self.cmd_nclip(),
+            0x0c => self.cmd_op(c),
+            0x10 => self.cmd_dpcs(c),
+            0x11 => self.cmd_intpl(c),
+            0x12 => self.cmd_mvmva(c),
+            0x13 => self.cmd_ncds(c),
+            0x14 => self.cmd_cdp(c),
+            0x16 => self.cmd_ncdt(c),
+            0x1b => self.cmd_nccs(c),
+            0x1c => self.cmd_cc(c),
+            0x1e => self.cmd_ncs(c),
+            0x20 => self.cmd_nct(c),
+            0x28 => self.cmd_sqr(c),
+            0x29 => self.cmd_dcpl(c),
+            0x2a => self.cmd_dpct(c),
+            0x2d => self.cmd_avsz3(),
+            0x2e => self.cmd_avsz4(),
+            0x30 => self.cmd_rtpt(c),
+            0x3d => self.cmd_gpf(c),
+            0x3e => self.cmd_gpl(c),
+            0x3f => self.cmd_ncct(c),
+            _ => return false,
+        }
+
+        self.update_flag_error();
+        true
+    }
+
+    fn lzcr(&self) -> u32 {
+        match (self.lzcs as i32) < 0 {
+            true => self.lzcs.leading_ones(),
+            false => self.lzcs.leading_zeros(),
+        }
+    }
+
+    fn update_flag_error(&mut self) {
+        let error = self.flag & 0x7f87_e000 != 0;
+
+        self.flag &= !(1 << 31);
+        self.flag |= (error as u32) << 31;
+    }
+
+    fn set_flag(&mut self, bit: u32) {
+        self.flag |= 1 << bit;
+    }
+
+    fn matrix(&self, m: Matrix) -> [[i16; 3]; 3] {
+        match m {
+            Matrix::Rotation => self.rt,
+            Matrix::Light => self.l,
+            Matrix::Color => self.lr,
+            // Selecting the fourth matrix gives a mix of unrelated registers
+            Matrix::Invalid => {
+                let r = (self.rgbc[0] as i16) << 4;
+                [[-r, r, self.ir[0]], [self.rt[0][2]; 3], [self.rt[1][1]; 3]]
+            }
+        }
+    }
+
+    fn control_vector(&self, cv: ControlVector) -> [i32; 3] {
+        match cv {
+            ControlVector::Translation => self.tr,
+            ControlVector::BackgroundColor => self.bk,
+            ControlVector::FarColor => self.fc,
+            ControlVector::Zero => [0; 3],
+        }
+    }
+
+    /// Check that an intermediate MAC1-3 value fits in 44 bits, flag the
+    /// overflow and return the value truncated to 44 bits
+    fn i64_to_i44(&mut self, index: usize, val: i64) -> i64 {
+        if val > 0x7ff_ffff_ffff {
+            self.set_flag(30 - index as u32);
+        } else if val < -0x800_0000_0000 {
+            self.set_flag(27 - index as u32);
+        }
+
+        (val << 20) >> 20
+    }
+
+    /// Check that a MAC0 value fits in 32 bits
+    fn i64_to_i32_mac0(&mut self, val: i64) -> i32 {
+        if val > 0x7fff_ffff {
+            self.set_flag(16);
+        } else if val < -0x8000_0000 {
+            self.set_flag(15);
+        }
+
+        val as i32
+    }
+
+    /// Saturate `val` into IR1-3, `index` being 0 for IR1
+    fn i32_to_ir(&mut self, index: usize, val: i32, lm: bool) -> i16 {
+        let min = if lm { 0 } else { -0x8000 };
+
+        if val < min || val > 0x7fff {
+            self.set_flag(24 - index as u32);
+        }
+
+        val.clamp(min, 0x7fff) as i16
+    }
+
+    fn set_mac(&mut self, index: usize, val: i64, shift: u32) {
+        self.mac[index + 1] = (val >> shift) as i32;
+    }
+
+    fn mac_to_ir(&mut self, lm: bool) {
+        for i in 0..3 {
+            self.ir[i + 1] = self.i32_to_ir(i, self.mac[i + 1], lm);
+        }
+    }
+
+    fn mac_to_color(&mut self, index: usize) -> u8 {
+        let c = self.mac[index + 1] >> 4;
+
+        if !(0..=0xff).contains(&c) {
+            self.set_flag(21 - index as u32);
+        }
+
+        c.clamp(0, 0xff) as u8
+    }
+
+    fn push_color(&mut self) {
+        let r = self.mac_to_color(0);
+        let g = self.mac_to_color(1);
+        let b = self.mac_to_color(2);
+
+        self.rgb[0] = self.rgb[1];
+        self.rgb[1] = self.rgb[2];
+        self.rgb[2] = [r, g, b, self.rgbc[3]];
+    }
+
+    fn push_sxy(&mut self, x: i16, y: i16) {
+        self.sxy[0] = self.sxy[1];
+        self.sxy[1] = self.sxy[2];
+        self.sxy[2] = (x, y);
+    }
+
+    fn push_sz(&mut self, z: i64) {
+        if !(0..=0xffff).contains(&z) {
+            self.set_flag(18);
+        }
+
+        self.sz[0] = self.sz[1];
+        self.sz[1] = self.sz[2];
+        self.sz[2] = self.sz[3];
+        self.sz[3] = z.clamp(0, 0xffff) as u16;
+    }
+
+    fn set_otz(&mut self, z: i32) {
+        if !(0..=0xffff).contains(&z) {
+            self.set_flag(18);
+        }
+
+        self.otz = z.clamp(0, 0xffff) as u16;
+    }
+
+    /// [MAC1, MAC2, MAC3] = (cv << 12 + M * V) >> sf, followed by the
+    /// saturation of the result into IR1-3
+    fn multiply_matrix_by_vector(
+        &mut self,
+        c: Command,
+        matrix: Matrix,
+        vector_index: usize,
+        cv: ControlVector,
+    ) {
+        let m = self.matrix(matrix);
+        let v = self.v[vector_index];
+        let cv_val = self.control_vector(cv);
+
+        for r in 0..3 {
+            let mut res = (cv_val[r] as i64) << 12;
+
+            // The far color control vector is broken: only the first
+            // column is accumulated with it and that result is thrown away
+            // after setting the flags
+            if cv == ControlVector::FarColor {
+                let tmp = self.i64_to_i44(r, res + m[r][0] as i64 * v[0] as i64);
+                self.i32_to_ir(r, (tmp >> c.shift()) as i32, false);
+                res = 0;
+
+                for col in 1..3 {
+                    res = self.i64_to_i44(r, res + m[r][col] as i64 * v[col] as i64);
+                }
+            } else {
+                for col in 0..3 {
+                    res = self.i64_to_i44(r, res + m[r][col] as i64 * v[col] as i64);
+                }
+            }
+
+            self.set_mac(r, res, c.shift());
+        }
+
+        self.mac_to_ir(c.lm());
+    }
+
+    /// Perspective transformation of vector `index`, returns the raw
+    /// projection factor H/SZ3 for the depth cueing step
+    fn do_rtp(&mut self, c: Command, index: usize) -> i64 {
+        let v = self.v[index];
+        let rt = self.rt;
+        let mut z_shifted = 0;
+
+        for (r, row) in rt.iter().enumerate() {
+            let mut res = (self.tr[r] as i64) << 12;
+
+            for (&m, &v) in row.iter().zip(v.iter()) {
+                res = self.i64_to_i44(r, res + m as i64 * v as i64);
+            }
+
+            self.set_mac(r, res, c.shift());
+
+            if r == 2 {
+                z_shifted = res >> 12;
+            }
+        }
+
+        self.ir[1] = self.i32_to_ir(0, self.mac[1], c.lm());
+        self.ir[2] = self.i32_to_ir(1, self.mac[2], c.lm());
+
+        // IR3 saturation is flagged on the value shifted by 12 regardless of
+        // the sf bit
+        let min = if c.lm() { 0 } else { -0x8000 };
+        if !(-0x8000..=0x7fff).contains(&z_shifted) {
+            self.set_flag(22);
+        }
+        self.ir[3] = self.mac[3].clamp(min, 0x7fff) as i16;
+
+        self.push_sz(z_shifted);
+
+        let projection = self.divide() as i64;
+
+        let x = projection * self.ir[1] as i64 + self.ofx as i64;
+        let y = projection * self.ir[2] as i64 + self.ofy as i64;
+
+        let x = self.i64_to_i32_mac0(x) >> 16;
+        let y = self.i64_to_i32_mac0(y) >> 16;
+
+        if !(-0x400..=0x3ff).contains(&x) {
+            self.set_flag(14);
+        }
+        if !(-0x400..=0x3ff).contains(&y) {
+            self.set_flag(13);
+        }
+
+        self.push_sxy(x.clamp(-0x400, 0x3ff) as i16, y.clamp(-0x400, 0x3ff) as i16);
+
+        projection
+    }
+
+    fn depth_cue(&mut self, projection: i64) {
+        let depth = projection * self.dqa as i64 + self.dqb as i64;
+
+        self.mac[0] = self.i64_to_i32_mac0(depth);
+
+        let ir0 = depth >> 12;
+        if !(0..=0x1000).contains(&ir0) {
+            self.set_flag(12);
+        }
+        self.ir[0] = ir0.clamp(0, 0x1000) as i16;
+    }
+
+    /// Compute H / SZ3 the way the hardware divider does, with an unsigned
+    /// Newton-Raphson approximation saturated to 17 bits
+    fn divide(&mut self) -> u32 {
+        let h = self.h as u32;
+        let sz3 = self.sz[3] as u32;
+
+        if h >= sz3 * 2 {
+            self.set_flag(17);
+            return 0x1ffff;
+        }
+
+        let shift = (sz3 as u16).leading_zeros();
+
+        let n = (h as u64) << shift;
+        let d = (sz3 << shift) as u64;
+
+        let u = UNR_TABLE[((d - 0x7fc0) >> 7) as usize] as u64 + 0x101;
+        let d = (0x200_0080 - d * u) >> 8;
+        let d = (0x000_0080 + d * u) >> 8;
+
+        ((n * d + 0x8000) >> 16).min(0x1ffff) as u32
+    }
+
+    /// [MAC1, MAC2, MAC3] = (base + IR0 * (FC - base)) >> sf with `base`
+    /// given in 1.31.12 fixed point, then push the result as a color
+    fn interpolate(&mut self, c: Command, base: [i64; 3]) {
+        for (i, &base) in base.iter().enumerate() {
+            let fc = (self.fc[i] as i64) << 12;
+            let diff = self.i64_to_i44(i, fc - base) >> c.shift();
+            let ir = self.i32_to_ir(i, diff as i32, false);
+
+            let res = self.i64_to_i44(i, base + self.ir[0] as i64 * ir as i64);
+            self.set_mac(i, res, c.shift());
+        }
+
+        self.mac_to_ir(c.lm());
+        self.push_color();
+    }
+
+    /// Light source and light color stages shared by the NCxx commands:
+    /// IR = BK + LR * (L * V)
+    fn light(&mut self, c: Command, vector_index: usize) {
+        self.multiply_matrix_by_vector(c, Matrix::Light, vector_index, ControlVector::Zero);
+        self.color_from_ir(c);
+    }
+
+    /// IR = BK + LR * IR
+    fn color_from_ir(&mut self, c: Command) {
+        self.v[3] = [self.ir[1], self.ir[2], self.ir[3]];
+        self.multiply_matrix_by_vector(c, Matrix::Color, 3, ControlVector::BackgroundColor);
+    }
+
+    /// [R * IR1, G * IR2, B * IR3] << 4
+    fn color_times_ir(&self) -> [i64; 3] {
+        let mut res = [0; 3];
+
+        for (i, r) in res.iter_mut().enumerate() {
+            *r = ((self.rgbc[i] as i64) * (self.ir[i + 1] as i64)) << 4;
+        }
+
+        res
+    }
+
+    fn color_times_ir_to_mac(&mut self, c: Command) {
+        let base = self.color_times_ir();
+
+        for (i, &v) in base.iter().enumerate() {
+            let v = self.i64_to_i44(i, v);
+            self.set_mac(i, v, c.shift());
+        }
+
+        self.mac_to_ir(c.lm());
+        self.push_color();
+    }
+
+    fn do_ncs(&mut self, c: Command, vector_index: usize) {
+        self.light(c, vector_index);
+        self.push_color();
+    }
+
+    fn do_ncc(&mut self, c: Command, vector_index: usize) {
+        self.light(c, vector_index);
+        self.color_times_ir_to_mac(c);
+    }
+
+    fn do_ncd(&mut self, c: Command, vector_index: usize) {
+        self.light(c, vector_index);
+
+        let base = self.color_times_ir();
+        self.interpolate(c, base);
+    }
+
+    fn do_dpc(&mut self, c: Command, color: [u8; 4]) {
+        let mut base = [0; 3];
+
+        for (i, b) in base.iter_mut().enumerate() {
+            *b = (color[i] as i64) << 16;
+        }
+
+        self.interpolate(c, base);
+    }
+
+    /// Perspective transformation, single
+    fn cmd_rtps(&mut self, c: Command) {
+        let projection = self.do_rtp(c, 0);
+        self.depth_cue(projection);
+    }
+
+    /// Perspective transformation, triple
+    fn cmd_rtpt(&mut self, c: Command) {
+        self.do_rtp(c, 0);
+        self.do_rtp(c, 1);
+        let projection = self.do_rtp(c, 2);
+        self.depth_cue(projection);
+    }
+
+    /// Normal clipping: winding of the triangle in the SXY FIFO
+    fn cmd_nclip(&mut self) {
+        let [(x0, y0), (x1, y1), (x2, y2)] = self.sxy.map(|(x, y)| (x as i64, y as i64));
+
+        let sum = x0 * y1 + x1 * y2 + x2 * y0 - x0 * y2 - x1 * y0 - x2 * y1;
+
+        self.mac[0] = self.i64_to_i32_mac0(sum);
+    }
+
+    /// Outer product of IR with the rotation matrix diagonal
+    fn cmd_op(&mut self, c: Command) {
+        let d = [
+            self.rt[0][0] as i64,
+            self.rt[1][1] as i64,
+            self.rt[2][2] as i64,
+        ];
+        let ir = [self.ir[1] as i64, self.ir[2] as i64, self.ir[3] as i64];
+
+        let res = [
+            ir[2] * d[1] - ir[1] * d[2],
+            ir[0] * d[2] - ir[2] * d[0],
+            ir[1] * d[0] - ir[0] * d[1],
+        ];
+
+        for (i, &v) in res.iter().enumerate() {
+            let v = self.i64_to_i44(i, v);
+            self.set_mac(i, v, c.shift());
+        }
+
+        self.mac_to_ir(c.lm());
+    }
+
+    /// Depth cueing of RGBC
+    fn cmd_dpcs(&mut self, c: Command) {
+        self.do_dpc(c, self.rgbc);
+    }
+
+    /// Depth cueing of the three color FIFO entries
+    fn cmd_dpct(&mut self, c: Command) {
+        for _ in 0..3 {
+            self.do_dpc(c, self.rgb[0]);
+        }
+    }
+
+    /// Interpolation of IR towards the far color
+    fn cmd_intpl(&mut self, c: Command) {
+        let base = [
+            (self.ir[1] as i64) << 12,
+            (self.ir[2] as i64) << 12,
+            (self.ir[3] as i64) << 12,
+        ];
+
+        self.interpolate(c, base);
+    }
+
+    /// Depth cueing of RGBC multiplied by IR
+    fn cmd_dcpl(&mut self, c: Command) {
+        let base = self.color_times_ir();
+        self.interpolate(c, base);
+    }
+
+    /// Generic matrix * vector + control vector
+    fn cmd_mvmva(&mut self, c: Command) {
+        self.v[3] = [self.ir[1], self.ir[2], self.ir[3]];
+
+        self.multiply_matrix_by_vector(c, c.matrix(), c.vector_index(), c.control_vector());
+    }
+
+    /// Normal color, single
+    fn cmd_ncs(&mut self, c: Command) {
+        self.do_ncs(c, 0);
+    }
+
+    /// Normal color, triple
+    fn cmd_nct(&mut self, c: Command) {
+        for v in 0..3 {
+            self.do_ncs(c, v);
+        }
+    }
+
+    /// Normal color with color, single
+    fn cmd_nccs(&mut self, c: Command) {
+        self.do_ncc(c, 0);
+    }
+
+    /// Normal color with color, triple
+    fn cmd_ncct(&mut self, c: Command) {
+        for v in 0..3 {
+            self.do_ncc(c, v);
+        }
+    }
+
+    /// Normal color with depth cueing, single
+    fn cmd_ncds(&mut self, c: Command) {
+        self.do_ncd(c, 0);
+    }
+
+    /// Normal color with depth cueing, triple
+    fn cmd_ncdt(&mut self, c: Command) {
+        for v in 0..3 {
+            self.do_ncd(c, v);
+        }
+    }
+
+    /// Color color: light color matrix applied to IR, times RGBC
+    fn cmd_cc(&mut self, c: Command) {
+        self.color_from_ir(c);
+        self.color_times_ir_to_mac(c);
+    }
+
+    /// Color depth cueing
+    fn cmd_cdp(&mut self, c: Command) {
+        self.color_from_ir(c);
+
+        let base = self.color_times_ir();
+        self.interpolate(c, base);
+    }
+
+    /// Square of IR
+    fn cmd_sqr(&mut self, c: Command) {
+        for i in 0..3 {
+            let ir = self.ir[i + 1] as i64;
+            self.set_mac(i, ir * ir, c.shift());
+        }
+
+        self.mac_to_ir(c.lm());
+    }
+
+    /// Average of the last three screen Z values
+    fn cmd_avsz3(&mut self) {
+        let sum = self.sz[1] as i64 + self.sz[2] as i64 + self.sz[3] as i64;
+        let avg = self.i64_to_i32_mac0(self.zsf3 as i64 * sum);
+
+        self.mac[0] = avg;
+        self.set_otz(avg >> 12);
+    }
+
+    /// Average of the four screen Z values
+    fn cmd_avsz4(&mut self) {
+        let sum = self.sz.iter().map(|&z| z as i64).sum::<i64>();
+        let avg = self.i64_to_i32_mac0(self.zsf4 as i64 * sum);
+
+        self.mac[0] = avg;
+        self.set_otz(avg >> 12);
+    }
+
+    /// General purpose interpolation: IR * IR0
+    fn cmd_gpf(&mut self, c: Command) {
+        let ir0 = self.ir[0] as i64;
+
+        for i in 0..3 {
+            let v = self.i64_to_i44(i, ir0 * self.ir[i + 1] as i64);
+            self.set_mac(i, v, c.shift());
+        }
+
+        self.mac_to_ir(c.lm());
+        self.push_color();
+    }
+
+    /// General purpose interpolation with base: MAC + IR * IR0
+    fn cmd_gpl(&mut self, c: Command) {
+        let ir0 = self.ir[0] as i64;
+
+        for i in 0..3 {
+            let base = (self.mac[i + 1] as i64) << c.shift();
+            let v = self.i64_to_i44(i, base + ir0 * self.ir[i + 1] as i64);
+            self.set_mac(i, v, c.shift());
+        }
+
+        self.mac_to_ir(c.lm());
+        self.push_color();
+    }
+}
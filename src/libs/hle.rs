@@ -0,0 +1,1373 @@
+use std::collections::VecDeque;
+
+use crate::consts;
+use crate::libs::bus::Bus;
+use crate::libs::cpu::CPU;
+use crate::libs::error::EmuError;
+use crate::libs::exe::Exe;
+use crate::libs::iso9660;
+use crate::libs::kernel;
+
+/// Guest callbacks run by the kernel return to this address
+pub const RETURN_ADDR: u32 = 0xbfc0_1000;
+
+/// Executables returning from main end up here
+pub const EXIT_ADDR: u32 = 0xbfc0_1008;
+
+/// WaitEvent idles here until the event is ready
+const WAIT_ADDR: u32 = 0xbfc0_1010;
+
+const EXCEPTION_VECTOR: u32 = 0x8000_0080;
+const DEBUG_VECTOR: u32 = 0x8000_0040;
+
+/// Stack of the callbacks run from an exception, one page per nesting level
+const EXCEPTION_STACK: u32 = 0x8000_fff0;
+const EXCEPTION_STACK_SIZE: u32 = 0x2000;
+
+/// Stack of executables that don't set their own
+const DEFAULT_STACK: u32 = 0x801f_fff0;
+
+const I_STAT: u32 = consts::IRQ_START as u32;
+const I_MASK: u32 = consts::IRQ_START as u32 + 4;
+const GP0: u32 = consts::GPU_START as u32;
+
+/// Interrupts acknowledged once the handlers ran: VBlank and the timers
+const DEFAULT_AUTO_ACK: u16 = 0x0071;
+
+const V0: usize = 2;
+const A0: usize = 4;
+const T1: usize = 9;
+const S0: usize = 16;
+const GP: usize = 28;
+const SP: usize = 29;
+const FP: usize = 30;
+const RA: usize = 31;
+
+/// Event status
+const EVENT_DISABLED: u32 = 0x1000;
+const EVENT_BUSY: u32 = 0x2000;
+const EVENT_READY: u32 = 0x4000;
+
+/// Event mode
+const EVENT_CALLBACK: u32 = 0x1000;
+
+const EVENT_HANDLE: u32 = 0xf100_0000;
+const MAX_EVENTS: usize = 16;
+
+/// RCnt event classes, the VBlank is counter 3
+const RCNT_CLASS: u32 = 0xf200_0000;
+const EVENT_SPEC_INTERRUPT: u32 = 0x0002;
+
+/// File descriptors 0 and 1 are the TTY
+const FIRST_FILE: usize = 2;
+const MAX_FILES: usize = 16;
+
+const ERROR: u32 = 0xffff_ffff;
+
+/// ROM image of the HLE BIOS. Only idle loops are needed since the kernel
+/// calls and exceptions are intercepted.
+pub fn rom() -> Vec<u8> {
+    let mut data = vec![0; consts::BIOS_SIZE];
+
+    for addr in [consts::BIOS_START as u32, RETURN_ADDR, EXIT_ADDR, WAIT_ADDR] {
+        let offset = (addr & 0x7_ffff) as usize;
+
+        data[offset..offset + 4].copy_from_slice(&jump(addr).to_le_bytes());
+    }
+
+    data
+}
+
+/// `j addr`
+fn jump(addr: u32) -> u32 {
+    0x0800_0000 | ((addr >> 2) & 0x03ff_ffff)
+}
+
+/// Return From Exception, pops the interrupt enable and mode stack
+fn rfe(sr: u32) -> u32 {
+    (sr & !0xf) | ((sr >> 2) & 0xf)
+}
+
+#[derive(Clone, Copy)]
+struct Event {
+    class: u32,
+    spec: u32,
+    mode: u32,
+    func: u32,
+    status: u32,
+}
+
+struct File {
+    lba: u32,
+    size: u32,
+    pos: u32,
+}
+
+#[derive(Clone, Copy)]
+struct GuestCall {
+    func: u32,
+    args: [u32; 4],
+    /// Called with the result of `func` when it isn't zero, for the
+    /// interrupt handlers queued with SysEnqIntRP
+    then: Option<u32>,
+}
+
+/// State saved while the kernel runs guest callbacks
+struct Context {
+    r: [u32; 32],
+    hi: u32,
+    lo: u32,
+    pc: u32,
+    /// Status register to pop on return, for exceptions
+    sr: Option<u32>,
+    /// Interrupts to acknowledge on return
+    ack: u16,
+    calls: VecDeque<GuestCall>,
+    then: Option<u32>,
+}
+
+/// First fit allocator, the blocks live in guest memory but the
+/// bookkeeping stays here
+struct Heap {
+    /// (address, size, free)
+    blocks: Vec<(u32, u32, bool)>,
+}
+
+impl Heap {
+    fn new() -> Heap {
+        Heap { blocks: Vec::new() }
+    }
+
+    fn init(&mut self, addr: u32, size: u32) {
+        self.blocks = vec![(addr, size, true)];
+    }
+
+    fn alloc(&mut self, size: u32) -> Option<u32> {
+        let size = size.max(1).div_ceil(4) * 4;
+        let index = self
+            .blocks
+            .iter()
+            .position(|&(_, len, free)| free && len >= size)?;
+        let (addr, len, _) = self.blocks[index];
+
+        self.blocks[index] = (addr, size, false);
+
+        if len > size {
+            self.blocks
+                .insert(index + 1, (addr + size, len - size, true));
+        }
+
+        Some(addr)
+    }
+
+    /// Size of the allocated block at `addr`
+    fn size(&self, addr: u32) -> Option<u32> {
+        self.blocks
+            .iter()
+            .find(|&&(a, _, free)| a == addr && !free)
+            .map(|&(_, len, _)| len)
+    }
+
+    fn free(&mut self, addr: u32) {
+        let Some(index) = self.blocks.iter().position(|&(a, _, _)| a == addr) else {
+            return;
+        };
+
+        self.blocks[index].2 = true;
+
+        if self.blocks.get(index + 1).is_some_and(|b| b.2) {
+            let (_, len, _) = self.blocks.remove(index + 1);
+            self.blocks[index].1 += len;
+        }
+
+        if index > 0 && self.blocks[index - 1].2 {
+            let (_, len, _) = self.blocks.remove(index);
+            self.blocks[index - 1].1 += len;
+        }
+    }
+}
+
+/// High level emulation of the BIOS kernel. The A0h, B0h and C0h functions
+/// and the exception handler are implemented here instead of running the
+/// ROM code.
+pub struct Hle {
+    heap: Heap,
+    kernel_heap: Heap,
+    seed: u32,
+    events: [Option<Event>; MAX_EVENTS],
+    /// Interrupt handlers queued with SysEnqIntRP, by priority
+    chains: [Vec<u32>; 4],
+    auto_ack: u16,
+    /// Controller buffers given to InitPad
+    pads: [(u32, u32); 2],
+    pad_started: bool,
+    files: Vec<Option<File>>,
+    /// Event WaitEvent is waiting for
+    waiting: Option<u32>,
+    /// Callbacks to run once the current kernel call or exception is done
+    pending_calls: VecDeque<GuestCall>,
+    contexts: Vec<Context>,
+    /// The cartridge pre-boot entry already ran
+    pre_boot_done: bool,
+}
+
+impl Hle {
+    pub fn new() -> Hle {
+        let mut kernel_heap = Heap::new();
+        kernel_heap.init(0x8000_a000, 0x4000);
+
+        Hle {
+            heap: Heap::new(),
+            kernel_heap,
+            seed: 0x24040001,
+            events: [None; MAX_EVENTS],
+            chains: Default::default(),
+            auto_ack: DEFAULT_AUTO_ACK,
+            pads: [(0, 0); 2],
+            pad_started: false,
+            files: (0..MAX_FILES).map(|_| None).collect(),
+            waiting: None,
+            pending_calls: VecDeque::new(),
+            contexts: Vec::new(),
+            pre_boot_done: false,
+        }
+    }
+
+    /// Called before the instruction at `pc` runs, returns true if the
+    /// kernel took over
+    pub fn intercept(&mut self, cpu: &mut CPU) -> Result<bool, EmuError> {
+        let pc = cpu.pc();
+
+        match pc & 0x1fff_ffff {
+            addr if addr == consts::BIOS_START as u32 => {
+                if let Err(e) = self.boot(cpu) {
+                    cpu.jump(EXIT_ADDR);
+                    return Err(e);
+                }
+            }
+            table @ (0xa0 | 0xb0 | 0xc0) => {
+                let function = cpu.reg(T1);
+                self.call(cpu, table, function);
+            }
+            _ if pc == EXCEPTION_VECTOR => self.exception(cpu)?,
+            _ if pc == RETURN_ADDR => self.callback_done(cpu)?,
+            _ if pc == WAIT_ADDR => return Ok(self.wait(cpu)),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn boot(&mut self, cpu: &mut CPU) -> Result<(), EmuError> {
+        // Licensed cartridges run first and return to the start of the BIOS
+        if !self.pre_boot_done {
+            self.pre_boot_done = true;
+
+            if let Some(entry) = cpu.bus().cartridge().and_then(|c| c.pre_boot_entry()) {
+                cpu.set_reg(RA, cpu.pc());
+                cpu.jump(entry);
+                return Ok(());
+            }
+        }
+
+        let mut exe = match cpu.take_exe() {
+            Some(exe) => exe,
+            None => disc_exe(cpu.bus()).map_err(|e| {
+                println!("{}", e);
+                EmuError::Kernel {
+                    pc: cpu.pc(),
+                    reason: "no executable to boot",
+                }
+            })?,
+        };
+
+        if exe.sp == 0 {
+            exe.sp = DEFAULT_STACK;
+        }
+
+        // Debug exceptions end up in the general handler unless the guest
+        // installs its own
+        let ram = cpu.bus_mut().ram_mut();
+        let offset = (DEBUG_VECTOR & 0x1f_ffff) as usize;
+        ram.store32(offset, jump(EXCEPTION_VECTOR));
+        ram.store32(offset + 4, 0);
+
+        cpu.set_sr(0x0000_0401);
+        cpu.set_reg(RA, EXIT_ADDR);
+        cpu.start_exe(exe)?;
+
+        Ok(())
+    }
+
+    fn call(&mut self, cpu: &mut CPU, table: u32, function: u32) {
+        let a = [
+            cpu.reg(A0),
+            cpu.reg(A0 + 1),
+            cpu.reg(A0 + 2),
+            cpu.reg(A0 + 3),
+        ];
+
+        let result = match (table, function) {
+            (0xa0, 0x00) | (0xb0, 0x32) => self.open(cpu.bus_mut(), a[0]),
+            (0xa0, 0x01) | (0xb0, 0x33) => self.lseek(a[0], a[1], a[2]),
+            (0xa0, 0x02) | (0xb0, 0x34) => self.read(cpu.bus_mut(), a[0], a[1], a[2]),
+            (0xa0, 0x03) | (0xb0, 0x35) => write(cpu.bus_mut(), a[0], a[1], a[2]),
+            (0xa0, 0x04) | (0xb0, 0x36) => self.close(a[0]),
+            (0xa0, 0x06) | (0xb0, 0x38) => {
+                println!("exit({})", a[0]);
+                cpu.jump(EXIT_ADDR);
+                return;
+            }
+            (0xa0, 0x09) | (0xb0, 0x3b) => {
+                if a[1] == 1 {
+                    cpu.bus_mut().tty_mut().putchar(a[0] as u8);
+                }
+                a[0]
+            }
+            (0xa0, 0x0e) | (0xa0, 0x0f) => (a[0] as i32).unsigned_abs(),
+            (0xa0, 0x10) | (0xa0, 0x11) => atoi(&read_string(cpu.bus_mut(), a[0])) as u32,
+            (0xa0, 0x13) => {
+                setjmp(cpu, a[0]);
+                0
+            }
+            (0xa0, 0x14) => {
+                longjmp(cpu, a[0]);
+                a[1]
+            }
+            (0xa0, 0x15) => strncat(cpu.bus_mut(), a[0], a[1], u32::MAX),
+            (0xa0, 0x16) => strncat(cpu.bus_mut(), a[0], a[1], a[2]),
+            (0xa0, 0x17) => strncmp(cpu.bus_mut(), a[0], a[1], u32::MAX),
+            (0xa0, 0x18) => strncmp(cpu.bus_mut(), a[0], a[1], a[2]),
+            (0xa0, 0x19) => strncpy(cpu.bus_mut(), a[0], a[1], u32::MAX),
+            (0xa0, 0x1a) => strncpy(cpu.bus_mut(), a[0], a[1], a[2]),
+            (0xa0, 0x1b) => read_string(cpu.bus_mut(), a[0]).len() as u32,
+            (0xa0, 0x1c) | (0xa0, 0x1e) => strchr(cpu.bus_mut(), a[0], a[1] as u8, false),
+            (0xa0, 0x1d) | (0xa0, 0x1f) => strchr(cpu.bus_mut(), a[0], a[1] as u8, true),
+            (0xa0, 0x24) => strstr(cpu.bus_mut(), a[0], a[1]),
+            (0xa0, 0x25) => (a[0] as u8).to_ascii_uppercase() as u32,
+            (0xa0, 0x26) => (a[0] as u8).to_ascii_lowercase() as u32,
+            (0xa0, 0x27) => {
+                memmove(cpu.bus_mut(), a[1], a[0], a[2]);
+                a[1]
+            }
+            (0xa0, 0x28) => {
+                memset(cpu.bus_mut(), a[0], 0, a[1]);
+                a[0]
+            }
+            (0xa0, 0x29) | (0xa0, 0x2d) => memcmp(cpu.bus_mut(), a[0], a[1], a[2]),
+            (0xa0, 0x2a) | (0xa0, 0x2c) => {
+                memmove(cpu.bus_mut(), a[0], a[1], a[2]);
+                a[0]
+            }
+            (0xa0, 0x2b) => {
+                memset(cpu.bus_mut(), a[0], a[1] as u8, a[2]);
+                a[0]
+            }
+            (0xa0, 0x2e) => memchr(cpu.bus_mut(), a[0], a[1] as u8, a[2]),
+            (0xa0, 0x2f) => self.rand(),
+            (0xa0, 0x30) => {
+                self.seed = a[0];
+                0
+            }
+            (0xa0, 0x33) => self.heap.alloc(a[0]).unwrap_or(0),
+            (0xa0, 0x34) => {
+                self.heap.free(a[0]);
+                0
+            }
+            (0xa0, 0x37) => {
+                let size = a[0].wrapping_mul(a[1]);
+                let addr = self.heap.alloc(size).unwrap_or(0);
+
+                if addr != 0 {
+                    memset(cpu.bus_mut(), addr, 0, size);
+                }
+                addr
+            }
+            (0xa0, 0x38) => self.realloc(cpu.bus_mut(), a[0], a[1]),
+            (0xa0, 0x39) => {
+                self.heap.init(a[0], a[1]);
+                0
+            }
+            (0xa0, 0x3c) | (0xb0, 0x3d) => {
+                cpu.bus_mut().tty_mut().putchar(a[0] as u8);
+                a[0]
+            }
+            (0xa0, 0x3e) | (0xb0, 0x3f) => {
+                let mut s = read_string(cpu.bus_mut(), a[0]);
+                s.push(b'\n');
+                puts(cpu.bus_mut(), &s);
+                1
+            }
+            (0xa0, 0x3f) => {
+                let sp = cpu.reg(SP);
+                let s = printf(cpu.bus_mut(), a[0], |bus, i| match i {
+                    0..=2 => a[i + 1],
+                    _ => load32(bus, sp.wrapping_add(4 * (i as u32 + 1))),
+                });
+                puts(cpu.bus_mut(), &s);
+                s.len() as u32
+            }
+            (0xa0, 0x44) => 0,
+            (0xa0, 0x49) => {
+                store32(cpu.bus_mut(), GP0, a[0]);
+                0
+            }
+            (0xa0, 0x54) | (0xa0, 0x71) => 1,
+            (0xa0, 0x56) | (0xa0, 0x72) => 0,
+            (0xb0, 0x00) => self.kernel_heap.alloc(a[0]).unwrap_or(0),
+            (0xb0, 0x01) => {
+                self.kernel_heap.free(a[0]);
+                0
+            }
+            (0xb0, 0x02) => init_timer(cpu.bus_mut(), a[0], a[1], a[2]),
+            (0xb0, 0x03) => match a[0] {
+                0..=2 => load32(cpu.bus_mut(), timer(a[0])) & 0xffff,
+                _ => 0,
+            },
+            (0xb0, 0x04) => {
+                set_irq_mask(cpu.bus_mut(), rcnt_irq(a[0]), true);
+                1
+            }
+            (0xb0, 0x05) => {
+                set_irq_mask(cpu.bus_mut(), rcnt_irq(a[0]), false);
+                1
+            }
+            (0xb0, 0x06) => {
+                if a[0] < 3 {
+                    store32(cpu.bus_mut(), timer(a[0]), 0);
+                }
+                1
+            }
+            (0xb0, 0x07) => {
+                self.deliver_event(a[0], a[1]);
+
+                if self.contexts.is_empty() && !self.pending_calls.is_empty() {
+                    self.run_pending(cpu);
+                    return;
+                }
+                0
+            }
+            (0xb0, 0x08) => self.open_event(a),
+            (0xb0, 0x09) => self.set_event_status(a[0], None),
+            (0xb0, 0x0a) => match self.event(a[0]).map(|e| e.status) {
+                Some(EVENT_READY) => self.set_event_status(a[0], Some(EVENT_BUSY)),
+                // Spin on the idle loop until an interrupt delivers it
+                Some(EVENT_BUSY) => {
+                    self.waiting = Some(a[0]);
+                    cpu.jump(WAIT_ADDR);
+                    return;
+                }
+                _ => 0,
+            },
+            (0xb0, 0x0b) => match self.event(a[0]).map(|e| e.status) {
+                Some(EVENT_READY) => self.set_event_status(a[0], Some(EVENT_BUSY)),
+                _ => 0,
+            },
+            (0xb0, 0x0c) => match self.event(a[0]).map(|e| e.status) {
+                Some(EVENT_DISABLED) => self.set_event_status(a[0], Some(EVENT_BUSY)),
+                Some(_) => 1,
+                None => 0,
+            },
+            (0xb0, 0x0d) => match self.event(a[0]) {
+                Some(_) => self.set_event_status(a[0], Some(EVENT_DISABLED)),
+                None => 0,
+            },
+            (0xb0, 0x12) => {
+                self.pads = [(a[0], a[1]), (a[2], a[3])];
+                2
+            }
+            (0xb0, 0x13) => {
+                self.pad_started = true;
+                set_irq_mask(cpu.bus_mut(), 0, true);
+                1
+            }
+            (0xb0, 0x14) => {
+                self.pad_started = false;
+                1
+            }
+            (0xb0, 0x17) => {
+                if !self.contexts.is_empty() {
+                    self.leave(cpu);
+                    return;
+                }
+                0
+            }
+            (0xb0, 0x18) | (0xb0, 0x19) | (0xb0, 0x5b) => 0,
+            (0xb0, 0x20) => {
+                self.undeliver_event(a[0], a[1]);
+                0
+            }
+            (0xc0, 0x00) | (0xc0, 0x01) | (0xc0, 0x0c) | (0xc0, 0x12) | (0xc0, 0x13) => 0,
+            (0xc0, 0x02) => {
+                let chain = &mut self.chains[a[0] as usize & 3];
+                chain.retain(|&s| s != a[1]);
+                chain.insert(0, a[1]);
+                0
+            }
+            (0xc0, 0x03) => {
+                self.chains[a[0] as usize & 3].retain(|&s| s != a[1]);
+                0
+            }
+            (0xc0, 0x0a) => self.set_auto_ack(rcnt_irq(a[0]), a[1] != 0),
+            (0xc0, 0x0d) => self.set_auto_ack(a[0], a[1] != 0),
+            _ => {
+                println!(
+                    "Unimplemented kernel call {:X}:{:02X} {}",
+                    table,
+                    function,
+                    kernel::function_name(table, function).unwrap_or("unknown")
+                );
+                0
+            }
+        };
+
+        cpu.set_reg(V0, result);
+        cpu.jump(cpu.reg(RA));
+    }
+
+    /// Return from WaitEvent once the event is ready, returns false to
+    /// keep idling
+    fn wait(&mut self, cpu: &mut CPU) -> bool {
+        let Some(handle) = self.waiting else {
+            return false;
+        };
+
+        match self.event(handle).map(|e| e.status) {
+            Some(EVENT_BUSY) => return false,
+            Some(EVENT_READY) => {
+                self.set_event_status(handle, Some(EVENT_BUSY));
+                cpu.set_reg(V0, 1);
+            }
+            _ => cpu.set_reg(V0, 0),
+        }
+
+        self.waiting = None;
+        cpu.jump(cpu.reg(RA));
+
+        true
+    }
+
+    fn exception(&mut self, cpu: &mut CPU) -> Result<(), EmuError> {
+        let cause = cpu.cause();
+        let code = (cause >> 2) & 0x1f;
+
+        match code {
+            // Interrupt
+            0x0 => self.interrupt(cpu),
+            // SysCall, the function is in a0
+            0x8 => {
+                let mut sr = cpu.sr();
+
+                match cpu.reg(A0) {
+                    // EnterCriticalSection
+                    1 => {
+                        cpu.set_reg(V0, (sr & 0x404 == 0x404) as u32);
+                        sr &= !0x404;
+                    }
+                    // ExitCriticalSection
+                    2 => sr |= 0x404,
+                    _ => (),
+                }
+
+                cpu.set_sr(rfe(sr));
+                cpu.jump(cpu.epc().wrapping_add(4));
+            }
+            _ => {
+                let epc = cpu.epc();
+
+                // The kernel hangs like the BIOS does
+                cpu.jump(EXIT_ADDR);
+                return Err(EmuError::UnhandledException { epc, code });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn interrupt(&mut self, cpu: &mut CPU) {
+        let bus = cpu.bus_mut();
+        let pending = (load32(bus, I_STAT) & load32(bus, I_MASK)) as u16;
+
+        if pending & 1 != 0 {
+            self.update_pads(bus);
+            self.deliver_event(RCNT_CLASS | 3, EVENT_SPEC_INTERRUPT);
+        }
+
+        for t in 0..3 {
+            if pending & (0x10 << t) != 0 {
+                self.deliver_event(RCNT_CLASS | t, EVENT_SPEC_INTERRUPT);
+            }
+        }
+
+        // struct { next, func2, func1, pad }
+        for &entry in self.chains.iter().flatten() {
+            let func1 = load32(bus, entry.wrapping_add(8));
+            let func2 = load32(bus, entry.wrapping_add(4));
+
+            if func1 != 0 {
+                self.pending_calls.push_back(GuestCall {
+                    func: func1,
+                    args: [0; 4],
+                    then: (func2 != 0).then_some(func2),
+                });
+            }
+        }
+
+        let (epc, sr) = (cpu.epc(), cpu.sr());
+        self.enter(cpu, epc, Some(sr), pending & self.auto_ack);
+    }
+
+    /// Queue the callbacks of the events waiting for `class` and `spec`, or
+    /// mark them as ready
+    fn deliver_event(&mut self, class: u32, spec: u32) {
+        for event in self.events.iter_mut().flatten() {
+            if event.status != EVENT_BUSY || event.class != class || event.spec != spec {
+                continue;
+            }
+
+            if event.mode == EVENT_CALLBACK {
+                if event.func != 0 {
+                    self.pending_calls.push_back(GuestCall {
+                        func: event.func,
+                        args: [0; 4],
+                        then: None,
+                    });
+                }
+            } else {
+                event.status = EVENT_READY;
+            }
+        }
+    }
+
+    fn undeliver_event(&mut self, class: u32, spec: u32) {
+        for event in self.events.iter_mut().flatten() {
+            if event.status == EVENT_READY && event.class == class && event.spec == spec {
+                event.status = EVENT_BUSY;
+            }
+        }
+    }
+
+    fn open_event(&mut self, a: [u32; 4]) -> u32 {
+        match self.events.iter().position(|e| e.is_none()) {
+            Some(index) => {
+                self.events[index] = Some(Event {
+                    class: a[0],
+                    spec: a[1],
+                    mode: a[2],
+                    func: a[3],
+                    status: EVENT_DISABLED,
+                });
+
+                EVENT_HANDLE | index as u32
+            }
+            None => ERROR,
+        }
+    }
+
+    fn event(&self, handle: u32) -> Option<&Event> {
+        self.events.get((handle & 0xffff) as usize)?.as_ref()
+    }
+
+    /// Change the status of event `handle`, None closes it
+    fn set_event_status(&mut self, handle: u32, status: Option<u32>) -> u32 {
+        let Some(slot) = self.events.get_mut((handle & 0xffff) as usize) else {
+            return 0;
+        };
+
+        match (slot.as_mut(), status) {
+            (Some(event), Some(status)) => event.status = status,
+            (Some(_), None) => *slot = None,
+            (None, _) => return 0,
+        }
+
+        1
+    }
+
+    fn set_auto_ack(&mut self, irq: u32, enable: bool) -> u32 {
+        let bit = 1u16 << (irq & 0xf);
+        let old = self.auto_ack & bit != 0;
+
+        self.auto_ack = match enable {
+            true => self.auto_ack | bit,
+            false => self.auto_ack & !bit,
+        };
+
+        old as u32
+    }
+
+    /// No controller is connected, report it in the buffers given to InitPad
+    fn update_pads(&self, bus: &mut Bus) {
+        if !self.pad_started {
+            return;
+        }
+
+        for &(buf, size) in &self.pads {
+            if buf != 0 && size >= 2 {
+                store8(bus, buf, 0xff);
+                store8(bus, buf + 1, 0xff);
+            }
+        }
+    }
+
+    /// Save the CPU state and start running the queued callbacks, the CPU
+    /// resumes at `pc` once they're done
+    fn enter(&mut self, cpu: &mut CPU, pc: u32, sr: Option<u32>, ack: u16) {
+        let mut r = [0; 32];
+        for (i, val) in r.iter_mut().enumerate() {
+            *val = cpu.reg(i);
+        }
+
+        self.contexts.push(Context {
+            r,
+            hi: cpu.hi(),
+            lo: cpu.lo(),
+            pc,
+            sr,
+            ack,
+            calls: std::mem::take(&mut self.pending_calls),
+            then: None,
+        });
+
+        self.resume(cpu);
+    }
+
+    /// Callbacks delivered outside of an exception run before returning to
+    /// the caller
+    fn run_pending(&mut self, cpu: &mut CPU) {
+        let ra = cpu.reg(RA);
+
+        cpu.set_reg(V0, 1);
+        self.enter(cpu, ra, None, 0);
+    }
+
+    fn resume(&mut self, cpu: &mut CPU) {
+        let depth = self.contexts.len() as u32;
+        let context = self.contexts.last_mut().unwrap();
+
+        // Callbacks queued by the callbacks themselves
+        context.calls.extend(self.pending_calls.drain(..));
+
+        let Some(call) = context.calls.pop_front() else {
+            self.leave(cpu);
+            return;
+        };
+
+        context.then = call.then;
+
+        for (i, &arg) in call.args.iter().enumerate() {
+            cpu.set_reg(A0 + i, arg);
+        }
+
+        cpu.set_reg(SP, EXCEPTION_STACK - (depth - 1) * EXCEPTION_STACK_SIZE);
+        cpu.set_reg(RA, RETURN_ADDR);
+        cpu.jump(call.func);
+    }
+
+    fn callback_done(&mut self, cpu: &mut CPU) -> Result<(), EmuError> {
+        let Some(context) = self.contexts.last_mut() else {
+            cpu.jump(EXIT_ADDR);
+            return Err(EmuError::Kernel {
+                pc: RETURN_ADDR,
+                reason: "callback returned without a caller",
+            });
+        };
+
+        let v0 = cpu.reg(V0);
+
+        if let Some(func) = context.then.take() {
+            if v0 != 0 {
+                context.calls.push_front(GuestCall {
+                    func,
+                    args: [v0, 0, 0, 0],
+                    then: None,
+                });
+            }
+        }
+
+        self.resume(cpu);
+
+        Ok(())
+    }
+
+    /// Restore the state saved by `enter`
+    fn leave(&mut self, cpu: &mut CPU) {
+        let context = self.contexts.pop().unwrap();
+
+        for (i, &val) in context.r.iter().enumerate() {
+            cpu.set_reg(i, val);
+        }
+
+        cpu.set_hi(context.hi);
+        cpu.set_lo(context.lo);
+
+        if let Some(sr) = context.sr {
+            cpu.set_sr(rfe(sr));
+        }
+
+        if context.ack != 0 {
+            store32(cpu.bus_mut(), I_STAT, !(context.ack as u32));
+        }
+
+        cpu.jump(context.pc);
+    }
+
+    fn rand(&mut self) -> u32 {
+        self.seed = self.seed.wrapping_mul(0x41c6_4e6d).wrapping_add(0x3039);
+
+        (self.seed >> 16) & 0x7fff
+    }
+
+    fn realloc(&mut self, bus: &mut Bus, addr: u32, size: u32) -> u32 {
+        if addr == 0 {
+            return self.heap.alloc(size).unwrap_or(0);
+        }
+
+        let old = self.heap.size(addr).unwrap_or(0);
+        self.heap.free(addr);
+
+        if size == 0 {
+            return 0;
+        }
+
+        // The old block may be merged back, move the data before anything
+        // overwrites it
+        let data: Vec<u8> = (0..old.min(size)).map(|i| load8(bus, addr + i)).collect();
+        let new = self.heap.alloc(size).unwrap_or(0);
+
+        if new != 0 {
+            for (i, &b) in data.iter().enumerate() {
+                store8(bus, new + i as u32, b);
+            }
+        }
+
+        new
+    }
+
+    fn open(&mut self, bus: &mut Bus, name: u32) -> u32 {
+        let name = String::from_utf8_lossy(&read_string(bus, name)).into_owned();
+
+        let path = match name.split_once(':') {
+            Some((device, path)) if device.eq_ignore_ascii_case("cdrom") => path,
+            _ => {
+                println!("open: unsupported device for {}", name);
+                return ERROR;
+            }
+        };
+
+        let Some(fd) = (FIRST_FILE..MAX_FILES).find(|&fd| self.files[fd].is_none()) else {
+            return ERROR;
+        };
+
+        let entry = bus
+            .disc()
+            .ok_or_else(|| "no disc".to_string())
+            .and_then(|disc| iso9660::find(disc, path));
+
+        match entry {
+            Ok(entry) => {
+                self.files[fd] = Some(File {
+                    lba: entry.lba,
+                    size: entry.size,
+                    pos: 0,
+                });
+
+                fd as u32
+            }
+            Err(e) => {
+                println!("open {}: {}", name, e);
+                ERROR
+            }
+        }
+    }
+
+    fn lseek(&mut self, fd: u32, offset: u32, whence: u32) -> u32 {
+        let Some(Some(file)) = self.files.get_mut(fd as usize) else {
+            return ERROR;
+        };
+
+        file.pos = match whence {
+            0 => offset,
+            1 => file.pos.wrapping_add(offset),
+            _ => return ERROR,
+        };
+
+        file.pos
+    }
+
+    fn read(&mut self, bus: &mut Bus, fd: u32, dst: u32, len: u32) -> u32 {
+        let Some(Some(file)) = self.files.get_mut(fd as usize) else {
+            return ERROR;
+        };
+
+        let len = len.min(file.size.saturating_sub(file.pos));
+        let mut data = Vec::with_capacity(len as usize);
+
+        while (data.len() as u32) < len {
+            let pos = file.pos + data.len() as u32;
+            let lba = file.lba + pos / iso9660::DATA_SIZE as u32;
+            let offset = pos as usize % iso9660::DATA_SIZE;
+            let wanted = (len as usize - data.len()).min(iso9660::DATA_SIZE - offset);
+
+            let sector = bus
+                .disc()
+                .ok_or_else(|| "no disc".to_string())
+                .and_then(|disc| iso9660::read_sector(disc, lba));
+
+            match sector {
+                Ok(sector) => data.extend_from_slice(&sector[offset..offset + wanted]),
+                Err(e) => {
+                    println!("read: {}", e);
+                    break;
+                }
+            }
+        }
+
+        for (i, &b) in data.iter().enumerate() {
+            store8(bus, dst + i as u32, b);
+        }
+
+        file.pos += data.len() as u32;
+
+        data.len() as u32
+    }
+
+    fn close(&mut self, fd: u32) -> u32 {
+        match self.files.get_mut(fd as usize) {
+            Some(file @ Some(_)) => {
+                *file = None;
+                fd
+            }
+            _ => ERROR,
+        }
+    }
+}
+
+/// Executable named by SYSTEM.CNF, or PSX.EXE
+fn disc_exe(bus: &Bus) -> Result<Exe, String> {
+    let disc = bus
+        .disc()
+        .ok_or("The HLE BIOS needs an executable or a disc to boot")?;
+
+    let mut path = "PSX.EXE;1".to_string();
+    let mut stack = None;
+
+    if let Ok(entry) = iso9660::find(disc, "SYSTEM.CNF") {
+        let cnf = iso9660::read(disc, &entry)?;
+
+        for line in String::from_utf8_lossy(&cnf).lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            match key.trim() {
+                "BOOT" => path = value.trim().to_string(),
+                "STACK" => stack = u32::from_str_radix(value.trim(), 16).ok(),
+                _ => (),
+            }
+        }
+    }
+
+    let path = path.split_once(':').map_or(path.as_str(), |(_, p)| p);
+    let entry = iso9660::find(disc, path)?;
+    let mut exe = Exe::parse(&iso9660::read(disc, &entry)?)?;
+
+    if exe.sp == 0 {
+        exe.sp = stack.unwrap_or(0);
+    }
+
+    Ok(exe)
+}
+
+fn setjmp(cpu: &mut CPU, buf: u32) {
+    let regs = [
+        RA,
+        SP,
+        FP,
+        S0,
+        S0 + 1,
+        S0 + 2,
+        S0 + 3,
+        S0 + 4,
+        S0 + 5,
+        S0 + 6,
+        S0 + 7,
+        GP,
+    ];
+
+    for (i, &reg) in regs.iter().enumerate() {
+        let val = cpu.reg(reg);
+        store32(cpu.bus_mut(), buf + 4 * i as u32, val);
+    }
+}
+
+fn longjmp(cpu: &mut CPU, buf: u32) {
+    let regs = [
+        RA,
+        SP,
+        FP,
+        S0,
+        S0 + 1,
+        S0 + 2,
+        S0 + 3,
+        S0 + 4,
+        S0 + 5,
+        S0 + 6,
+        S0 + 7,
+        GP,
+    ];
+
+    for (i, &reg) in regs.iter().enumerate() {
+        let val = load32(cpu.bus_mut(), buf + 4 * i as u32);
+        cpu.set_reg(reg, val);
+    }
+}
+
+fn timer(t: u32) -> u32 {
+    consts::TIMER_REGISTER_START as u32 + t * 0x10
+}
+
+/// Interrupt of root counter `t`, counter 3 is the VBlank
+fn rcnt_irq(t: u32) -> u32 {
+    match t {
+        0..=2 => 4 + t,
+        _ => 0,
+    }
+}
+
+fn init_timer(bus: &mut Bus, t: u32, target: u32, flags: u32) -> u32 {
+    if t < 3 {
+        // Reset and interrupt on target, repeatedly
+        store32(bus, timer(t) + 8, target & 0xffff);
+        store32(bus, timer(t) + 4, 0x0058 | (flags & 0x0300));
+    }
+
+    1
+}
+
+fn set_irq_mask(bus: &mut Bus, irq: u32, enable: bool) {
+    let mask = load32(bus, I_MASK);
+    let bit = 1 << irq;
+
+    store32(bus, I_MASK, if enable { mask | bit } else { mask & !bit });
+}
+
+fn write(bus: &mut Bus, fd: u32, src: u32, len: u32) -> u32 {
+    if fd > 1 {
+        return ERROR;
+    }
+
+    let data: Vec<u8> = (0..len).map(|i| load8(bus, src + i)).collect();
+    puts(bus, &data);
+
+    len
+}
+
+fn puts(bus: &mut Bus, s: &[u8]) {
+    for &c in s {
+        bus.tty_mut().putchar(c);
+    }
+}
+
+fn atoi(s: &[u8]) -> i32 {
+    let s = s.trim_ascii_start();
+    let (negative, digits) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let val = digits
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .fold(0i32, |n, &c| {
+            n.wrapping_mul(10).wrapping_add((c - b'0') as i32)
+        });
+
+    if negative {
+        val.wrapping_neg()
+    } else {
+        val
+    }
+}
+
+fn strncat(bus: &mut Bus, dst: u32, src: u32, n: u32) -> u32 {
+    let len = read_string(bus, dst).len() as u32;
+
+    strncpy(bus, dst + len, src, n);
+
+    // strncat always terminates the string
+    let copied = read_string(bus, src).len().min(n as usize) as u32;
+    store8(bus, dst + len + copied, 0);
+
+    dst
+}
+
+fn strncmp(bus: &mut Bus, a: u32, b: u32, n: u32) -> u32 {
+    for i in 0..n {
+        let x = load8(bus, a.wrapping_add(i));
+        let y = load8(bus, b.wrapping_add(i));
+
+        if x != y || x == 0 {
+            return (x as i32 - y as i32) as u32;
+        }
+    }
+
+    0
+}
+
+fn strncpy(bus: &mut Bus, dst: u32, src: u32, n: u32) -> u32 {
+    for i in 0..n {
+        let c = load8(bus, src.wrapping_add(i));
+        store8(bus, dst.wrapping_add(i), c);
+
+        if c == 0 {
+            break;
+        }
+    }
+
+    dst
+}
+
+fn strchr(bus: &mut Bus, s: u32, c: u8, last: bool) -> u32 {
+    let string = read_string(bus, s);
+    let mut positions = string
+        .iter()
+        .chain(&[0])
+        .enumerate()
+        .filter(|&(_, &x)| x == c);
+
+    let found = match last {
+        true => positions.last(),
+        false => positions.next(),
+    };
+
+    found.map_or(0, |(i, _)| s + i as u32)
+}
+
+fn strstr(bus: &mut Bus, s: u32, sub: u32) -> u32 {
+    let string = read_string(bus, s);
+    let sub = read_string(bus, sub);
+
+    if sub.is_empty() {
+        return s;
+    }
+
+    string
+        .windows(sub.len())
+        .position(|w| w == sub.as_slice())
+        .map_or(0, |i| s + i as u32)
+}
+
+fn memmove(bus: &mut Bus, dst: u32, src: u32, len: u32) {
+    let data: Vec<u8> = (0..len).map(|i| load8(bus, src.wrapping_add(i))).collect();
+
+    for (i, &b) in data.iter().enumerate() {
+        store8(bus, dst.wrapping_add(i as u32), b);
+    }
+}
+
+fn memset(bus: &mut Bus, dst: u32, val: u8, len: u32) {
+    for i in 0..len {
+        store8(bus, dst.wrapping_add(i), val);
+    }
+}
+
+fn memcmp(bus: &mut Bus, a: u32, b: u32, len: u32) -> u32 {
+    for i in 0..len {
+        let x = load8(bus, a.wrapping_add(i));
+        let y = load8(bus, b.wrapping_add(i));
+
+        if x != y {
+            return (x as i32 - y as i32) as u32;
+        }
+    }
+
+    0
+}
+
+fn memchr(bus: &mut Bus, s: u32, c: u8, len: u32) -> u32 {
+    (0..len)
+        .map(|i| s.wrapping_add(i))
+        .find(|&addr| load8(bus, addr) == c)
+        .unwrap_or(0)
+}
+
+/// Format `fmt` like the BIOS printf, `arg(bus, i)` returns the i-th
+/// argument after the format string
+fn printf<F>(bus: &mut Bus, fmt: u32, mut arg: F) -> Vec<u8>
+where
+    F: FnMut(&mut Bus, usize) -> u32,
+{
+    let fmt = read_string(bus, fmt);
+    let mut out = Vec::new();
+    let mut next = 0;
+    let mut chars = fmt.iter().copied().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != b'%' {
+            out.push(c);
+            continue;
+        }
+
+        let mut left = false;
+        let mut zero = false;
+        let mut sign = None;
+
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                b'-' => left = true,
+                b'0' => zero = true,
+                b'+' | b' ' => sign = Some(flag),
+                b'#' => (),
+                _ => break,
+            }
+            chars.next();
+        }
+
+        let mut width = 0;
+        let mut precision = None;
+
+        if chars.peek() == Some(&b'*') {
+            chars.next();
+            width = arg(bus, next) as usize;
+            next += 1;
+        }
+
+        while let Some(d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            width = width * 10 + (d - b'0') as usize;
+            chars.next();
+        }
+
+        if chars.peek() == Some(&b'.') {
+            chars.next();
+            let mut p = 0;
+
+            while let Some(d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                p = p * 10 + (d - b'0') as usize;
+                chars.next();
+            }
+
+            precision = Some(p);
+        }
+
+        while matches!(chars.peek(), Some(b'l' | b'h')) {
+            chars.next();
+        }
+
+        let Some(conversion) = chars.next() else {
+            break;
+        };
+
+        let mut field = match conversion {
+            b'%' => {
+                out.push(b'%');
+                continue;
+            }
+            b'd' | b'i' => {
+                let val = arg(bus, next) as i32;
+                let digits = val.unsigned_abs().to_string();
+
+                match (val < 0, sign) {
+                    (true, _) => format!("-{}", digits),
+                    (false, Some(s)) => format!("{}{}", s as char, digits),
+                    (false, None) => digits,
+                }
+                .into_bytes()
+            }
+            b'u' => arg(bus, next).to_string().into_bytes(),
+            b'x' => format!("{:x}", arg(bus, next)).into_bytes(),
+            b'X' => format!("{:X}", arg(bus, next)).into_bytes(),
+            b'o' => format!("{:o}", arg(bus, next)).into_bytes(),
+            b'p' => format!("{:08x}", arg(bus, next)).into_bytes(),
+            b'c' => vec![arg(bus, next) as u8],
+            b's' => {
+                let addr = arg(bus, next);
+                let mut s = read_string(bus, addr);
+
+                if let Some(p) = precision {
+                    s.truncate(p);
+                }
+                s
+            }
+            other => {
+                out.extend_from_slice(&[b'%', other]);
+                continue;
+            }
+        };
+
+        next += 1;
+
+        let pad = width.saturating_sub(field.len());
+
+        if left {
+            field.extend(std::iter::repeat_n(b' ', pad));
+        } else if zero && conversion != b's' && conversion != b'c' {
+            let at = field
+                .first()
+                .map_or(0, |c| matches!(c, b'-' | b'+' | b' ') as usize);
+            field.splice(at..at, std::iter::repeat_n(b'0', pad));
+        } else {
+            field.splice(0..0, std::iter::repeat_n(b' ', pad));
+        }
+
+        out.extend_from_slice(&field);
+    }
+
+    out
+}
+
+/// NUL terminated string at `addr`
+fn read_string(bus: &mut Bus, addr: u32) -> Vec<u8> {
+    (0..0x1000)
+        .map(|i| load8(bus, addr.wrapping_add(i)))
+        .take_while(|&c| c != 0)
+        .collect()
+}
+
+// Kernel accesses bypass the bus for RAM, nothing here needs to be logged
+
+fn load8(bus: &mut Bus, addr: u32) -> u8 {
+    match bus.ram_offset(addr as usize) {
+        Some(offset) => bus.ram().load8(offset),
+        None => bus.load8(addr as usize).unwrap_or_else(|e| {
+            println!("Kernel {}", e);
+            0
+        }),
+    }
+}
+
+fn store8(bus: &mut Bus, addr: u32, val: u8) {
+    match bus.ram_offset(addr as usize) {
+        Some(offset) => bus.ram_mut().store8(offset, val),
+        None => {
+            if let Err(e) = bus.store8(addr as usize, val) {
+                println!("Kernel {}", e);
+            }
+        }
+    }
+}
+
+fn load32(bus: &mut Bus, addr: u32) -> u32 {
+    match bus.ram_offset(addr as usize) {
+        Some(_) => u32::from_le_bytes([0, 1, 2, 3].map(|i| load8(bus, addr.wrapping_add(i)))),
+        None => bus.load32(addr as usize).unwrap_or_else(|e| {
+            println!("Kernel {}", e);
+            0
+        }),
+    }
+}
+
+fn store32(bus: &mut Bus, addr: u32, val: u32) {
+    match bus.ram_offset(addr as usize) {
+        Some(_) => {
+            for (i, b) in val.to_le_bytes().into_iter().enumerate() {
+                store8(bus, addr.wrapping_add(i as u32), b);
+            }
+        }
+        None => {
+            if let Err(e) = bus.store32(addr as usize, val) {
+                println!("Kernel {}", e);
+            }
+        }
+    }
+}
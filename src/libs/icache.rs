@@ -0,0 +1,114 @@
+/// CACHE_CONTROL bits
+pub const TAG_TEST: u32 = 1 << 2;
+pub const ENABLE: u32 = 1 << 11;
+
+const LINES: usize = 256;
+
+/// Four consecutive words, each with its own valid bit
+#[derive(Clone, Copy)]
+struct Line {
+    /// Address bits 31:12 of the cached words
+    tag: u32,
+    valid: [bool; 4],
+    words: [u32; 4],
+}
+
+/// 4KB direct mapped instruction cache
+pub struct ICache {
+    lines: [Line; LINES],
+}
+
+impl ICache {
+    pub fn new() -> ICache {
+        let line = Line {
+            tag: 0,
+            valid: [false; 4],
+            words: [0; 4],
+        };
+
+        Self {
+            lines: [line; LINES],
+        }
+    }
+
+    /// Only KUSEG and KSEG0 go through the cache
+    pub fn cached(addr: u32) -> bool {
+        addr >> 29 < 5
+    }
+
+    fn split(addr: u32) -> (usize, usize, u32) {
+        let addr = addr & 0x1fff_ffff;
+
+        (
+            (addr as usize >> 4) % LINES,
+            (addr as usize >> 2) & 3,
+            addr & !0xfff,
+        )
+    }
+
+    /// Word at `addr`, `None` on a miss
+    pub fn fetch(&self, addr: u32) -> Option<u32> {
+        let (line, word, tag) = Self::split(addr);
+        let line = &self.lines[line];
+
+        match line.tag == tag && line.valid[word] {
+            true => Some(line.words[word]),
+            false => None,
+        }
+    }
+
+    /// Words of `addr`'s line from `addr` onwards, as loaded on a miss
+    pub fn fill_range(addr: u32) -> impl Iterator<Item = u32> {
+        let first = (addr >> 2) & 3;
+
+        (first..4).map(move |i| (addr & !0xf) + 4 * i)
+    }
+
+    /// Store a word fetched from memory. The line is retagged and its other
+    /// words dropped if it held another address.
+    pub fn fill(&mut self, addr: u32, val: u32) {
+        let (line, word, tag) = Self::split(addr);
+        let line = &mut self.lines[line];
+
+        if line.tag != tag {
+            line.tag = tag;
+            line.valid = [false; 4];
+        }
+
+        line.words[word] = val;
+        line.valid[word] = true;
+    }
+
+    /// Store while the cache is isolated, `control` is CACHE_CONTROL
+    pub fn isolated_store(&mut self, control: u32, addr: u32, val: u32) {
+        if control & ENABLE == 0 {
+            return;
+        }
+
+        let (line, word, tag) = Self::split(addr);
+        let line = &mut self.lines[line];
+
+        if control & TAG_TEST != 0 {
+            line.tag = tag;
+            line.valid = [false; 4];
+        } else {
+            line.words[word] = val;
+        }
+    }
+
+    /// Drop the word at `addr` if it is cached
+    pub fn invalidate(&mut self, addr: u32) {
+        let (line, word, tag) = Self::split(addr);
+        let line = &mut self.lines[line];
+
+        if line.tag == tag {
+            line.valid[word] = false;
+        }
+    }
+
+    pub fn invalidate_all(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.valid = [false; 4];
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/// Interrupt controller, I_STAT and I_MASK
+pub struct Irq {
+    status: u16,
+    mask: u16,
+}
+
+impl Irq {
+    pub fn new() -> Irq {
+        Irq { status: 0, mask: 0 }
+    }
+
+    /// True when an unmasked interrupt is pending, this drives bit 10 of the
+    /// COP0 cause register
+    pub fn active(&self) -> bool {
+        self.status & self.mask != 0
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Writing 0 to a bit of I_STAT acknowledges it, writing 1 leaves it
+    /// unchanged
+    pub fn ack(&mut self, val: u16) {
+        self.status &= val;
+    }
+
+    pub fn mask(&self) -> u16 {
+        self.mask
+    }
+
+    pub fn set_mask(&mut self, val: u16) {
+        self.mask = val & 0x7ff;
+    }
+
+    pub fn assert(&mut self, which: Interrupt) {
+        self.status |= 1 << (which as usize);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interrupt {
+    /// Vertical blanking, from the GPU
+    VBlank = 0,
+    /// GP0(1Fh) interrupt request
+    Gpu = 1,
+    CdRom = 2,
+    Dma = 3,
+    Timer0 = 4,
+    Timer1 = 5,
+    Timer2 = 6,
+    /// Controller and memory card byte received
+    PadMemCard = 7,
+    /// Serial port. There is no SIO emulation yet, nothing raises it.
+    Sio = 8,
+    Spu = 9,
+    /// Controller lightpen, shares its line with the PIO extension port
+    Lightpen = 10,
+}
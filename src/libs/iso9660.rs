@@ -0,0 +1,132 @@
+use crate::libs::disc::Disc;
+
+/// Size of the user data in a Mode 1 or Mode 2 Form 1 sector
+pub const DATA_SIZE: usize = 2048;
+
+/// The primary volume descriptor follows the 16 sector system area
+const VOLUME_DESCRIPTOR: u32 = 16;
+
+/// File or directory in the ISO9660 file system of a data disc
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub lba: u32,
+    pub size: u32,
+    pub dir: bool,
+}
+
+/// User data of sector `lba`, skipping the sync pattern, the header and the
+/// Mode 2 subheader
+pub fn read_sector(disc: &Disc, lba: u32) -> Result<&[u8], String> {
+    let sector = disc
+        .sector_lba(lba)
+        .ok_or_else(|| format!("Sector {} is past the end of the disc", lba))?;
+
+    let start = match sector[15] {
+        1 => 16,
+        2 => 24,
+        mode => {
+            return Err(format!(
+                "Sector {} isn't a data sector (mode {})",
+                lba, mode
+            ))
+        }
+    };
+
+    Ok(&sector[start..start + DATA_SIZE])
+}
+
+/// Find `path`, made of components separated by `\` or `/`. The version
+/// suffix (`;1`) is optional and names are case insensitive.
+pub fn find(disc: &Disc, path: &str) -> Result<Entry, String> {
+    let mut entry = root(disc)?;
+
+    for component in path.split(['\\', '/']).filter(|c| !c.is_empty()) {
+        if !entry.dir {
+            return Err(format!("{} isn't a directory", entry.name));
+        }
+
+        let wanted = strip_version(component);
+
+        entry = list(disc, &entry)?
+            .into_iter()
+            .find(|e| strip_version(&e.name).eq_ignore_ascii_case(wanted))
+            .ok_or_else(|| format!("{} not found on the disc", path))?;
+    }
+
+    Ok(entry)
+}
+
+/// Contents of the file `entry`
+pub fn read(disc: &Disc, entry: &Entry) -> Result<Vec<u8>, String> {
+    let mut data = Vec::with_capacity(entry.size as usize);
+    let mut lba = entry.lba;
+
+    while data.len() < entry.size as usize {
+        let remaining = entry.size as usize - data.len();
+        let sector = read_sector(disc, lba)?;
+
+        data.extend_from_slice(&sector[..remaining.min(DATA_SIZE)]);
+        lba += 1;
+    }
+
+    Ok(data)
+}
+
+/// Entries of the directory `dir`, without `.` and `..`
+pub fn list(disc: &Disc, dir: &Entry) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let sectors = (dir.size as usize).div_ceil(DATA_SIZE) as u32;
+
+    for lba in dir.lba..dir.lba + sectors {
+        let sector = read_sector(disc, lba)?;
+        let mut offset = 0;
+
+        // Records don't cross sector boundaries, the rest is zero padded
+        while offset < DATA_SIZE && sector[offset] != 0 {
+            let record = &sector[offset..];
+            let len = record[0] as usize;
+
+            if len < 34 || offset + len > DATA_SIZE {
+                return Err(format!("Bad directory record in sector {}", lba));
+            }
+
+            let name = &record[33..33 + record[32] as usize];
+
+            // The first two records are `.` and `..`
+            if name != [0] && name != [1] {
+                entries.push(parse_record(record));
+            }
+
+            offset += len;
+        }
+    }
+
+    Ok(entries)
+}
+
+fn root(disc: &Disc) -> Result<Entry, String> {
+    let descriptor = read_sector(disc, VOLUME_DESCRIPTOR)?;
+
+    if descriptor[0] != 1 || &descriptor[1..6] != b"CD001" {
+        return Err("No ISO9660 primary volume descriptor".to_string());
+    }
+
+    Ok(parse_record(&descriptor[156..]))
+}
+
+fn parse_record(record: &[u8]) -> Entry {
+    let word = |offset: usize| u32::from_le_bytes(record[offset..offset + 4].try_into().unwrap());
+    let name = &record[33..33 + record[32] as usize];
+
+    Entry {
+        name: String::from_utf8_lossy(name).into_owned(),
+        lba: word(2),
+        size: word(10),
+        dir: record[25] & 2 != 0,
+    }
+}
+
+fn strip_version(name: &str) -> &str {
+    name.split(';').next().unwrap_or(name)
+}
@@ -0,0 +1,407 @@
+use std::io::Write;
+
+/// Kernel functions reached through the A0h table
+const A0_FUNCTIONS: [&str; 0xb5] = [
+    "open",
+    "lseek",
+    "read",
+    "write",
+    "close",
+    "ioctl",
+    "exit",
+    "isatty",
+    "getc",
+    "putc",
+    "todigit",
+    "atof",
+    "strtoul",
+    "strtol",
+    "abs",
+    "labs",
+    "atoi",
+    "atol",
+    "atob",
+    "setjmp",
+    "longjmp",
+    "strcat",
+    "strncat",
+    "strcmp",
+    "strncmp",
+    "strcpy",
+    "strncpy",
+    "strlen",
+    "index",
+    "rindex",
+    "strchr",
+    "strrchr",
+    "strpbrk",
+    "strspn",
+    "strcspn",
+    "strtok",
+    "strstr",
+    "toupper",
+    "tolower",
+    "bcopy",
+    "bzero",
+    "bcmp",
+    "memcpy",
+    "memset",
+    "memmove",
+    "memcmp",
+    "memchr",
+    "rand",
+    "srand",
+    "qsort",
+    "strtod",
+    "malloc",
+    "free",
+    "lsearch",
+    "bsearch",
+    "calloc",
+    "realloc",
+    "InitHeap",
+    "SystemErrorExit",
+    "std_in_getchar",
+    "std_out_putchar",
+    "std_in_gets",
+    "std_out_puts",
+    "printf",
+    "SystemErrorUnresolvedException",
+    "LoadExeHeader",
+    "LoadExeFile",
+    "DoExecute",
+    "FlushCache",
+    "init_a0_b0_c0_vectors",
+    "GPU_dw",
+    "gpu_send_dma",
+    "SendGP1Command",
+    "GPU_cw",
+    "GPU_cwp",
+    "send_gpu_linked_list",
+    "gpu_abort_dma",
+    "GetGPUStatus",
+    "gpu_sync",
+    "SystemError",
+    "SystemError",
+    "LoadAndExecute",
+    "GetSysSp",
+    "SystemError",
+    "CdInit",
+    "_bu_init",
+    "CdRemove",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "dev_tty_init",
+    "dev_tty_open",
+    "dev_tty_in_out",
+    "dev_tty_ioctl",
+    "dev_cd_open",
+    "dev_cd_read",
+    "dev_cd_close",
+    "dev_cd_firstfile",
+    "dev_cd_nextfile",
+    "dev_cd_chdir",
+    "dev_card_open",
+    "dev_card_read",
+    "dev_card_write",
+    "dev_card_close",
+    "dev_card_firstfile",
+    "dev_card_nextfile",
+    "dev_card_erase",
+    "dev_card_undelete",
+    "dev_card_format",
+    "dev_card_rename",
+    "card_clear_error",
+    "_bu_init",
+    "CdInit",
+    "CdRemove",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "CdAsyncSeekL",
+    "nop",
+    "nop",
+    "nop",
+    "CdAsyncGetStatus",
+    "nop",
+    "CdAsyncReadSector",
+    "nop",
+    "nop",
+    "CdAsyncSetMode",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "CdromIoIrqFunc1",
+    "CdromDmaIrqFunc1",
+    "CdromIoIrqFunc2",
+    "CdromDmaIrqFunc2",
+    "CdromGetInt5errCode",
+    "CdInitSubFunc",
+    "AddCDROMDevice",
+    "AddMemCardDevice",
+    "AddDuartTtyDevice",
+    "AddDummyTtyDevice",
+    "SystemError",
+    "SystemError",
+    "SetConf",
+    "GetConf",
+    "SetCdromIrqAutoAbort",
+    "SetMemSize",
+    "WarmBoot",
+    "SystemErrorBootOrDiskFailure",
+    "EnqueueCdIntr",
+    "DequeueCdIntr",
+    "CdGetLbn",
+    "CdReadSector",
+    "CdGetStatus",
+    "bu_callback_okay",
+    "bu_callback_err_write",
+    "bu_callback_err_busy",
+    "bu_callback_err_eject",
+    "_card_info",
+    "_card_async_load_directory",
+    "set_card_auto_format",
+    "bu_callback_err_prev_write",
+    "card_write_test",
+    "nop",
+    "nop",
+    "ioabort_raw",
+    "nop",
+    "GetSystemInfo",
+];
+
+/// Kernel functions reached through the B0h table
+const B0_FUNCTIONS: [&str; 0x5e] = [
+    "alloc_kernel_memory",
+    "free_kernel_memory",
+    "init_timer",
+    "get_timer",
+    "enable_timer_irq",
+    "disable_timer_irq",
+    "restart_timer",
+    "DeliverEvent",
+    "OpenEvent",
+    "CloseEvent",
+    "WaitEvent",
+    "TestEvent",
+    "EnableEvent",
+    "DisableEvent",
+    "OpenThread",
+    "CloseThread",
+    "ChangeThread",
+    "jump_to_00000000h",
+    "InitPad",
+    "StartPad",
+    "StopPad",
+    "OutdatedPadInitAndStart",
+    "OutdatedPadGetButtons",
+    "ReturnFromException",
+    "SetDefaultExitFromException",
+    "SetCustomExitFromException",
+    "SystemError",
+    "SystemError",
+    "SystemError",
+    "SystemError",
+    "SystemError",
+    "SystemError",
+    "UnDeliverEvent",
+    "SystemError",
+    "SystemError",
+    "SystemError",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "SystemError",
+    "SystemError",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "jump_to_00000000h",
+    "open",
+    "lseek",
+    "read",
+    "write",
+    "close",
+    "ioctl",
+    "exit",
+    "isatty",
+    "getc",
+    "putc",
+    "std_in_getchar",
+    "std_out_putchar",
+    "std_in_gets",
+    "std_out_puts",
+    "chdir",
+    "FormatDevice",
+    "firstfile",
+    "nextfile",
+    "rename",
+    "erase",
+    "undelete",
+    "AddDevice",
+    "RemoveDevice",
+    "PrintInstalledDevices",
+    "InitCard",
+    "StartCard",
+    "StopCard",
+    "_card_info_subfunc",
+    "write_card_sector",
+    "read_card_sector",
+    "allow_new_card",
+    "Krom2RawAdd",
+    "SystemError",
+    "Krom2Offset",
+    "GetLastError",
+    "GetLastFileError",
+    "GetC0Table",
+    "GetB0Table",
+    "get_bu_callback_port",
+    "testdevice",
+    "SystemError",
+    "ChangeClearPad",
+    "get_card_status",
+    "wait_card_status",
+];
+
+/// Kernel functions reached through the C0h table
+const C0_FUNCTIONS: [&str; 0x1e] = [
+    "EnqueueTimerAndVblankIrqs",
+    "EnqueueSyscallHandler",
+    "SysEnqIntRP",
+    "SysDeqIntRP",
+    "get_free_EvCB_slot",
+    "get_free_TCB_slot",
+    "ExceptionHandler",
+    "InstallExceptionHandlers",
+    "SysInitMemory",
+    "SysInitKernelVariables",
+    "ChangeClearRCnt",
+    "SystemError",
+    "InitDefInt",
+    "SetIrqAutoAck",
+    "dev_sio_init",
+    "dev_sio_open",
+    "dev_sio_in_out",
+    "dev_sio_ioctl",
+    "InstallDevices",
+    "FlushStdInOutPut",
+    "SystemError",
+    "tty_cdevinput",
+    "tty_cdevscan",
+    "tty_circgetc",
+    "tty_circputc",
+    "ioabort",
+    "set_card_find_mode",
+    "KernelRedirect",
+    "AdjustA0Table",
+    "get_card_find_mode",
+];
+
+/// Calls that never come back to their caller
+const NO_RETURN: [(u32, u32); 4] = [(0xa0, 0x14), (0xa0, 0x06), (0xb0, 0x17), (0xb0, 0x38)];
+
+/// Nested calls are tracked up to this depth, deeper calls are dropped
+const MAX_DEPTH: usize = 64;
+
+/// Name of kernel function `function` of the `table` (0xa0, 0xb0 or 0xc0)
+pub fn function_name(table: u32, function: u32) -> Option<&'static str> {
+    let names: &[&str] = match table {
+        0xa0 => &A0_FUNCTIONS,
+        0xb0 => &B0_FUNCTIONS,
+        0xc0 => &C0_FUNCTIONS,
+        _ => return None,
+    };
+
+    names.get(function as usize).copied()
+}
+
+struct PendingCall {
+    table: u32,
+    function: u32,
+    ra: u32,
+}
+
+/// Logs the kernel calls made through the A0h, B0h and C0h vectors along
+/// with their return value
+pub struct KernelTracer {
+    out: Box<dyn Write>,
+    pending: Vec<PendingCall>,
+}
+
+impl KernelTracer {
+    pub fn new(out: Box<dyn Write>) -> KernelTracer {
+        KernelTracer {
+            out,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Called before the instruction at `pc` runs
+    pub fn step(&mut self, pc: u32, r: &[u32; 32]) {
+        if let Some(depth) = self.pending.iter().rposition(|c| c.ra == pc) {
+            // Anything called after this one isn't coming back
+            self.pending.truncate(depth + 1);
+            let call = self.pending.pop().unwrap();
+
+            let _ = writeln!(
+                self.out,
+                "{} = {:08x}",
+                Self::describe(call.table, call.function),
+                r[2]
+            );
+        }
+
+        let table = pc & 0x1fff_ffff;
+
+        if !matches!(table, 0xa0 | 0xb0 | 0xc0) {
+            return;
+        }
+
+        // Function number in t1, arguments in a0-a3
+        let function = r[9];
+
+        let _ = writeln!(
+            self.out,
+            "{}({:08x}, {:08x}, {:08x}, {:08x})",
+            Self::describe(table, function),
+            r[4],
+            r[5],
+            r[6],
+            r[7]
+        );
+
+        if !NO_RETURN.contains(&(table, function)) && self.pending.len() < MAX_DEPTH {
+            self.pending.push(PendingCall {
+                table,
+                function,
+                ra: r[31],
+            });
+        }
+    }
+
+    fn describe(table: u32, function: u32) -> String {
+        let name = function_name(table, function).unwrap_or("unknown");
+
+        format!("{:X}:{:02X} {}", table, function, name)
+    }
+}
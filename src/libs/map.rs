@@ -1,6 +1,7 @@
 pub mod memory {
     use crate::consts;
 
+    #[derive(Clone, Copy)]
     pub struct Range(usize, usize);
 
     impl Range {
@@ -25,9 +26,17 @@ pub mod memory {
             let Range(start, length) = self;
 
             if offset >= start && offset < (start + length) {
-                return Some(offset - start);
+                Some(offset - start)
             } else {
-                return None;
+                None
+            }
+        }
+
+        /// Like `contains`, but the range isn't visible through KSEG1
+        pub fn contains_cached(self, offset: usize) -> Option<usize> {
+            match offset >> 29 {
+                5 => None,
+                _ => self.contains(offset),
             }
         }
     }
@@ -36,15 +45,41 @@ pub mod memory {
     pub const MEM_CONTROL: Range = Range(consts::HARDWARE_REGISTER_START, 36);
     pub const SYS_CONTROL: Range = Range(consts::SYS_CONTROL_START, 36);
     pub const RAM_SIZE: Range = Range(consts::RAM_SIZE_START, 4);
-    pub const RAM: Range = Range(consts::RAM_START, consts::RAM_SIZE);
+    pub const RAM: Range = Range(consts::RAM_START, consts::RAM_WINDOW_SIZE);
+    /// D-cache used as fast RAM, see `contains_cached`
+    pub const SCRATCHPAD: Range = Range(consts::SCRATCHPAD_START, consts::SCRATCHPAD_SIZE);
     pub const CACHE_CONTROL: Range = Range(consts::CACHE_CONTROL_START, 4);
     pub const SPU: Range = Range(consts::SPU_START, 640);
-    pub const EXPANSION_1: Range = Range(consts::EXPANSION_1_START, 176);
+    pub const EXPANSION_1: Range = Range(consts::EXPANSION_1_START, consts::EXPANSION_1_SIZE);
     pub const EXPANSION_2: Range = Range(consts::EXPANSION_2_START, 66);
     pub const IRQ_CONTROL: Range = Range(consts::IRQ_START, 8);
     pub const TIMERS: Range = Range(consts::TIMER_REGISTER_START, 48);
     pub const DMA: Range = Range(consts::DMA_START, 0x80);
     pub const GPU: Range = Range(consts::GPU_START, 8);
+    pub const CDROM: Range = Range(consts::CDROM_START, 4);
+
+    const ALL: [Range; 14] = [
+        BIOS,
+        MEM_CONTROL,
+        SYS_CONTROL,
+        RAM_SIZE,
+        RAM,
+        CACHE_CONTROL,
+        SPU,
+        EXPANSION_1,
+        EXPANSION_2,
+        IRQ_CONTROL,
+        TIMERS,
+        DMA,
+        GPU,
+        CDROM,
+    ];
+
+    /// Whether any device responds at `addr`
+    pub fn is_mapped(addr: usize) -> bool {
+        ALL.iter().any(|range| range.contains(addr).is_some())
+            || SCRATCHPAD.contains_cached(addr).is_some()
+    }
 }
 
 pub mod opcode {
@@ -1,9 +1,32 @@
 pub mod bios;
 pub mod bus;
+pub mod cartridge;
+pub mod cdrom;
 pub mod channel;
 pub mod cpu;
+pub mod disasm;
+pub mod disc;
 pub mod dma;
+pub mod error;
+pub mod exe;
+pub mod framebuffer;
+pub mod gdb;
+pub mod gpu;
+pub mod gte;
+pub mod hle;
+pub mod icache;
+pub mod irq;
+pub mod iso9660;
+pub mod kernel;
 pub mod map;
+pub mod monitor;
 pub mod ram;
+pub mod rasterizer;
+pub mod scheduler;
+pub mod scratchpad;
 #[cfg(test)]
 pub mod tests;
+pub mod timers;
+pub mod trace;
+pub mod tty;
+pub mod watch;
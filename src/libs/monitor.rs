@@ -0,0 +1,357 @@
+use std::io::{BufRead, Write};
+
+use crate::libs::cpu::CPU;
+use crate::libs::disasm::{self, REGISTERS};
+use crate::libs::map::opcode::Instruction;
+use crate::libs::watch::{Access, Watchpoint};
+
+const HELP: &str = "Commands:
+    s [N]                   step N instructions (default: 1)
+    c [PC]                  continue, until PC if given
+    b PC                    set a breakpoint
+    bd PC                   delete a breakpoint
+    w ADDR [WIDTH] [r|w|rw] set a watchpoint (default: 4 bytes, rw)
+    wd ADDR                 delete the watchpoints at ADDR
+    l                       list the breakpoints and watchpoints
+    r                       dump the registers
+    x ADDR [LEN]            dump LEN bytes of memory (default: 64)
+    u [ADDR] [N]            disassemble N instructions (default: around pc)
+    q                       quit
+An empty line repeats the last command, addresses are hexadecimal.";
+
+enum Action {
+    Prompt,
+    Resume,
+    Quit,
+}
+
+/// Command line debugger, halts before the first instruction
+pub struct Monitor {
+    input: Box<dyn BufRead>,
+    out: Box<dyn Write>,
+    breakpoints: Vec<u32>,
+    halted: bool,
+    /// Instructions left to run before halting
+    steps: Option<u64>,
+    until: Option<u32>,
+    /// A breakpoint on the address execution resumes from doesn't fire
+    resume_pc: Option<u32>,
+    last: String,
+}
+
+impl Monitor {
+    pub fn new(input: Box<dyn BufRead>, out: Box<dyn Write>) -> Monitor {
+        Monitor {
+            input,
+            out,
+            breakpoints: Vec::new(),
+            halted: true,
+            steps: None,
+            until: None,
+            resume_pc: None,
+            last: String::new(),
+        }
+    }
+
+    /// Prompt before the next instruction, dropping any pending step count
+    /// or run target
+    pub fn halt(&mut self) {
+        self.halted = true;
+        self.steps = None;
+        self.until = None;
+    }
+
+    /// Run before every instruction, prompts while halted. Returns false
+    /// once the user quits.
+    pub fn poll(&mut self, cpu: &mut CPU) -> bool {
+        if !self.halted {
+            match self.stop_reason(cpu) {
+                Some(reason) => {
+                    self.print(&reason);
+                    self.halted = true;
+                }
+                None => return true,
+            }
+        }
+
+        let current = self.instruction(cpu, cpu.pc());
+        self.print(&current);
+
+        loop {
+            let _ = write!(self.out, "> ");
+            let _ = self.out.flush();
+
+            let mut line = String::new();
+
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => return false,
+                Ok(_) => (),
+            }
+
+            let line = match line.trim() {
+                "" => self.last.clone(),
+                line => line.to_string(),
+            };
+
+            self.last = line.clone();
+
+            match self.command(cpu, &line) {
+                Action::Prompt => (),
+                Action::Resume => {
+                    self.halted = false;
+                    self.resume_pc = Some(cpu.pc());
+                    cpu.take_watch_hit();
+                    return true;
+                }
+                Action::Quit => return false,
+            }
+        }
+    }
+
+    fn stop_reason(&mut self, cpu: &mut CPU) -> Option<String> {
+        if let Some(hit) = cpu.take_watch_hit() {
+            self.steps = None;
+            self.until = None;
+
+            let kind = if hit.write { "write" } else { "read" };
+
+            return Some(format!(
+                "Watchpoint: {} of {} byte(s) at {:08x}",
+                kind, hit.watchpoint.len, hit.addr
+            ));
+        }
+
+        let pc = cpu.pc();
+
+        if let Some(steps) = &mut self.steps {
+            *steps -= 1;
+
+            if *steps == 0 {
+                self.steps = None;
+                return Some(String::new());
+            }
+        }
+
+        if self.until == Some(pc) {
+            self.until = None;
+            return Some(format!("Reached {:08x}", pc));
+        }
+
+        if self.resume_pc.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.steps = None;
+            self.until = None;
+            return Some(format!("Breakpoint at {:08x}", pc));
+        }
+
+        None
+    }
+
+    fn command(&mut self, cpu: &mut CPU, line: &str) -> Action {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let hex = |i: usize| words.get(i).map(|w| parse_hex(w));
+        let count = |i: usize| words.get(i).map(|w| w.parse::<u64>().ok());
+
+        let output = match words.as_slice() {
+            [] => String::new(),
+            ["h" | "help", ..] => HELP.to_string(),
+            ["q" | "quit", ..] => return Action::Quit,
+            ["s", ..] => match count(1) {
+                None => {
+                    self.steps = Some(1);
+                    return Action::Resume;
+                }
+                Some(Some(n)) if n > 0 => {
+                    self.steps = Some(n);
+                    return Action::Resume;
+                }
+                _ => format!("Invalid count {}", words[1]),
+            },
+            ["c", ..] => match hex(1) {
+                None => return Action::Resume,
+                Some(Some(pc)) => {
+                    self.until = Some(pc);
+                    return Action::Resume;
+                }
+                _ => format!("Invalid address {}", words[1]),
+            },
+            ["b", _] => match hex(1).flatten() {
+                Some(pc) => {
+                    if !self.breakpoints.contains(&pc) {
+                        self.breakpoints.push(pc);
+                    }
+                    format!("Breakpoint at {:08x}", pc)
+                }
+                None => format!("Invalid address {}", words[1]),
+            },
+            ["bd", _] => match hex(1).flatten() {
+                Some(pc) if self.breakpoints.contains(&pc) => {
+                    self.breakpoints.retain(|&b| b != pc);
+                    format!("Deleted the breakpoint at {:08x}", pc)
+                }
+                _ => format!("No breakpoint at {}", words[1]),
+            },
+            ["w", _, ..] if words.len() <= 4 => {
+                let len = match words.get(2) {
+                    None => Some(4),
+                    Some(w) => w.parse().ok().filter(|len| [1, 2, 4].contains(len)),
+                };
+
+                let access = match words.get(3) {
+                    None | Some(&"rw") => Some(Access::ReadWrite),
+                    Some(&"r") => Some(Access::Read),
+                    Some(&"w") => Some(Access::Write),
+                    Some(_) => None,
+                };
+
+                match (hex(1).flatten(), len, access) {
+                    (Some(addr), Some(len), Some(access)) => {
+                        cpu.add_watchpoint(Watchpoint { addr, len, access });
+                        format!("Watchpoint on {} byte(s) at {:08x}", len, addr)
+                    }
+                    _ => "Usage: w ADDR [1|2|4] [r|w|rw]".to_string(),
+                }
+            }
+            ["wd", _] => match hex(1).flatten() {
+                Some(addr) => {
+                    let matching: Vec<Watchpoint> = cpu
+                        .watchpoints()
+                        .iter()
+                        .filter(|w| w.addr == addr)
+                        .copied()
+                        .collect();
+
+                    for watchpoint in &matching {
+                        cpu.remove_watchpoint(watchpoint);
+                    }
+
+                    format!("Deleted {} watchpoint(s)", matching.len())
+                }
+                None => format!("Invalid address {}", words[1]),
+            },
+            ["l"] => {
+                let breakpoints = self
+                    .breakpoints
+                    .iter()
+                    .map(|pc| format!("breakpoint {:08x}", pc));
+
+                let watchpoints = cpu
+                    .watchpoints()
+                    .iter()
+                    .map(|w| format!("watchpoint {:08x} {} {:?}", w.addr, w.len, w.access));
+
+                breakpoints
+                    .chain(watchpoints)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            ["r"] => registers(cpu),
+            ["x", _] | ["x", _, _] => match (hex(1).flatten(), count(2).unwrap_or(Some(64))) {
+                (Some(addr), Some(len)) => hexdump(cpu, addr, len as u32),
+                _ => "Usage: x ADDR [LEN]".to_string(),
+            },
+            ["u", ..] if words.len() <= 3 => {
+                let pc = cpu.pc();
+
+                match (hex(1), count(2).unwrap_or(Some(10))) {
+                    (None, Some(n)) => self.disassemble(cpu, pc.wrapping_sub(16), n as u32),
+                    (Some(Some(addr)), Some(n)) => self.disassemble(cpu, addr, n as u32),
+                    _ => "Usage: u [ADDR] [N]".to_string(),
+                }
+            }
+            _ => format!("Unknown command {}, h for help", line),
+        };
+
+        self.print(&output);
+        Action::Prompt
+    }
+
+    /// `=>` marks the pc and `*` the breakpoints
+    fn instruction(&self, cpu: &CPU, addr: u32) -> String {
+        let marker = match (addr == cpu.pc(), self.breakpoints.contains(&addr)) {
+            (true, _) => "=>",
+            (false, true) => " *",
+            (false, false) => "  ",
+        };
+
+        match peek32(cpu, addr) {
+            Some(word) => {
+                let i = Instruction(word);
+                format!(
+                    "{} {:08x}: {:08x} {}",
+                    marker,
+                    addr,
+                    word,
+                    disasm::disassemble(i, Some(addr))
+                )
+            }
+            None => format!("{} {:08x}: ????????", marker, addr),
+        }
+    }
+
+    fn disassemble(&self, cpu: &CPU, addr: u32, n: u32) -> String {
+        (0..n)
+            .map(|i| self.instruction(cpu, (addr & !3).wrapping_add(4 * i)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn print(&mut self, text: &str) {
+        if !text.is_empty() {
+            let _ = writeln!(self.out, "{}", text);
+        }
+    }
+}
+
+fn registers(cpu: &CPU) -> String {
+    let gprs = (0..32)
+        .map(|n| format!("{:>4}: {:08x}", REGISTERS[n], cpu.reg(n)))
+        .collect::<Vec<_>>()
+        .chunks(4)
+        .map(|row| row.join("  "))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("{}\n{}", gprs, cpu)
+}
+
+fn hexdump(cpu: &CPU, addr: u32, len: u32) -> String {
+    let mut lines = Vec::new();
+
+    for line in (0..len).step_by(16) {
+        let start = addr.wrapping_add(line);
+        let bytes: Vec<Option<u8>> = (0..16.min(len - line))
+            .map(|i| cpu.bus().peek8(start.wrapping_add(i) as usize))
+            .collect();
+
+        let hex: Vec<String> = bytes
+            .iter()
+            .map(|b| b.map_or("??".to_string(), |b| format!("{:02x}", b)))
+            .collect();
+
+        let ascii: String = bytes
+            .iter()
+            .map(|b| match b {
+                Some(b) if b.is_ascii_graphic() || *b == b' ' => *b as char,
+                _ => '.',
+            })
+            .collect();
+
+        lines.push(format!("{:08x}: {:<47}  {}", start, hex.join(" "), ascii));
+    }
+
+    lines.join("\n")
+}
+
+fn peek32(cpu: &CPU, addr: u32) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = cpu.bus().peek8(addr.wrapping_add(i as u32) as usize)?;
+    }
+
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn parse_hex(val: &str) -> Option<u32> {
+    u32::from_str_radix(val.trim_start_matches("0x"), 16).ok()
+}
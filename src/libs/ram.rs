@@ -7,7 +7,7 @@ pub struct Ram {
 impl Ram {
     pub fn new() -> Ram {
         let data = vec![0xca; consts::RAM_SIZE];
-        Self { data: data }
+        Self { data }
     }
 
     pub fn load8(&self, addr: usize) -> u8 {
@@ -0,0 +1,396 @@
+use crate::libs::gpu::{TextureDepth, VRAM_HEIGHT, VRAM_WIDTH};
+
+/// 4x4 ordered dithering matrix, added to the 8-bit color components before
+/// they get truncated to 5 bits
+const DITHER: [[i32; 4]; 4] = [
+    [-4, 0, -3, 1],
+    [2, -2, 3, -1],
+    [-3, 1, -4, 0],
+    [3, -1, 2, -2],
+];
+
+/// Polygons bigger than this are silently dropped by the GPU
+const MAX_WIDTH: i32 = 1023;
+const MAX_HEIGHT: i32 = 511;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+    /// Screen position, drawing offset included
+    pub x: i32,
+    pub y: i32,
+    /// 24-bit RGB color
+    pub color: [u8; 3],
+    /// Texture coordinates
+    pub u: u8,
+    pub v: u8,
+}
+
+impl Vertex {
+    pub fn new(x: i32, y: i32, color: [u8; 3]) -> Vertex {
+        Vertex {
+            x,
+            y,
+            color,
+            u: 0,
+            v: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Texture {
+    /// Texture page position in VRAM pixels
+    pub page_x: usize,
+    pub page_y: usize,
+    pub depth: TextureDepth,
+    /// Color lookup table position in VRAM pixels, 4 and 8 bit textures only
+    pub clut_x: usize,
+    pub clut_y: usize,
+    /// Raw textures aren't modulated by the vertex color
+    pub raw: bool,
+}
+
+/// GP0(E2h) texture window, in units of 8 pixels
+#[derive(Copy, Clone)]
+pub struct TextureWindow {
+    pub mask_x: u8,
+    pub mask_y: u8,
+    pub offset_x: u8,
+    pub offset_y: u8,
+}
+
+impl TextureWindow {
+    fn apply(&self, u: u8, v: u8) -> (u8, u8) {
+        let u = (u & !(self.mask_x << 3)) | ((self.offset_x & self.mask_x) << 3);
+        let v = (v & !(self.mask_y << 3)) | ((self.offset_y & self.mask_y) << 3);
+
+        (u, v)
+    }
+}
+
+/// Software renderer drawing GP0 primitives into VRAM
+pub struct Rasterizer<'a> {
+    vram: &'a mut [u16],
+    /// Drawing area, inclusive
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub dithering: bool,
+    /// Semi-transparency mode of the current texture page
+    pub semi_transparency: u8,
+    pub force_set_mask_bit: bool,
+    pub preserve_masked_pixels: bool,
+    pub window: TextureWindow,
+}
+
+impl<'a> Rasterizer<'a> {
+    pub fn new(vram: &'a mut [u16]) -> Rasterizer<'a> {
+        Rasterizer {
+            vram,
+            left: 0,
+            top: 0,
+            right: VRAM_WIDTH as i32 - 1,
+            bottom: VRAM_HEIGHT as i32 - 1,
+            dithering: false,
+            semi_transparency: 0,
+            force_set_mask_bit: false,
+            preserve_masked_pixels: false,
+            window: TextureWindow {
+                mask_x: 0,
+                mask_y: 0,
+                offset_x: 0,
+                offset_y: 0,
+            },
+        }
+    }
+
+    /// Quads are drawn as two triangles sharing the v1-v2 edge
+    pub fn quad(&mut self, v: [Vertex; 4], texture: Option<Texture>, gouraud: bool, semi: bool) {
+        self.triangle([v[0], v[1], v[2]], texture, gouraud, semi);
+        self.triangle([v[1], v[2], v[3]], texture, gouraud, semi);
+    }
+
+    pub fn triangle(
+        &mut self,
+        v: [Vertex; 3],
+        texture: Option<Texture>,
+        gouraud: bool,
+        semi: bool,
+    ) {
+        let (v0, mut v1, mut v2) = (v[0], v[1], v[2]);
+
+        let mut area = edge(&v0, &v1, v2.x, v2.y);
+        if area == 0 {
+            return;
+        }
+
+        // Make the winding consistent so that inside pixels have positive
+        // edge functions
+        if area < 0 {
+            std::mem::swap(&mut v1, &mut v2);
+            area = -area;
+        }
+
+        let min_x = v0.x.min(v1.x).min(v2.x);
+        let max_x = v0.x.max(v1.x).max(v2.x);
+        let min_y = v0.y.min(v1.y).min(v2.y);
+        let max_y = v0.y.max(v1.y).max(v2.y);
+
+        if max_x - min_x > MAX_WIDTH || max_y - min_y > MAX_HEIGHT {
+            return;
+        }
+
+        let dither = self.dithering && (gouraud || texture.is_some_and(|t| !t.raw));
+
+        // Pixels lying exactly on an edge are only drawn for top and left
+        // edges so that adjacent polygons don't overlap
+        let bias = [
+            top_left(&v1, &v2) as i64 - 1,
+            top_left(&v2, &v0) as i64 - 1,
+            top_left(&v0, &v1) as i64 - 1,
+        ];
+
+        for y in min_y.max(self.top)..=max_y.min(self.bottom) {
+            for x in min_x.max(self.left)..=max_x.min(self.right) {
+                let w = [
+                    edge(&v1, &v2, x, y),
+                    edge(&v2, &v0, x, y),
+                    edge(&v0, &v1, x, y),
+                ];
+
+                if w.iter().zip(bias).any(|(&w, bias)| w + bias < 0) {
+                    continue;
+                }
+
+                let interpolate = |a: u8, b: u8, c: u8| {
+                    ((w[0] * a as i64 + w[1] * b as i64 + w[2] * c as i64) / area) as i32
+                };
+
+                let color = match gouraud {
+                    true => [0, 1, 2].map(|i| interpolate(v0.color[i], v1.color[i], v2.color[i])),
+                    false => v0.color.map(|c| c as i32),
+                };
+
+                let uv = texture.map(|_| {
+                    (
+                        interpolate(v0.u, v1.u, v2.u) as u8,
+                        interpolate(v0.v, v1.v, v2.v) as u8,
+                    )
+                });
+
+                self.plot(x, y, color, texture, uv, dither, semi);
+            }
+        }
+    }
+
+    /// Draw a line including both end points
+    pub fn line(&mut self, a: Vertex, b: Vertex, gouraud: bool, semi: bool) {
+        let dx = b.x - a.x;
+        let dy = b.y - a.y;
+
+        if dx.abs() > MAX_WIDTH || dy.abs() > MAX_HEIGHT {
+            return;
+        }
+
+        let dither = self.dithering && gouraud;
+        let steps = dx.abs().max(dy.abs());
+
+        for i in 0..=steps {
+            let x = a.x + step(dx, i, steps);
+            let y = a.y + step(dy, i, steps);
+
+            let color = match gouraud && steps > 0 {
+                true => [0, 1, 2].map(|c| {
+                    let from = a.color[c] as i32;
+                    let to = b.color[c] as i32;
+
+                    from + (to - from) * i / steps
+                }),
+                false => a.color.map(|c| c as i32),
+            };
+
+            self.plot(x, y, color, None, None, dither, semi);
+        }
+    }
+
+    /// Draw a rectangle starting at `origin`. Texture coordinates increment
+    /// (or decrement when flipped) by one per pixel and wrap around.
+    #[allow(clippy::too_many_arguments)]
+    pub fn rectangle(
+        &mut self,
+        origin: Vertex,
+        width: i32,
+        height: i32,
+        texture: Option<Texture>,
+        semi: bool,
+        flip_x: bool,
+        flip_y: bool,
+    ) {
+        let color = origin.color.map(|c| c as i32);
+
+        for dy in 0..height {
+            let y = origin.y + dy;
+            let v = match flip_y {
+                true => origin.v.wrapping_sub(dy as u8),
+                false => origin.v.wrapping_add(dy as u8),
+            };
+
+            for dx in 0..width {
+                let x = origin.x + dx;
+                let u = match flip_x {
+                    true => origin.u.wrapping_sub(dx as u8),
+                    false => origin.u.wrapping_add(dx as u8),
+                };
+
+                let uv = texture.map(|_| (u, v));
+
+                // Rectangles are never dithered
+                self.plot(x, y, color, texture, uv, false, semi);
+            }
+        }
+    }
+
+    /// Shade and write a single pixel. `color` holds the 8-bit RGB
+    /// components of the vertex color.
+    #[allow(clippy::too_many_arguments)]
+    fn plot(
+        &mut self,
+        x: i32,
+        y: i32,
+        color: [i32; 3],
+        texture: Option<Texture>,
+        uv: Option<(u8, u8)>,
+        dither: bool,
+        semi: bool,
+    ) {
+        if x < self.left || x > self.right || y < self.top || y > self.bottom {
+            return;
+        }
+
+        let index = (y as usize % VRAM_HEIGHT) * VRAM_WIDTH + (x as usize % VRAM_WIDTH);
+        let background = self.vram[index];
+
+        if self.preserve_masked_pixels && background & 0x8000 != 0 {
+            return;
+        }
+
+        let (mut rgb, mask, blend) = match (texture, uv) {
+            (Some(texture), Some((u, v))) => {
+                let texel = self.texel(&texture, u, v);
+
+                // Fully black texels are transparent
+                if texel == 0 {
+                    return;
+                }
+
+                let t = split_rgb15(texel);
+                let rgb = match texture.raw {
+                    true => t.map(|c| c << 3),
+                    false => [0, 1, 2].map(|i| (t[i] * color[i]) >> 4),
+                };
+
+                // Only texels with their mask bit set are semi-transparent
+                let stp = texel & 0x8000 != 0;
+
+                (rgb, stp, semi && stp)
+            }
+            _ => (color, false, semi),
+        };
+
+        if dither {
+            let offset = DITHER[(y & 3) as usize][(x & 3) as usize];
+            rgb = rgb.map(|c| c + offset);
+        }
+
+        let mut out = rgb.map(|c| c.clamp(0, 0xff) >> 3);
+
+        if blend {
+            let back = split_rgb15(background);
+
+            for (f, b) in out.iter_mut().zip(back) {
+                *f = match self.semi_transparency {
+                    0 => (b + *f) >> 1,
+                    1 => b + *f,
+                    2 => b - *f,
+                    _ => b + (*f >> 2),
+                }
+                .clamp(0, 0x1f);
+            }
+        }
+
+        let mask = (mask || self.force_set_mask_bit) as u16;
+
+        self.vram[index] =
+            (out[0] as u16) | ((out[1] as u16) << 5) | ((out[2] as u16) << 10) | (mask << 15);
+    }
+
+    fn texel(&self, texture: &Texture, u: u8, v: u8) -> u16 {
+        let (u, v) = self.window.apply(u, v);
+        let (u, v) = (u as usize, v as usize);
+
+        let y = (texture.page_y + v) % VRAM_HEIGHT;
+        let row = y * VRAM_WIDTH;
+
+        let clut = |index: u16| {
+            let x = (texture.clut_x + index as usize) % VRAM_WIDTH;
+            self.vram[texture.clut_y * VRAM_WIDTH + x]
+        };
+
+        match texture.depth {
+            TextureDepth::T4Bit => {
+                let x = (texture.page_x + u / 4) % VRAM_WIDTH;
+                let index = (self.vram[row + x] >> ((u & 3) * 4)) & 0xf;
+
+                clut(index)
+            }
+            TextureDepth::T8Bit => {
+                let x = (texture.page_x + u / 2) % VRAM_WIDTH;
+                let index = (self.vram[row + x] >> ((u & 1) * 8)) & 0xff;
+
+                clut(index)
+            }
+            TextureDepth::T15Bit => {
+                let x = (texture.page_x + u) % VRAM_WIDTH;
+
+                self.vram[row + x]
+            }
+        }
+    }
+}
+
+/// Edge function of `p` relative to the a->b edge, twice the signed area of
+/// the triangle they form
+fn edge(a: &Vertex, b: &Vertex, x: i32, y: i32) -> i64 {
+    let (ax, ay) = (a.x as i64, a.y as i64);
+    let (bx, by) = (b.x as i64, b.y as i64);
+
+    (bx - ax) * (y as i64 - ay) - (by - ay) * (x as i64 - ax)
+}
+
+/// True when the inside of the a->b edge is towards the right, or below it
+/// when the edge is horizontal
+fn top_left(a: &Vertex, b: &Vertex) -> bool {
+    let nx = a.y - b.y;
+    let ny = b.x - a.x;
+
+    nx > 0 || (nx == 0 && ny > 0)
+}
+
+/// Offset of step `i` out of `steps` along a distance of `d`, rounded to the
+/// nearest pixel
+fn step(d: i32, i: i32, steps: i32) -> i32 {
+    if steps == 0 {
+        return 0;
+    }
+
+    (d * i * 2 + steps * d.signum()) / (steps * 2)
+}
+
+fn split_rgb15(val: u16) -> [i32; 3] {
+    [
+        (val & 0x1f) as i32,
+        ((val >> 5) & 0x1f) as i32,
+        ((val >> 10) & 0x1f) as i32,
+    ]
+}
@@ -0,0 +1,59 @@
+/// Queue of events due after a number of CPU cycles
+pub struct Scheduler<E> {
+    /// CPU cycles elapsed since the scheduler was created
+    now: u64,
+    /// Pending events with the cycle they're due at
+    events: Vec<(u64, E)>,
+}
+
+impl<E: Copy + PartialEq> Scheduler<E> {
+    pub fn new() -> Scheduler<E> {
+        Scheduler {
+            now: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn advance(&mut self, cycles: u32) {
+        self.now += cycles as u64;
+    }
+
+    pub fn schedule(&mut self, delay: u64, event: E) {
+        self.events.push((self.now + delay, event));
+    }
+
+    /// Remove every pending instance of `event`
+    pub fn cancel(&mut self, event: E) {
+        self.events.retain(|&(_, e)| e != event);
+    }
+
+    pub fn is_scheduled(&self, event: E) -> bool {
+        self.events.iter().any(|&(_, e)| e == event)
+    }
+
+    /// Cycles left until `event` fires, if it's pending
+    pub fn remaining(&self, event: E) -> Option<u64> {
+        self.events
+            .iter()
+            .filter(|&&(_, e)| e == event)
+            .map(|&(due, _)| due.saturating_sub(self.now))
+            .min()
+    }
+
+    /// Take the earliest event that is due, events due at the same cycle
+    /// are returned in the order they were scheduled
+    pub fn pop(&mut self) -> Option<E> {
+        let (index, _) = self
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, &(due, _))| due <= self.now)
+            .min_by_key(|(_, &(due, _))| due)?;
+
+        Some(self.events.remove(index).1)
+    }
+}
@@ -0,0 +1,40 @@
+use crate::consts;
+
+/// 1KB of the D-cache, mapped as fast RAM. Only the CPU can reach it.
+pub struct Scratchpad {
+    data: [u8; consts::SCRATCHPAD_SIZE],
+}
+
+impl Scratchpad {
+    pub fn new() -> Scratchpad {
+        Self {
+            data: [0; consts::SCRATCHPAD_SIZE],
+        }
+    }
+
+    pub fn load8(&self, offset: usize) -> u8 {
+        self.data[offset]
+    }
+
+    pub fn store8(&mut self, offset: usize, val: u8) {
+        self.data[offset] = val;
+    }
+
+    pub fn load16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    pub fn store16(&mut self, offset: usize, val: u16) {
+        self.data[offset..offset + 2].copy_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn load32(&self, offset: usize) -> u32 {
+        let bytes: [u8; 4] = self.data[offset..offset + 4].try_into().unwrap();
+
+        u32::from_le_bytes(bytes)
+    }
+
+    pub fn store32(&mut self, offset: usize, val: u32) {
+        self.data[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+}
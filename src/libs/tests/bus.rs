@@ -0,0 +1,76 @@
+use crate::libs::bios::Bios;
+use crate::libs::bus::Bus;
+use crate::libs::error::BusError;
+use crate::libs::ram::Ram;
+
+fn bus() -> Bus {
+    Bus::new(Bios::from_bytes(Vec::new()), Ram::new())
+}
+
+#[test]
+pub fn ram_mirrors() {
+    let mut bus = bus();
+
+    bus.store32(0x8000_0100, 0x1234_5678).unwrap();
+
+    for mirror in [0x0020_0100, 0x8040_0100, 0xa060_0100] {
+        assert_eq!(bus.load32(mirror), Ok(0x1234_5678));
+    }
+
+    bus.store8(0x0060_0100, 0xab).unwrap();
+    assert_eq!(bus.load16(0x8000_0100), Ok(0x56ab));
+    assert_eq!(bus.ram_offset(0x807f_fffc), Some(0x1f_fffc));
+}
+
+#[test]
+pub fn ram_size_register() {
+    let mut bus = bus();
+
+    assert_eq!(bus.load32(0x1f80_1060), Ok(0xb88));
+
+    // 2MB of memory, 2MB high-Z, 4MB locked
+    bus.store32(0x1f80_1060, 0xc88).unwrap();
+    assert_eq!(bus.load32(0x1f80_1060), Ok(0xc88));
+    assert_eq!(bus.ram_size(), 0xc88);
+
+    bus.store32(0x8020_0000, 0x1234_5678).unwrap();
+    assert_eq!(bus.load32(0x8020_0000), Ok(0xffff_ffff));
+    assert_eq!(bus.load32(0x8000_0000), Ok(0xcaca_caca));
+    assert_eq!(bus.ram_offset(0x8020_0000), None);
+
+    assert_eq!(
+        bus.load32(0x8040_0000),
+        Err(BusError::Unmapped {
+            addr: 0x8040_0000,
+            width: 4,
+            val: None,
+        })
+    );
+    assert!(bus.store8(0x807f_ffff, 0).is_err());
+}
+
+#[test]
+pub fn ram_window_smaller_than_ram() {
+    let mut bus = bus();
+
+    // 1MB of memory, 7MB locked
+    bus.store32(0x1f80_1060, 0x088).unwrap();
+
+    assert!(bus.load32(0x000f_fffc).is_ok());
+    assert!(bus.load32(0x0010_0000).is_err());
+}
+
+#[test]
+pub fn mem_control_registers() {
+    let mut bus = bus();
+
+    bus.store32(0x1f80_1000, 0x1f00_0000).unwrap();
+    bus.store32(0x1f80_1010, 0x0013_243f).unwrap();
+
+    assert_eq!(bus.load32(0x1f80_1000), Ok(0x1f00_0000));
+    assert_eq!(bus.load32(0x1f80_1010), Ok(0x0013_243f));
+
+    // Relocating expansion 1 isn't supported
+    assert!(bus.store32(0x1f80_1000, 0x1e00_0000).is_err());
+    assert_eq!(bus.load32(0x1f80_1000), Ok(0x1f00_0000));
+}
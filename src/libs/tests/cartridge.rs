@@ -0,0 +1,118 @@
+use crate::libs::bios::Bios;
+use crate::libs::bus::Bus;
+use crate::libs::cartridge::{Cartridge, LICENSE};
+use crate::libs::cpu::CPU;
+use crate::libs::exe::Exe;
+use crate::libs::ram::Ram;
+use crate::libs::tests::cpu_with_program;
+
+/// Licensed cartridge whose pre-boot entry runs `code`
+fn licensed(code: &[u32]) -> Vec<u8> {
+    let mut rom = vec![0; 0x100];
+
+    rom[0x80..0x84].copy_from_slice(&0x1f00_0100u32.to_le_bytes());
+    rom[0x84..0x84 + LICENSE.len()].copy_from_slice(LICENSE);
+    rom.extend(code.iter().flat_map(|w| w.to_le_bytes()));
+
+    rom
+}
+
+#[test]
+pub fn pre_boot_entry() {
+    let cartridge = Cartridge::from_bytes(licensed(&[]));
+    assert_eq!(cartridge.pre_boot_entry(), Some(0x1f00_0100));
+
+    let mut rom = licensed(&[]);
+    rom[0x84] = b'l';
+    assert_eq!(Cartridge::from_bytes(rom).pre_boot_entry(), None);
+
+    assert_eq!(Cartridge::from_bytes(vec![0; 0x90]).pre_boot_entry(), None);
+}
+
+#[test]
+pub fn reads() {
+    let mut bus = Bus::new(Bios::from_bytes(Vec::new()), Ram::new());
+
+    // Open bus without a cartridge
+    assert_eq!(bus.load32(0x1f00_0080), Ok(0xffff_ffff));
+
+    bus.insert_cartridge(Cartridge::from_bytes(licensed(&[0x1234_5678])));
+
+    assert_eq!(bus.load32(0x1f00_0080), Ok(0x1f00_0100));
+    assert_eq!(bus.load16(0x9f00_0102), Ok(0x1234));
+    assert_eq!(bus.load8(0xbf00_0084), Ok(b'L'));
+    assert_eq!(bus.peek8(0x1f00_0100), Some(0x78));
+
+    // Past the image, up to the end of the region
+    assert_eq!(bus.load32(0x1f7f_fffc), Ok(0xffff_ffff));
+    assert!(bus.load32(0x1f80_0400).is_err());
+}
+
+#[test]
+pub fn writes() {
+    let mut bus = Bus::new(Bios::from_bytes(Vec::new()), Ram::new());
+
+    assert!(bus.store32(0x1f00_0100, 0).is_err());
+
+    bus.insert_cartridge(Cartridge::from_bytes(licensed(&[0x1234_5678])));
+
+    // Ignored by the ROM
+    assert_eq!(bus.store32(0x1f00_0100, 0), Ok(()));
+    assert_eq!(bus.store16(0x9f00_0100, 0), Ok(()));
+    assert_eq!(bus.store8(0xbf00_0100, 0), Ok(()));
+    assert_eq!(bus.load32(0x1f00_0100), Ok(0x1234_5678));
+}
+
+#[test]
+pub fn boot_into_pre_boot_entry() {
+    // The BIOS check: lui t0, 0x1f00; lw t1, 0x80(t0); nop; jalr t1; nop
+    let program: [u32; 5] = [0x3c08_1f00, 0x8d09_0080, 0, 0x0120_f809, 0];
+    let mut cpu = cpu_with_program(&program);
+
+    // li t2, 0x2a
+    cpu.bus_mut()
+        .insert_cartridge(Cartridge::from_bytes(licensed(&[0x240a_002a])));
+
+    for _ in 0..6 {
+        cpu.run_next_opcode().unwrap();
+    }
+
+    assert_eq!(cpu.pc(), 0x1f00_0104);
+    assert_eq!(cpu.reg(10), 0x2a);
+    assert_eq!(cpu.reg(31), 0x1fc0_0014);
+}
+
+#[test]
+pub fn hle_runs_pre_boot_entry() {
+    let mut cpu = CPU::new(Bus::new(Bios::hle(), Ram::new()));
+
+    // li t2, 0x2a; jr ra; nop
+    cpu.bus_mut()
+        .insert_cartridge(Cartridge::from_bytes(licensed(&[
+            0x240a_002a,
+            0x03e0_0008,
+            0,
+        ])));
+
+    // j 0x80010000; nop
+    cpu.side_load(Exe {
+        pc: 0x8001_0000,
+        gp: 0,
+        text_addr: 0x8001_0000,
+        text: [0x0800_4000u32, 0]
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect(),
+        bss_addr: 0,
+        bss_size: 0,
+        sp: 0,
+    });
+
+    for _ in 0..6 {
+        cpu.run_next_opcode().unwrap();
+    }
+
+    // The executable boots once the cartridge returns
+    assert_eq!(cpu.reg(10), 0x2a);
+    assert_eq!(cpu.pc() & !4, 0x8001_0000);
+}
@@ -0,0 +1,204 @@
+use crate::libs::cdrom::CdRom;
+use crate::libs::disc::{Disc, SECTOR_SIZE};
+use crate::libs::irq::Irq;
+
+/// Disc with 16 sectors, the first data byte of each sector is its index
+pub fn disc() -> Disc {
+    let mut data = vec![0; 16 * SECTOR_SIZE];
+
+    for (i, sector) in data.chunks_mut(SECTOR_SIZE).enumerate() {
+        sector[24] = i as u8;
+    }
+
+    let license = b"          Licensed  by          Sony Computer Entertainment Amer  ica ";
+    data[4 * SECTOR_SIZE + 24..4 * SECTOR_SIZE + 24 + license.len()].copy_from_slice(license);
+
+    Disc::from_bin(data).unwrap()
+}
+
+fn command(cdrom: &mut CdRom, irq: &mut Irq, command: u8, params: &[u8]) {
+    cdrom.store(0, 0, irq);
+    for &p in params {
+        cdrom.store(2, p, irq);
+    }
+    cdrom.store(1, command, irq);
+}
+
+/// Run until an interrupt is raised, returns its type and the response
+fn wait_response(cdrom: &mut CdRom, irq: &mut Irq) -> (u8, Vec<u8>) {
+    for _ in 0..10_000 {
+        cdrom.tick(100, irq);
+
+        cdrom.store(0, 1, irq);
+        let int = cdrom.load(3) & 0x1f;
+
+        if int != 0 {
+            let mut response = Vec::new();
+            while cdrom.load(0) & 0x20 != 0 {
+                response.push(cdrom.load(1));
+            }
+
+            // Acknowledge
+            cdrom.store(3, 0x1f, irq);
+
+            return (int, response);
+        }
+    }
+
+    panic!("No CD-ROM response");
+}
+
+fn setup() -> (CdRom, Irq) {
+    let mut cdrom = CdRom::new();
+    let mut irq = Irq::new();
+
+    cdrom.store(0, 1, &mut irq);
+    cdrom.store(2, 0x1f, &mut irq);
+
+    (cdrom, irq)
+}
+
+#[test]
+pub fn get_stat_delay() {
+    let (mut cdrom, mut irq) = setup();
+
+    command(&mut cdrom, &mut irq, 0x01, &[]);
+    assert_ne!(cdrom.load(0) & 0x80, 0);
+
+    // The response isn't immediate
+    cdrom.tick(1000, &mut irq);
+    assert_eq!(irq.status(), 0);
+
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (3, vec![0x10]));
+    assert_eq!(irq.status(), 1 << 2);
+    assert_eq!(cdrom.load(0) & 0x80, 0);
+}
+
+#[test]
+pub fn test_version() {
+    let (mut cdrom, mut irq) = setup();
+
+    command(&mut cdrom, &mut irq, 0x19, &[0x20]);
+    assert_eq!(
+        wait_response(&mut cdrom, &mut irq),
+        (3, vec![0x94, 0x09, 0x19, 0xc0])
+    );
+}
+
+#[test]
+pub fn get_id_no_disc() {
+    let (mut cdrom, mut irq) = setup();
+
+    command(&mut cdrom, &mut irq, 0x1a, &[]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq).0, 3);
+    assert_eq!(
+        wait_response(&mut cdrom, &mut irq),
+        (5, vec![0x08, 0x40, 0, 0, 0, 0, 0, 0])
+    );
+}
+
+#[test]
+pub fn get_id() {
+    let (mut cdrom, mut irq) = setup();
+    cdrom.insert_disc(disc());
+
+    command(&mut cdrom, &mut irq, 0x1a, &[]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (3, vec![0x02]));
+    assert_eq!(
+        wait_response(&mut cdrom, &mut irq),
+        (2, vec![0x02, 0x00, 0x20, 0x00, b'S', b'C', b'E', b'A'])
+    );
+}
+
+#[test]
+pub fn init() {
+    let (mut cdrom, mut irq) = setup();
+    cdrom.insert_disc(disc());
+
+    command(&mut cdrom, &mut irq, 0x0a, &[]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (3, vec![0x02]));
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (2, vec![0x02]));
+}
+
+#[test]
+pub fn get_tn_td() {
+    let (mut cdrom, mut irq) = setup();
+    cdrom.insert_disc(disc());
+
+    command(&mut cdrom, &mut irq, 0x13, &[]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (3, vec![0x02, 1, 1]));
+
+    command(&mut cdrom, &mut irq, 0x14, &[1]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (3, vec![0x02, 0, 2]));
+}
+
+#[test]
+pub fn invalid_command() {
+    let (mut cdrom, mut irq) = setup();
+
+    command(&mut cdrom, &mut irq, 0x1f, &[]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (5, vec![0x11, 0x40]));
+}
+
+#[test]
+pub fn wrong_parameter_count() {
+    let (mut cdrom, mut irq) = setup();
+    cdrom.insert_disc(disc());
+
+    for (op, params) in [
+        (0x02, &[0x00, 0x02][..]),
+        (0x0e, &[]),
+        (0x14, &[]),
+        (0x19, &[]),
+    ] {
+        command(&mut cdrom, &mut irq, op, params);
+        let (int, response) = wait_response(&mut cdrom, &mut irq);
+        assert_eq!((int, response[1]), (5, 0x20));
+    }
+
+    // Getstat takes none
+    command(&mut cdrom, &mut irq, 0x01, &[0x00]);
+    let (int, response) = wait_response(&mut cdrom, &mut irq);
+    assert_eq!((int, response[1]), (5, 0x20));
+}
+
+#[test]
+pub fn no_disc() {
+    let (mut cdrom, mut irq) = setup();
+
+    for op in [0x06, 0x13, 0x15] {
+        command(&mut cdrom, &mut irq, op, &[]);
+        assert_eq!(wait_response(&mut cdrom, &mut irq), (5, vec![0x11, 0x80]));
+    }
+}
+
+#[test]
+pub fn read_sectors() {
+    let (mut cdrom, mut irq) = setup();
+    cdrom.insert_disc(disc());
+
+    // Seek to 00:02:05, the sixth sector of the image
+    command(&mut cdrom, &mut irq, 0x02, &[0x00, 0x02, 0x05]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq).0, 3);
+
+    command(&mut cdrom, &mut irq, 0x06, &[]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (3, vec![0x02]));
+
+    for expected in 5..7 {
+        assert_eq!(wait_response(&mut cdrom, &mut irq), (1, vec![0x22]));
+
+        cdrom.store(0, 0, &mut irq);
+        cdrom.store(3, 0x80, &mut irq);
+        assert_ne!(cdrom.load(0) & 0x40, 0);
+
+        assert_eq!(cdrom.dma_read(), expected);
+        for _ in 1..0x200 {
+            cdrom.dma_read();
+        }
+        assert_eq!(cdrom.load(0) & 0x40, 0);
+    }
+
+    command(&mut cdrom, &mut irq, 0x09, &[]);
+    assert_eq!(wait_response(&mut cdrom, &mut irq).0, 3);
+    assert_eq!(wait_response(&mut cdrom, &mut irq), (2, vec![0x02]));
+}
@@ -0,0 +1,310 @@
+use crate::libs::cpu::CPU;
+use crate::libs::irq::Interrupt;
+use crate::libs::tests::cpu_with_program;
+
+/// CPU running `program` from the BIOS, `handler` is the exception handler
+fn cpu(program: &[u32], handler: &[u32]) -> CPU {
+    let mut cpu = cpu_with_program(program);
+
+    for (i, &word) in handler.iter().enumerate() {
+        cpu.bus_mut().ram_mut().store32(0x80 + 4 * i, word);
+    }
+
+    cpu
+}
+
+fn run(cpu: &mut CPU, steps: usize) {
+    for _ in 0..steps {
+        cpu.run_next_opcode().unwrap();
+    }
+}
+
+fn exception_code(cpu: &CPU) -> u32 {
+    (cpu.cause() >> 2) & 0x1f
+}
+
+// mfc0 k0, badvaddr; nop; nop
+const READ_BADVADDR: [u32; 3] = [0x401a_4000, 0, 0];
+
+#[test]
+pub fn general_exception_vector() {
+    // syscall
+    let mut cpu = cpu_with_program(&[0x0000_000c]);
+
+    // Handler at 0x80000080: sw zero, 0x100(zero)
+    cpu.bus_mut().store32(0x80, 0xac00_0100).unwrap();
+    cpu.bus_mut().store32(0x100, 0x1234_5678).unwrap();
+
+    cpu.run_next_opcode().unwrap();
+    cpu.run_next_opcode().unwrap();
+
+    assert_eq!(cpu.bus_mut().load32(0x100).unwrap(), 0);
+}
+
+#[test]
+pub fn misaligned_load_sets_badvaddr() {
+    // lui t0, 0x8001; lw t1, 1(t0)
+    let mut cpu = cpu(&[0x3c08_8001, 0x8d09_0001], &READ_BADVADDR);
+
+    run(&mut cpu, 2);
+    assert_eq!(exception_code(&cpu), 4);
+    assert_eq!(cpu.badvaddr(), 0x8001_0001);
+    assert_eq!(cpu.epc(), 0x1fc0_0004);
+
+    run(&mut cpu, 2);
+    assert_eq!(cpu.reg(26), 0x8001_0001);
+}
+
+#[test]
+pub fn misaligned_store_sets_badvaddr() {
+    // lui t0, 0x8001; sh t1, 3(t0)
+    let mut cpu = cpu(&[0x3c08_8001, 0xa509_0003], &READ_BADVADDR);
+
+    run(&mut cpu, 2);
+    assert_eq!(exception_code(&cpu), 5);
+    assert_eq!(cpu.badvaddr(), 0x8001_0003);
+}
+
+#[test]
+pub fn misaligned_fetch_sets_badvaddr() {
+    // lui t0, 0x8001; ori t0, t0, 2; jr t0; nop
+    let mut cpu = cpu(&[0x3c08_8001, 0x3508_0002, 0x0100_0008, 0], &READ_BADVADDR);
+
+    run(&mut cpu, 5);
+    assert_eq!(exception_code(&cpu), 4);
+    assert_eq!(cpu.badvaddr(), 0x8001_0002);
+    assert_eq!(cpu.epc(), 0x8001_0002);
+    assert_eq!(cpu.pc(), 0x8000_0080);
+}
+
+#[test]
+pub fn cop0_prid_and_reserved_registers() {
+    // mfc0 k0, prid; nop; nop; mfc0 k0, r0
+    let mut cpu = cpu(&[0x401a_7800, 0, 0, 0x401a_0000], &[]);
+
+    run(&mut cpu, 3);
+    assert_eq!(cpu.reg(26), 2);
+
+    run(&mut cpu, 1);
+    assert_eq!(exception_code(&cpu), 0xa);
+}
+
+#[test]
+pub fn execute_breakpoint() {
+    // lui t0, 0x1fc0; ori t0, t0, 0x20; mtc0 t0, bpc; li t1, -1; mtc0 t1, bpcm
+    // lui t2, 0xc100; mtc0 t2, dcic; nop; nop
+    let mut cpu = cpu(
+        &[
+            0x3c08_1fc0,
+            0x3508_0020,
+            0x4088_1800,
+            0x2409_ffff,
+            0x4089_5800,
+            0x3c0a_c100,
+            0x408a_3800,
+            0,
+            0,
+        ],
+        &[],
+    );
+
+    run(&mut cpu, 8);
+    assert_eq!(cpu.pc(), 0x1fc0_0020);
+
+    run(&mut cpu, 1);
+    assert_eq!(exception_code(&cpu), 9);
+    assert_eq!(cpu.epc(), 0x1fc0_0020);
+    assert_eq!(cpu.pc(), 0x8000_0040);
+    assert_eq!(cpu.cop0(7).unwrap() & 0x1f, 0b00011);
+}
+
+#[test]
+pub fn data_read_breakpoint() {
+    // lui t0, 0x8001; mtc0 t0, bda; li t1, -1; mtc0 t1, bdam
+    // lui t2, 0xc600; mtc0 t2, dcic; sw zero, 0(t0); lw t3, 0(t0)
+    let mut cpu = cpu(
+        &[
+            0x3c08_8001,
+            0x4088_2800,
+            0x2409_ffff,
+            0x4089_4800,
+            0x3c0a_c600,
+            0x408a_3800,
+            0xad00_0000,
+            0x8d0b_0000,
+        ],
+        &[],
+    );
+    cpu.set_reg(11, 0x1234);
+
+    // Only reads are enabled, the store goes through
+    run(&mut cpu, 7);
+    assert_eq!(cpu.pc(), 0x1fc0_001c);
+
+    run(&mut cpu, 1);
+    assert_eq!(exception_code(&cpu), 9);
+    assert_eq!(cpu.epc(), 0x1fc0_001c);
+    assert_eq!(cpu.pc(), 0x8000_0040);
+    assert_eq!(cpu.cop0(7).unwrap() & 0x1f, 0b01101);
+
+    // The load is cancelled
+    run(&mut cpu, 1);
+    assert_eq!(cpu.reg(11), 0x1234);
+}
+
+#[test]
+pub fn data_write_breakpoint() {
+    // lui t0, 0x8001; mtc0 t0, bda; li t1, -1; mtc0 t1, bdam
+    // lui t2, 0xca00; mtc0 t2, dcic; sw t3, 0(t0)
+    let mut cpu = cpu(
+        &[
+            0x3c08_8001,
+            0x4088_2800,
+            0x2409_ffff,
+            0x4089_4800,
+            0x3c0a_ca00,
+            0x408a_3800,
+            0xad0b_0000,
+        ],
+        &[],
+    );
+    cpu.set_reg(11, 0x1234);
+    cpu.bus_mut().ram_mut().store32(0x1_0000, 0xcafe);
+
+    run(&mut cpu, 7);
+    assert_eq!(exception_code(&cpu), 9);
+    assert_eq!(cpu.epc(), 0x1fc0_0018);
+    assert_eq!(cpu.cop0(7).unwrap() & 0x1f, 0b10101);
+
+    // The store only happens when the instruction runs again
+    assert_eq!(cpu.bus().ram().load32(0x1_0000), 0xcafe);
+}
+
+#[test]
+pub fn interrupt_on_gte_command() {
+    // lui t0, 0x4000; ori t0, t0, 0x401; mtc0 t0, sr; rtps
+    let mut cpu = cpu(
+        &[0x3c08_4000, 0x3508_0401, 0x4088_6000, 0x4a18_0001],
+        // cfc2 k0, flag; nop
+        &[0x485a_f800, 0],
+    );
+
+    run(&mut cpu, 3);
+
+    cpu.bus_mut().store32(0x1f80_1074, 1).unwrap();
+    cpu.bus_mut().irq_mut().assert(Interrupt::VBlank);
+
+    run(&mut cpu, 1);
+    assert_eq!(exception_code(&cpu), 0);
+    assert_eq!(cpu.epc(), 0x1fc0_000c);
+
+    // The projection ran anyway and divided by a zero depth
+    run(&mut cpu, 2);
+    assert_ne!(cpu.reg(26) & (1 << 17), 0);
+}
+
+#[test]
+pub fn swl_skips_read_breakpoint() {
+    // lui t0, 0x8001; mtc0 t0, bda; li t1, -1; mtc0 t1, bdam
+    // lui t2, 0xc600; mtc0 t2, dcic; swl t3, 0(t0)
+    let mut cpu = cpu(
+        &[
+            0x3c08_8001,
+            0x4088_2800,
+            0x2409_ffff,
+            0x4089_4800,
+            0x3c0a_c600,
+            0x408a_3800,
+            0xa90b_0000,
+        ],
+        &[],
+    );
+    cpu.set_reg(11, 0x1234_5678);
+    cpu.bus_mut().ram_mut().store32(0x1_0000, 0);
+
+    run(&mut cpu, 7);
+    assert_eq!(cpu.pc(), 0x1fc0_001c);
+    assert_eq!(cpu.cop0(7).unwrap() & 0x1f, 0);
+    assert_eq!(cpu.bus().ram().load32(0x1_0000), 0x12);
+}
+
+// lui t0, 0x4000; mtc0 t0, sr; lui t1, 0x8001
+const ENABLE_GTE: [u32; 3] = [0x3c08_4000, 0x4088_6000, 0x3c09_8001];
+
+#[test]
+pub fn lwc2_loads_data_register() {
+    // lwc2 0, 0(t1); mfc2 t2, 0; nop
+    let mut cpu = cpu(
+        &[&ENABLE_GTE[..], &[0xc920_0000, 0x480a_0000, 0]].concat(),
+        &[],
+    );
+    cpu.bus_mut().ram_mut().store32(0x1_0000, 0x1234_5678);
+
+    run(&mut cpu, 6);
+    assert_eq!(cpu.reg(10), 0x1234_5678);
+}
+
+#[test]
+pub fn swc2_round_trip() {
+    // lwc2 0, 0(t1); swc2 0, 4(t1)
+    let mut cpu = cpu(
+        &[&ENABLE_GTE[..], &[0xc920_0000, 0xe920_0004]].concat(),
+        &[],
+    );
+    cpu.bus_mut().ram_mut().store32(0x1_0000, 0x1234_5678);
+
+    run(&mut cpu, 5);
+    assert_eq!(cpu.bus().ram().load32(0x1_0004), 0x1234_5678);
+}
+
+#[test]
+pub fn misaligned_gte_transfers() {
+    // lwc2 0, 1(t1)
+    let mut load = cpu(&[&ENABLE_GTE[..], &[0xc920_0001]].concat(), &[]);
+
+    run(&mut load, 4);
+    assert_eq!(exception_code(&load), 4);
+    assert_eq!(load.badvaddr(), 0x8001_0001);
+
+    // swc2 0, 1(t1)
+    let mut store = cpu(&[&ENABLE_GTE[..], &[0xe920_0001]].concat(), &[]);
+
+    run(&mut store, 4);
+    assert_eq!(exception_code(&store), 5);
+    assert_eq!(store.badvaddr(), 0x8001_0001);
+}
+
+#[test]
+pub fn gte_transfers_need_cop2() {
+    // lui t1, 0x8001; lwc2 0, 0(t1)
+    let mut load = cpu(&[0x3c09_8001, 0xc920_0000], &[]);
+
+    run(&mut load, 2);
+    assert_eq!(exception_code(&load), 11);
+
+    // lui t1, 0x8001; swc2 0, 0(t1)
+    let mut store = cpu(&[0x3c09_8001, 0xe920_0000], &[]);
+    store.bus_mut().ram_mut().store32(0x1_0000, 0x1234_5678);
+
+    run(&mut store, 2);
+    assert_eq!(exception_code(&store), 11);
+    assert_eq!(store.bus().ram().load32(0x1_0000), 0x1234_5678);
+}
+
+#[test]
+pub fn lwc2_bus_error() {
+    // lwc2 0, 0(t1); lui t2, 0x1e00; lwc2 0, 0(t2)
+    let mut cpu = cpu(
+        &[&ENABLE_GTE[..], &[0xc920_0000, 0x3c0a_1e00, 0xc940_0000]].concat(),
+        // mfc2 k0, 0; nop
+        &[0x481a_0000, 0],
+    );
+    cpu.bus_mut().ram_mut().store32(0x1_0000, 0x1234_5678);
+
+    run(&mut cpu, 6);
+    assert_eq!(exception_code(&cpu), 7);
+
+    // The GTE register keeps its value
+    run(&mut cpu, 2);
+    assert_eq!(cpu.reg(26), 0x1234_5678);
+}
@@ -0,0 +1,56 @@
+use crate::libs::disasm::disassemble;
+use crate::libs::map::opcode::Instruction;
+
+fn text(word: u32) -> String {
+    Instruction(word).to_string()
+}
+
+#[test]
+pub fn alu_and_memory() {
+    assert_eq!(text(0x27bd_ffe8), "addiu   sp, sp, -0x18");
+    assert_eq!(text(0x8fbf_0014), "lw      ra, 0x14(sp)");
+    assert_eq!(text(0xa104_2023), "sb      a0, 0x2023(t0)");
+    assert_eq!(text(0x3c08_1f80), "lui     t0, 0x1f80");
+    assert_eq!(text(0x0085_1821), "addu    v1, a0, a1");
+    assert_eq!(text(0x0004_2080), "sll     a0, a0, 2");
+    assert_eq!(text(0x0085_001a), "div     a0, a1");
+    assert_eq!(text(0x03e0_0008), "jr      ra");
+    assert_eq!(text(0x0100_f809), "jalr    t0");
+    assert_eq!(text(0x0000_000c), "syscall");
+}
+
+#[test]
+pub fn pseudo_ops() {
+    assert_eq!(text(0), "nop");
+    assert_eq!(text(0x0200_2025), "move    a0, s0");
+    assert_eq!(text(0x0000_2021), "move    a0, zero");
+    assert_eq!(text(0x3409_003d), "li      t1, 0x3d");
+    assert_eq!(text(0x2404_ffff), "li      a0, -0x1");
+    assert_eq!(text(0x1000_fffc), "b       pc-0xc");
+    assert_eq!(text(0x1080_0003), "beqz    a0, pc+0x10");
+}
+
+#[test]
+pub fn branch_targets() {
+    let at = |word, pc| disassemble(Instruction(word), Some(pc));
+
+    assert_eq!(at(0x1000_fffc, 0x8001_0010), "b       0x80010004");
+    assert_eq!(at(0x1485_0002, 0x8001_0000), "bne     a0, a1, 0x8001000c");
+    assert_eq!(at(0x0c00_c000, 0x8001_0000), "jal     0x80030000");
+    assert_eq!(at(0x0411_0001, 0xbfc0_0000), "bal     0xbfc00008");
+    assert_eq!(at(0x0480_0001, 0xbfc0_0000), "bltz    a0, 0xbfc00008");
+    assert_eq!(text(0x0c00_c000), "jal     0x00030000");
+}
+
+#[test]
+pub fn coprocessors() {
+    assert_eq!(text(0x4088_6000), "mtc0    t0, sr");
+    assert_eq!(text(0x400c_6800), "mfc0    t4, cause");
+    assert_eq!(text(0x4200_0010), "rfe");
+    assert_eq!(text(0x4a18_0001), "rtps    sf");
+    assert_eq!(text(0x4a48_6012), "mvmva   sf, mx=0, v=0, cv=3");
+    assert_eq!(text(0x4888_0000), "mtc2    t0, vxy0");
+    assert_eq!(text(0x48c8_f800), "ctc2    t0, flag");
+    assert_eq!(text(0xc893_0004), "lwc2    sz3, 0x4(a0)");
+    assert_eq!(text(0xfc00_0000), ".word   0xfc000000");
+}
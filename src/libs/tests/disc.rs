@@ -0,0 +1,129 @@
+use crate::libs::disc::{Disc, Msf, TrackType, SECTOR_SIZE};
+
+/// `sectors` sectors where every byte is `fill` plus the sector index
+fn bin(sectors: usize, fill: u8) -> Vec<u8> {
+    let mut data = vec![0; sectors * SECTOR_SIZE];
+
+    for (i, sector) in data.chunks_mut(SECTOR_SIZE).enumerate() {
+        sector.fill(fill + i as u8);
+    }
+
+    data
+}
+
+fn first_byte(disc: &Disc, msf: Msf) -> Option<u8> {
+    disc.sector(msf).map(|s| s[0])
+}
+
+#[test]
+pub fn msf() {
+    let msf = Msf::from_bcd(0x01, 0x23, 0x45);
+
+    assert_eq!(msf, Msf::new(1, 23, 45));
+    assert_eq!(msf.to_bcd(), [0x01, 0x23, 0x45]);
+    assert_eq!(Msf::from_lba(0), Msf::new(0, 2, 0));
+    assert_eq!(Msf::new(0, 3, 1).lba(), Some(76));
+    assert_eq!(Msf::new(0, 1, 0).lba(), None);
+}
+
+#[test]
+pub fn single_bin() {
+    let disc = Disc::from_bin(bin(4, 0)).unwrap();
+
+    assert_eq!(disc.tracks().len(), 1);
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 0)), Some(0));
+    assert_eq!(disc.sector_lba(3).map(|s| s[0]), Some(3));
+    assert_eq!(disc.sector_lba(4), None);
+    assert_eq!(disc.lead_out(), Msf::new(0, 2, 4));
+
+    assert!(Disc::from_bin(vec![0; 100]).is_err());
+}
+
+#[test]
+pub fn cue_single_file() {
+    let cue = r#"
+REM A comment
+FILE "Game (USA).bin" BINARY
+  TRACK 01 MODE2/2352
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 00 00:00:10
+    INDEX 01 00:00:12
+"#;
+
+    let disc = Disc::from_cue(cue, |name| {
+        assert_eq!(name, "Game (USA).bin");
+        Ok(bin(20, 0))
+    })
+    .unwrap();
+
+    let tracks = disc.tracks();
+    assert_eq!(tracks.len(), 2);
+
+    assert_eq!(tracks[0].kind, TrackType::Mode2);
+    assert_eq!(tracks[0].start, Msf::new(0, 2, 0));
+    assert_eq!(tracks[0].length, 10);
+
+    assert_eq!(tracks[1].kind, TrackType::Audio);
+    assert_eq!(tracks[1].start, Msf::new(0, 2, 12));
+    assert_eq!(tracks[1].length, 8);
+    assert_eq!(tracks[1].pregap, 2);
+
+    // The pregap is stored in the file
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 10)), Some(10));
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 12)), Some(12));
+    assert_eq!(disc.lead_out(), Msf::new(0, 2, 20));
+}
+
+#[test]
+pub fn cue_multiple_files() {
+    let cue = r#"
+FILE "track1.bin" BINARY
+  TRACK 01 MODE2/2352
+    INDEX 01 00:00:00
+FILE "track2.bin" BINARY
+  TRACK 02 AUDIO
+    PREGAP 00:00:05
+    INDEX 01 00:00:00
+FILE "track3.bin" BINARY
+  TRACK 03 AUDIO
+    INDEX 00 00:00:00
+    INDEX 01 00:00:02
+"#;
+
+    let disc = Disc::from_cue(cue, |name| match name {
+        "track1.bin" => Ok(bin(10, 0)),
+        "track2.bin" => Ok(bin(4, 100)),
+        "track3.bin" => Ok(bin(6, 200)),
+        _ => Err(format!("unknown file {}", name)),
+    })
+    .unwrap();
+
+    let tracks = disc.tracks();
+
+    // Track 2 starts after track 1 and a 5 sector gap missing from the image
+    assert_eq!(tracks[1].start, Msf::new(0, 2, 15));
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 10)), Some(0));
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 14)), Some(0));
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 15)), Some(100));
+
+    assert_eq!(tracks[2].start, Msf::new(0, 2, 21));
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 19)), Some(200));
+    assert_eq!(first_byte(&disc, Msf::new(0, 2, 21)), Some(202));
+    assert_eq!(disc.lead_out(), Msf::new(0, 2, 25));
+}
+
+#[test]
+pub fn cue_errors() {
+    let read = |_: &str| Ok(bin(4, 0));
+
+    assert!(Disc::from_cue("TRACK 01 MODE2/2352", read).is_err());
+    assert!(Disc::from_cue("FILE \"a.bin\" BINARY\nTRACK 01 MODE2/2336", read).is_err());
+    assert!(Disc::from_cue("FILE \"a.bin\" BINARY\nTRACK 01 AUDIO", read).is_err());
+    assert!(Disc::from_cue(
+        "FILE \"a.bin\" BINARY\nTRACK 01 AUDIO\nINDEX 01 00:61:00",
+        read
+    )
+    .is_err());
+    assert!(Disc::from_cue("FILE \"a.bin\" BINARY", read).is_err());
+}
@@ -0,0 +1,108 @@
+use crate::libs::bios::Bios;
+use crate::libs::bus::Bus;
+use crate::libs::error::{BusError, EmuError};
+use crate::libs::ram::Ram;
+use crate::libs::tests::cpu_with_program;
+
+#[test]
+pub fn strict_unmapped_load_reads_open_bus() {
+    // lui t0, 0x1e00; lw t1, 0(t0); nop; nop
+    let mut cpu = cpu_with_program(&[0x3c08_1e00, 0x8d09_0000, 0, 0]);
+    cpu.set_strict(true);
+
+    cpu.run_next_opcode().unwrap();
+    assert_eq!(
+        cpu.run_next_opcode(),
+        Err(EmuError::Bus(BusError::Unmapped {
+            addr: 0x1e00_0000,
+            width: 4,
+            val: None,
+        }))
+    );
+
+    // The error doesn't stop execution
+    cpu.run_next_opcode().unwrap();
+    assert_eq!(cpu.reg(9), 0xffff_ffff);
+}
+
+#[test]
+pub fn data_bus_error() {
+    // lui t0, 0x1e00; lw t1, 0(t0); nop
+    let mut cpu = cpu_with_program(&[0x3c08_1e00, 0x8d09_0000, 0]);
+    cpu.set_reg(9, 0x1234);
+
+    cpu.run_next_opcode().unwrap();
+    cpu.run_next_opcode().unwrap();
+
+    assert_eq!((cpu.cause() >> 2) & 0x1f, 7);
+    assert_eq!(cpu.epc(), 0x1fc0_0004);
+    assert_eq!(cpu.pc(), 0x8000_0080);
+
+    // The load is cancelled
+    cpu.run_next_opcode().unwrap();
+    assert_eq!(cpu.reg(9), 0x1234);
+}
+
+#[test]
+pub fn instruction_bus_error() {
+    // lui t0, 0x1e00; jr t0; nop
+    let mut cpu = cpu_with_program(&[0x3c08_1e00, 0x0100_0008, 0]);
+
+    for _ in 0..4 {
+        cpu.run_next_opcode().unwrap();
+    }
+
+    assert_eq!((cpu.cause() >> 2) & 0x1f, 6);
+    assert_eq!(cpu.epc(), 0x1e00_0000);
+    assert_eq!(cpu.pc(), 0x8000_0080);
+}
+
+#[test]
+pub fn unhandled_register() {
+    let mut bus = Bus::new(Bios::from_bytes(Vec::new()), Ram::new());
+
+    assert_eq!(
+        bus.store8(0x1f80_1070, 1),
+        Err(BusError::UnhandledRegister {
+            addr: 0x1f80_1070,
+            width: 1,
+            val: Some(1),
+        })
+    );
+}
+
+#[test]
+pub fn invalid_dma() {
+    let mut bus = Bus::new(Bios::from_bytes(Vec::new()), Ram::new());
+
+    // GPU channel control with the reserved sync mode
+    assert_eq!(
+        bus.store32(0x1f80_10a8, 3 << 9),
+        Err(BusError::InvalidDma {
+            port: 2,
+            reason: "reserved sync mode 3",
+        })
+    );
+
+    // CD-ROM channel control, linked list from RAM
+    let error = bus.store32(0x1f80_10b8, 0x0100_0401).unwrap_err();
+    assert!(matches!(error, BusError::InvalidDma { port: 3, .. }));
+}
+
+#[test]
+pub fn unimplemented_opcode() {
+    // cop0 with an unknown rs
+    let mut cpu = cpu_with_program(&[0x4100_0000]);
+
+    let error = cpu.run_next_opcode().unwrap_err();
+    assert_eq!(
+        error,
+        EmuError::UnimplementedOpcode {
+            pc: 0x1fc0_0000,
+            opcode: 0x4100_0000,
+        }
+    );
+    assert!(error
+        .to_string()
+        .starts_with("Unimplemented opcode 41000000"));
+}
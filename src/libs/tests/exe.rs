@@ -0,0 +1,96 @@
+use crate::libs::error::{BusError, EmuError};
+use crate::libs::exe::Exe;
+use crate::libs::tests::cpu_with_program;
+
+fn words(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+fn exe(text: &[u32]) -> Vec<u8> {
+    let mut data = vec![0; 0x800];
+
+    data[..8].copy_from_slice(b"PS-X EXE");
+
+    let header = [
+        (0x10, 0x8001_0000),
+        (0x14, 0x8001_8000),
+        (0x18, 0x8001_0000),
+        (0x1c, text.len() as u32 * 4),
+        (0x28, 0x8002_0000),
+        (0x2c, 0x10),
+        (0x30, 0x801f_fff0),
+        (0x34, 0),
+    ];
+
+    for (offset, val) in header {
+        data[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(val));
+    }
+
+    data.extend(words(text));
+    data
+}
+
+#[test]
+pub fn parse() {
+    let exe = Exe::parse(&exe(&[0x1234_5678])).unwrap();
+
+    assert_eq!(exe.pc, 0x8001_0000);
+    assert_eq!(exe.gp, 0x8001_8000);
+    assert_eq!(exe.text_addr, 0x8001_0000);
+    assert_eq!(exe.text, vec![0x78, 0x56, 0x34, 0x12]);
+    assert_eq!((exe.bss_addr, exe.bss_size), (0x8002_0000, 0x10));
+    assert_eq!(exe.sp, 0x801f_fff0);
+}
+
+#[test]
+pub fn invalid() {
+    assert!(Exe::parse(b"PS-X EXE").is_err());
+
+    let mut data = exe(&[0]);
+    data.truncate(0x802);
+    assert!(Exe::parse(&data).is_err());
+
+    data[0] = b'X';
+    assert!(Exe::parse(&data).is_err());
+}
+
+#[test]
+pub fn side_load() {
+    // lui $8, 0x8003; jr $8; nop
+    let mut cpu = cpu_with_program(&[0x3c08_8003, 0x0100_0008, 0]);
+
+    // sw $28, 0x100($0); sw $29, 0x104($0)
+    let exe = Exe::parse(&exe(&[0xac1c_0100, 0xac1d_0104])).unwrap();
+    cpu.side_load(exe);
+
+    for _ in 0..5 {
+        cpu.run_next_opcode().unwrap();
+    }
+
+    let bus = cpu.bus_mut();
+    assert_eq!(bus.load32(0x100), Ok(0x8001_8000));
+    assert_eq!(bus.load32(0x104), Ok(0x801f_fff0));
+    assert_eq!(bus.load32(0x8001_0000), Ok(0xac1c_0100));
+    assert_eq!(bus.load32(0x8002_000c), Ok(0));
+}
+
+#[test]
+pub fn side_load_outside_ram() {
+    // lui $8, 0x8003; jr $8; nop
+    let mut cpu = cpu_with_program(&[0x3c08_8003, 0x0100_0008, 0]);
+
+    let mut exe = Exe::parse(&exe(&[0x1234_5678])).unwrap();
+    exe.text_addr = 0x1f00_0000;
+    cpu.side_load(exe);
+
+    let error = (0..5).find_map(|_| cpu.run_next_opcode().err());
+
+    assert_eq!(
+        error,
+        Some(EmuError::Bus(BusError::Unmapped {
+            addr: 0x1f00_0000,
+            width: 1,
+            val: Some(0x78),
+        }))
+    );
+}
@@ -0,0 +1,95 @@
+use crate::libs::framebuffer::{DumpArea, Image};
+use crate::libs::gpu::Gpu;
+
+#[test]
+pub fn default_display_area() {
+    let gpu = Gpu::new();
+    let image = Image::capture(&gpu, DumpArea::Display);
+
+    // 256x240 NTSC
+    assert_eq!((image.width, image.height), (256, 240));
+    assert_eq!(image.rgb.len(), 256 * 240 * 3);
+}
+
+#[test]
+pub fn display_area_15bit() {
+    let mut gpu = Gpu::new();
+
+    // 320 pixels wide starting at (2, 1) in VRAM
+    gpu.gp1(0x0800_0001);
+    gpu.gp1(0x0500_0000 | (1 << 10) | 2);
+    gpu.gp1(0x0600_0000 | (0xc60 << 12) | 0x260);
+
+    gpu.gp0(0xa000_0000);
+    gpu.gp0((1 << 16) | 2);
+    gpu.gp0((1 << 16) | 2);
+    gpu.gp0(0x7c00_001f);
+
+    let image = Image::capture(&gpu, DumpArea::Display);
+
+    assert_eq!(image.width, 320);
+    assert_eq!(&image.rgb[..6], &[0xff, 0, 0, 0, 0, 0xff]);
+}
+
+#[test]
+pub fn display_area_24bit() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp1(0x0800_0010);
+
+    // Two 24-bit pixels packed in three halfwords
+    gpu.gp0(0xa000_0000);
+    gpu.gp0(0);
+    gpu.gp0((1 << 16) | 4);
+    gpu.gp0(0x3322_1111);
+    gpu.gp0(0x0000_5544);
+
+    let image = Image::capture(&gpu, DumpArea::Display);
+
+    assert_eq!(&image.rgb[..6], &[0x11, 0x11, 0x22, 0x33, 0x44, 0x55]);
+}
+
+#[test]
+pub fn vram_dump() {
+    let gpu = Gpu::new();
+    let image = Image::capture(&gpu, DumpArea::Vram);
+
+    assert_eq!((image.width, image.height), (1024, 512));
+}
+
+#[test]
+pub fn ppm() {
+    let image = Image {
+        width: 2,
+        height: 1,
+        rgb: vec![1, 2, 3, 4, 5, 6],
+    };
+
+    let mut out = Vec::new();
+    image.write_ppm(&mut out).unwrap();
+
+    assert_eq!(out, b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+}
+
+#[test]
+pub fn png() {
+    let image = Image {
+        width: 1,
+        height: 1,
+        rgb: vec![0xff, 0, 0],
+    };
+
+    let mut out = Vec::new();
+    image.write_png(&mut out).unwrap();
+
+    let expected: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, // Signature
+        0, 0, 0, 13, b'I', b'H', b'D', b'R', 0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0, 0x90, 0x77,
+        0x53, 0xde, // IHDR
+        0, 0, 0, 15, b'I', b'D', b'A', b'T', 0x78, 0x01, 0x01, 0x04, 0x00, 0xfb, 0xff, 0x00, 0xff,
+        0x00, 0x00, 0x03, 0x01, 0x01, 0x00, 0x8d, 0x1d, 0xe5, 0x82, // IDAT
+        0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82, // IEND
+    ];
+
+    assert_eq!(out, expected);
+}
@@ -0,0 +1,143 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use crate::libs::gdb::{GdbStub, Status};
+use crate::libs::tests::cpu_with_program;
+use crate::libs::watch::{Access, Watchpoint};
+
+// lui t0, 0x8001; ori t0, t0, 0x10; li t1, 0x2a; sw t1, 0(t0); lw t2, 0(t0)
+// j .; nop
+const PROGRAM: [u32; 7] = [
+    0x3c08_8001,
+    0x3508_0010,
+    0x2409_002a,
+    0xad09_0000,
+    0x8d0a_0000,
+    0x0bf0_0005,
+    0,
+];
+
+/// Minimal RSP client
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn byte(&mut self) -> u8 {
+        let mut b = [0u8];
+        self.stream.read_exact(&mut b).unwrap();
+        b[0]
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        let sum = data.bytes().fold(0u8, |s, b| s.wrapping_add(b));
+        write!(self.stream, "${}#{:02x}", data, sum).unwrap();
+        assert_eq!(self.byte(), b'+');
+
+        if data == "k" {
+            return String::new();
+        }
+
+        assert_eq!(self.byte(), b'$');
+
+        let mut reply = Vec::new();
+        loop {
+            match self.byte() {
+                b'#' => break,
+                b => reply.push(b),
+            }
+        }
+
+        self.byte();
+        self.byte();
+        self.stream.write_all(b"+").unwrap();
+
+        String::from_utf8(reply).unwrap()
+    }
+}
+
+#[test]
+pub fn remote_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        let mut gdb = Client { stream };
+
+        assert_eq!(gdb.request("?"), "S05");
+        assert!(gdb
+            .request("qSupported:swbreak+")
+            .contains("qXfer:features:read+"));
+        assert!(gdb
+            .request("qXfer:features:read:target.xml:0,1000")
+            .contains("mips:3000"));
+
+        // Run to the store
+        assert_eq!(gdb.request("Z0,1fc0000c,4"), "OK");
+        assert_eq!(gdb.request("c"), "S05");
+        assert_eq!(gdb.request("p25"), "0c00c01f");
+        assert_eq!(gdb.request("p8"), "10000180");
+        assert_eq!(gdb.request("g").len(), 73 * 8);
+
+        assert_eq!(gdb.request("s"), "S05");
+        assert_eq!(gdb.request("p25"), "1000c01f");
+        assert_eq!(gdb.request("m80010010,4"), "2a000000");
+
+        // The load is caught by the watchpoint, through another segment
+        assert_eq!(gdb.request("M00010010,4:78563412"), "OK");
+        assert_eq!(gdb.request("Z3,a0010010,4"), "OK");
+        assert_eq!(gdb.request("c"), "T05rwatch:80010010;");
+        assert_eq!(gdb.request("p25"), "1400c01f");
+
+        // I/O registers aren't read behind the emulator's back
+        assert_eq!(gdb.request("m1f801070,4"), "E01");
+
+        assert_eq!(gdb.request("P2=efbeadde"), "OK");
+        assert_eq!(gdb.request("p2"), "efbeadde");
+        gdb.request("k");
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut stub = GdbStub::new(stream);
+
+    let mut cpu = cpu_with_program(&PROGRAM);
+
+    for _ in 0..1000 {
+        match stub.poll(&mut cpu) {
+            Status::Running => cpu.run_next_opcode().unwrap(),
+            Status::Killed => break,
+            Status::Detached => panic!("The client went away"),
+        }
+    }
+
+    // Unblock the client if the session didn't end
+    drop(stub);
+    client.join().unwrap();
+
+    assert_eq!(cpu.reg(2), 0xdead_beef);
+    assert_eq!(cpu.bus().peek8(0x8001_0010), Some(0x78));
+}
+
+#[test]
+pub fn swl_only_writes() {
+    // lui t0, 0x8001; swl t1, 0(t0)
+    let mut cpu = cpu_with_program(&[0x3c08_8001, 0xa909_0000]);
+
+    for access in [Access::Read, Access::Write] {
+        cpu.add_watchpoint(Watchpoint {
+            addr: 0x8001_0000,
+            len: 4,
+            access,
+        });
+    }
+
+    cpu.run_next_opcode().unwrap();
+    cpu.run_next_opcode().unwrap();
+
+    // The word merged into isn't a read
+    assert!(cpu.take_watch_hit().unwrap().write);
+}
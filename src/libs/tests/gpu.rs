@@ -0,0 +1,197 @@
+use crate::libs::gpu::{Gpu, VRAM_WIDTH};
+use crate::libs::irq::Irq;
+use crate::libs::timers::Timers;
+
+#[test]
+pub fn reset_status() {
+    let gpu = Gpu::new();
+
+    // Display disabled, interlace field set, ready for commands and DMA
+    assert_eq!(gpu.status(), 0x1480_2000);
+}
+
+#[test]
+pub fn draw_mode() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0xe100_0000 | 0x2ff);
+    assert_eq!(gpu.status() & 0x7ff, 0x2ff);
+}
+
+#[test]
+pub fn dma_direction() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp1(0x0400_0002);
+    assert_eq!((gpu.status() >> 29) & 3, 2);
+    assert_ne!(gpu.status() & (1 << 25), 0);
+}
+
+#[test]
+pub fn get_info() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0xe300_0000 | (20 << 10) | 10);
+    gpu.gp1(0x1000_0003);
+    assert_eq!(gpu.read(), (20 << 10) | 10);
+
+    gpu.gp1(0x1000_0007);
+    assert_eq!(gpu.read(), 2);
+}
+
+#[test]
+pub fn fill_rect() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0x0200_00ff);
+    gpu.gp0((2 << 16) | 16);
+    gpu.gp0((1 << 16) | 1);
+
+    // Width is rounded up to 16 pixels
+    assert_eq!(gpu.vram()[2 * VRAM_WIDTH + 16], 0x1f);
+    assert_eq!(gpu.vram()[2 * VRAM_WIDTH + 31], 0x1f);
+    assert_eq!(gpu.vram()[2 * VRAM_WIDTH + 32], 0);
+    assert_eq!(gpu.vram()[3 * VRAM_WIDTH + 16], 0);
+}
+
+#[test]
+pub fn image_load_and_store() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0xa000_0000);
+    gpu.gp0((5 << 16) | 4);
+    gpu.gp0((2 << 16) | 3);
+    assert_eq!(gpu.status() & (1 << 26), 0);
+
+    // 6 pixels in 3 words
+    gpu.gp0(0x0002_0001);
+    gpu.gp0(0x0004_0003);
+    gpu.gp0(0x0006_0005);
+    assert_ne!(gpu.status() & (1 << 26), 0);
+
+    assert_eq!(gpu.vram()[5 * VRAM_WIDTH + 4], 1);
+    assert_eq!(gpu.vram()[5 * VRAM_WIDTH + 6], 3);
+    assert_eq!(gpu.vram()[6 * VRAM_WIDTH + 4], 4);
+
+    gpu.gp0(0xc000_0000);
+    gpu.gp0((5 << 16) | 4);
+    gpu.gp0((2 << 16) | 3);
+    assert_ne!(gpu.status() & (1 << 27), 0);
+
+    assert_eq!(gpu.read(), 0x0002_0001);
+    assert_eq!(gpu.read(), 0x0004_0003);
+    assert_eq!(gpu.read(), 0x0006_0005);
+    assert_eq!(gpu.status() & (1 << 27), 0);
+}
+
+#[test]
+pub fn odd_image_load_padding() {
+    let mut gpu = Gpu::new();
+
+    // 3x1 pixels in 2 words, the last halfword is padding
+    gpu.gp0(0xa000_0000);
+    gpu.gp0((5 << 16) | 4);
+    gpu.gp0((1 << 16) | 3);
+    gpu.gp0(0x0002_0001);
+    gpu.gp0(0x7fff_0003);
+    assert_ne!(gpu.status() & (1 << 26), 0);
+
+    assert_eq!(gpu.vram()[5 * VRAM_WIDTH + 6], 3);
+    // The pixel below the rectangle isn't touched
+    assert_eq!(gpu.vram()[6 * VRAM_WIDTH + 4], 0);
+}
+
+#[test]
+pub fn mask_bit() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0xe600_0003);
+    gpu.gp0(0xa000_0000);
+    gpu.gp0(0);
+    gpu.gp0((1 << 16) | 2);
+    gpu.gp0(0x0002_0001);
+    assert_eq!(gpu.vram()[0], 0x8001);
+
+    // Masked pixels are preserved
+    gpu.gp0(0xa000_0000);
+    gpu.gp0(0);
+    gpu.gp0((1 << 16) | 2);
+    gpu.gp0(0x0004_0003);
+    assert_eq!(gpu.vram()[0], 0x8001);
+}
+
+#[test]
+pub fn polygon_consumes_words() {
+    let mut gpu = Gpu::new();
+
+    // Textured gouraud quad, 12 words
+    gpu.gp0(0x3c00_0000);
+    for _ in 0..11 {
+        gpu.gp0(0);
+    }
+    gpu.gp0(0x0200_00ff);
+    gpu.gp0(0);
+    gpu.gp0((1 << 16) | 1);
+    assert_eq!(gpu.vram()[0], 0x1f);
+}
+
+#[test]
+pub fn polyline_terminator() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0x4800_0000);
+    gpu.gp0(0);
+    gpu.gp0(0);
+    gpu.gp0(0x0010_0010);
+    gpu.gp0(0x5555_5555);
+    gpu.gp0(0x0200_00ff);
+    gpu.gp0(0);
+    gpu.gp0((1 << 16) | 1);
+    assert_eq!(gpu.vram()[0], 0x1f);
+}
+
+#[test]
+pub fn vblank_interrupt() {
+    let mut gpu = Gpu::new();
+    let mut irq = Irq::new();
+    let mut timers = Timers::new();
+
+    // One NTSC frame is 263 lines of 3413 GPU clocks
+    let frame = 263 * 3413 * 7 / 11 + 1;
+
+    for _ in 0..frame / 100 + 1 {
+        gpu.tick(100, &mut irq, &mut timers);
+    }
+
+    assert_eq!(irq.status() & 1, 1);
+    assert_eq!(gpu.frame(), 1);
+}
+
+#[test]
+pub fn pal_switch_mid_line() {
+    let mut gpu = Gpu::new();
+    let mut irq = Irq::new();
+    let mut timers = Timers::new();
+
+    // Past the end of a PAL line, then switch to PAL
+    gpu.tick(3410 * 7 / 11, &mut irq, &mut timers);
+    gpu.gp1(0x0800_0008);
+    gpu.tick(3406 * 7 / 11, &mut irq, &mut timers);
+
+    timers.store(0x14, 1 << 8);
+    gpu.tick(3406 * 10 * 7 / 11 + 1, &mut irq, &mut timers);
+    assert_eq!(timers.load(0x10), 10);
+}
+
+#[test]
+pub fn hblank_clocks_timer1() {
+    let mut gpu = Gpu::new();
+    let mut irq = Irq::new();
+    let mut timers = Timers::new();
+
+    timers.store(0x14, 1 << 8);
+
+    // Ten lines
+    gpu.tick(3413 * 10 * 7 / 11 + 1, &mut irq, &mut timers);
+    assert_eq!(timers.load(0x10), 10);
+}
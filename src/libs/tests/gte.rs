@@ -0,0 +1,542 @@
+use crate::libs::gte::Gte;
+
+const SF: u32 = 1 << 19;
+const LM: u32 = 1 << 10;
+
+fn pack(lo: i16, hi: i16) -> u32 {
+    (lo as u16 as u32) | ((hi as u16 as u32) << 16)
+}
+
+/// Load `diag` on the diagonal of the matrix starting at control register `base`
+fn set_diagonal(gte: &mut Gte, base: usize, diag: [i16; 3]) {
+    gte.set_control(base, pack(diag[0], 0));
+    gte.set_control(base + 1, 0);
+    gte.set_control(base + 2, pack(diag[1], 0));
+    gte.set_control(base + 3, 0);
+    gte.set_control(base + 4, diag[2] as u32);
+}
+
+fn set_vector(gte: &mut Gte, index: usize, v: [i16; 3]) {
+    gte.set_data(index * 2, pack(v[0], v[1]));
+    gte.set_data(index * 2 + 1, v[2] as u32);
+}
+
+fn set_ir(gte: &mut Gte, ir: [i16; 4]) {
+    for (i, &v) in ir.iter().enumerate() {
+        gte.set_data(8 + i, v as u32);
+    }
+}
+
+fn mac(gte: &Gte) -> [i32; 3] {
+    [
+        gte.data(25) as i32,
+        gte.data(26) as i32,
+        gte.data(27) as i32,
+    ]
+}
+
+/// Common setup for the perspective transformation tests: identity rotation,
+/// screen center at (160, 120) and H = 0x80
+fn rtp_setup() -> Gte {
+    let mut gte = Gte::new();
+
+    set_diagonal(&mut gte, 0, [0x1000; 3]);
+    gte.set_control(5, 0);
+    gte.set_control(6, 0);
+    gte.set_control(7, 0xd0);
+    gte.set_control(24, 160 << 16);
+    gte.set_control(25, 120 << 16);
+    gte.set_control(26, 0x80);
+    gte.set_control(27, 0x100);
+    gte.set_control(28, 0);
+
+    gte
+}
+
+mod registers {
+    use super::*;
+
+    #[test]
+    pub fn vector_sign_extension() {
+        let mut gte = Gte::new();
+
+        gte.set_data(1, 0xffff_8000);
+        assert_eq!(gte.data(1), 0xffff_8000);
+        gte.set_data(1, 0x0001_7fff);
+        assert_eq!(gte.data(1), 0x7fff);
+    }
+
+    #[test]
+    pub fn sxyp_pushes_fifo() {
+        let mut gte = Gte::new();
+
+        gte.set_data(15, 0x0001_0001);
+        gte.set_data(15, 0x0002_0002);
+        gte.set_data(15, 0x0003_0003);
+        gte.set_data(15, 0x0004_0004);
+
+        assert_eq!(gte.data(12), 0x0002_0002);
+        assert_eq!(gte.data(13), 0x0003_0003);
+        assert_eq!(gte.data(14), 0x0004_0004);
+        assert_eq!(gte.data(15), 0x0004_0004);
+    }
+
+    #[test]
+    pub fn irgb_orgb() {
+        let mut gte = Gte::new();
+
+        gte.set_data(28, 0x7c1f);
+        assert_eq!(gte.data(9), 0x1f << 7);
+        assert_eq!(gte.data(10), 0);
+        assert_eq!(gte.data(11), 0x1f << 7);
+
+        set_ir(&mut gte, [0, -0x100, 0x180, 0x7fff]);
+        assert_eq!(gte.data(29), (0x1f << 10) | (0x3 << 5));
+    }
+
+    #[test]
+    pub fn lzcr() {
+        let mut gte = Gte::new();
+
+        gte.set_data(30, 0x0000_ffff);
+        assert_eq!(gte.data(31), 16);
+        gte.set_data(30, 0xff00_0000);
+        assert_eq!(gte.data(31), 8);
+        gte.set_data(30, 0);
+        assert_eq!(gte.data(31), 32);
+    }
+
+    #[test]
+    pub fn h_reads_sign_extended() {
+        let mut gte = Gte::new();
+
+        gte.set_control(26, 0x8000);
+        assert_eq!(gte.control(26), 0xffff_8000);
+    }
+
+    #[test]
+    pub fn flag_error_bit() {
+        let mut gte = Gte::new();
+
+        gte.set_control(31, 0xffff_ffff);
+        assert_eq!(gte.control(31), 0xffff_f000);
+
+        // IR0 saturation is not part of the error summary
+        gte.set_control(31, 1 << 12);
+        assert_eq!(gte.control(31), 1 << 12);
+    }
+}
+
+mod commands {
+    use super::*;
+
+    #[test]
+    pub fn rtps() {
+        let mut gte = rtp_setup();
+
+        set_vector(&mut gte, 0, [0x10, 0x20, 0x30]);
+        gte.command(SF | 0x01);
+
+        assert_eq!(mac(&gte), [0x10, 0x20, 0x100]);
+        assert_eq!(gte.data(19), 0x100);
+        assert_eq!(gte.data(14), pack(168, 136));
+        assert_eq!(gte.data(24), 0x80_0000);
+        assert_eq!(gte.data(8), 0x800);
+        assert_eq!(gte.control(31), 0);
+    }
+
+    #[test]
+    pub fn rtpt() {
+        let mut gte = rtp_setup();
+
+        set_vector(&mut gte, 0, [0x10, 0x20, 0x30]);
+        set_vector(&mut gte, 1, [-0x10, 0, 0x30]);
+        set_vector(&mut gte, 2, [0, -0x20, 0x130]);
+        gte.command(SF | 0x30);
+
+        assert_eq!(gte.data(12), pack(168, 136));
+        assert_eq!(gte.data(13), pack(152, 120));
+        assert_eq!(gte.data(14), pack(160, 112));
+        assert_eq!(gte.data(17), 0x100);
+        assert_eq!(gte.data(18), 0x100);
+        assert_eq!(gte.data(19), 0x200);
+        assert_eq!(gte.control(31), 0);
+    }
+
+    #[test]
+    pub fn rtps_divide_overflow() {
+        let mut gte = rtp_setup();
+
+        gte.set_control(26, 0x200);
+        set_vector(&mut gte, 0, [0x10, 0x20, 0x30]);
+        gte.command(SF | 0x01);
+
+        let flag = gte.control(31);
+        assert_ne!(flag & (1 << 17), 0);
+        assert_ne!(flag & (1 << 31), 0);
+    }
+
+    #[test]
+    pub fn rtps_screen_saturation() {
+        let mut gte = rtp_setup();
+
+        set_vector(&mut gte, 0, [0x1000, -0x1000, 0x30]);
+        gte.command(SF | 0x01);
+
+        assert_eq!(gte.data(14), pack(0x3ff, -0x400));
+        assert_ne!(gte.control(31) & (1 << 14), 0);
+        assert_ne!(gte.control(31) & (1 << 13), 0);
+    }
+
+    #[test]
+    pub fn nclip() {
+        let mut gte = Gte::new();
+
+        gte.set_data(12, pack(0, 0));
+        gte.set_data(13, pack(10, 0));
+        gte.set_data(14, pack(0, 10));
+        gte.command(0x06);
+
+        assert_eq!(gte.data(24), 100);
+
+        gte.set_data(13, pack(0, 10));
+        gte.set_data(14, pack(10, 0));
+        gte.command(0x06);
+
+        assert_eq!(gte.data(24) as i32, -100);
+    }
+
+    #[test]
+    pub fn op() {
+        let mut gte = Gte::new();
+
+        set_diagonal(&mut gte, 0, [0x1000; 3]);
+        set_ir(&mut gte, [0, 0x10, 0x20, 0x30]);
+        gte.command(SF | 0x0c);
+
+        assert_eq!(mac(&gte), [0x10, -0x20, 0x10]);
+        assert_eq!(gte.data(10) as i32, -0x20);
+    }
+
+    #[test]
+    pub fn sqr() {
+        let mut gte = Gte::new();
+
+        set_ir(&mut gte, [0, 0x100, -0x200, 0x300]);
+        gte.command(SF | 0x28);
+
+        assert_eq!(mac(&gte), [0x10, 0x40, 0x90]);
+    }
+
+    #[test]
+    pub fn avsz3() {
+        let mut gte = Gte::new();
+
+        gte.set_data(17, 100);
+        gte.set_data(18, 200);
+        gte.set_data(19, 300);
+        gte.set_control(29, 0x555);
+        gte.command(0x2d);
+
+        assert_eq!(gte.data(24), 0x555 * 600);
+        assert_eq!(gte.data(7), 199);
+    }
+
+    #[test]
+    pub fn avsz4() {
+        let mut gte = Gte::new();
+
+        for (i, z) in [100, 200, 300, 400].iter().enumerate() {
+            gte.set_data(16 + i, *z);
+        }
+        gte.set_control(30, 0x400);
+        gte.command(0x2e);
+
+        assert_eq!(gte.data(24), 1024000);
+        assert_eq!(gte.data(7), 250);
+    }
+
+    #[test]
+    pub fn avsz3_otz_saturation() {
+        let mut gte = Gte::new();
+
+        gte.set_data(19, 0xffff);
+        gte.set_control(29, 0x7fff);
+        gte.command(0x2d);
+
+        assert_eq!(gte.data(7), 0xffff);
+        assert_ne!(gte.control(31) & (1 << 18), 0);
+    }
+
+    #[test]
+    pub fn mvmva() {
+        let mut gte = Gte::new();
+
+        set_diagonal(&mut gte, 0, [0x2000; 3]);
+        set_vector(&mut gte, 0, [0x10, 0x20, 0x30]);
+        for r in 5..8 {
+            gte.set_control(r, 1);
+        }
+        // mx = RT, v = V0, cv = TR
+        gte.command(SF | 0x12);
+
+        assert_eq!(mac(&gte), [0x21, 0x41, 0x61]);
+    }
+
+    #[test]
+    pub fn mvmva_ir_vector() {
+        let mut gte = Gte::new();
+
+        // mx = L, v = IR, cv = none
+        set_diagonal(&mut gte, 8, [0x1000, 0x800, 0x400]);
+        set_ir(&mut gte, [0, 0x100, 0x100, 0x100]);
+        gte.command(SF | (1 << 17) | (3 << 15) | (3 << 13) | 0x12);
+
+        assert_eq!(mac(&gte), [0x100, 0x80, 0x40]);
+    }
+
+    #[test]
+    pub fn mvmva_lm_saturation() {
+        let mut gte = Gte::new();
+
+        set_diagonal(&mut gte, 0, [0x1000; 3]);
+        set_vector(&mut gte, 0, [-0x10, 0x20, 0x30]);
+        gte.command(SF | LM | (3 << 13) | 0x12);
+
+        assert_eq!(mac(&gte), [-0x10, 0x20, 0x30]);
+        assert_eq!(gte.data(9), 0);
+        assert_eq!(gte.control(31), (1 << 31) | (1 << 24));
+    }
+
+    #[test]
+    pub fn mvmva_far_color_bug() {
+        let mut gte = Gte::new();
+
+        set_diagonal(&mut gte, 0, [0x1000; 3]);
+        set_vector(&mut gte, 0, [0x10, 0x20, 0x30]);
+        for r in 21..24 {
+            gte.set_control(r, 0x100);
+        }
+        gte.command(SF | (2 << 13) | 0x12);
+
+        // Neither the far color nor the first matrix column make it into
+        // the result
+        assert_eq!(mac(&gte), [0, 0x20, 0x30]);
+    }
+
+    #[test]
+    pub fn mac_overflow() {
+        let mut gte = Gte::new();
+
+        set_diagonal(&mut gte, 0, [0x7fff; 3]);
+        gte.set_control(5, 0x7fff_ffff);
+        set_vector(&mut gte, 0, [0x7fff, 0, 0]);
+        gte.command(0x12);
+
+        assert_ne!(gte.control(31) & (1 << 30), 0);
+    }
+
+    /// Identity light and light color matrices, no background color
+    fn light_setup() -> Gte {
+        let mut gte = Gte::new();
+
+        set_diagonal(&mut gte, 8, [0x1000; 3]);
+        set_diagonal(&mut gte, 16, [0x1000; 3]);
+        gte.set_data(6, 0x2a80_8080);
+
+        gte
+    }
+
+    #[test]
+    pub fn ncs() {
+        let mut gte = light_setup();
+
+        set_vector(&mut gte, 0, [0x800, 0x400, 0x100]);
+        gte.command(SF | LM | 0x1e);
+
+        assert_eq!(gte.data(22), 0x2a10_4080);
+    }
+
+    #[test]
+    pub fn nct() {
+        let mut gte = light_setup();
+
+        set_vector(&mut gte, 0, [0x800, 0x400, 0x100]);
+        set_vector(&mut gte, 1, [0x100, 0x800, 0x400]);
+        set_vector(&mut gte, 2, [0x400, 0x100, 0x800]);
+        gte.command(SF | LM | 0x20);
+
+        assert_eq!(gte.data(20), 0x2a10_4080);
+        assert_eq!(gte.data(21), 0x2a40_8010);
+        assert_eq!(gte.data(22), 0x2a80_1040);
+    }
+
+    #[test]
+    pub fn nccs() {
+        let mut gte = light_setup();
+
+        set_vector(&mut gte, 0, [0x800, 0x400, 0x100]);
+        gte.command(SF | LM | 0x1b);
+
+        assert_eq!(mac(&gte), [0x400, 0x200, 0x80]);
+        assert_eq!(gte.data(22), 0x2a08_2040);
+    }
+
+    #[test]
+    pub fn ncct() {
+        let mut gte = light_setup();
+
+        for v in 0..3 {
+            set_vector(&mut gte, v, [0x800, 0x400, 0x100]);
+        }
+        gte.command(SF | LM | 0x3f);
+
+        for reg in 20..23 {
+            assert_eq!(gte.data(reg), 0x2a08_2040);
+        }
+    }
+
+    #[test]
+    pub fn ncds() {
+        let mut gte = light_setup();
+
+        set_vector(&mut gte, 0, [0x800, 0x400, 0x100]);
+        gte.set_control(21, 0x800);
+        gte.set_control(22, 0x400);
+        gte.set_control(23, 0x100);
+        // Fully interpolated to the far color
+        gte.set_data(8, 0x1000);
+        gte.command(SF | LM | 0x13);
+
+        assert_eq!(mac(&gte), [0x800, 0x400, 0x100]);
+        assert_eq!(gte.data(22), 0x2a10_4080);
+    }
+
+    #[test]
+    pub fn ncdt() {
+        let mut gte = light_setup();
+
+        for v in 0..3 {
+            set_vector(&mut gte, v, [0x800, 0x400, 0x100]);
+        }
+        gte.set_data(8, 0);
+        gte.command(SF | LM | 0x16);
+
+        for reg in 20..23 {
+            assert_eq!(gte.data(reg), 0x2a08_2040);
+        }
+    }
+
+    #[test]
+    pub fn cc() {
+        let mut gte = light_setup();
+
+        gte.set_data(6, 0x0020_4080);
+        set_ir(&mut gte, [0, 0x800, 0x800, 0x800]);
+        gte.command(SF | LM | 0x1c);
+
+        assert_eq!(mac(&gte), [0x400, 0x200, 0x100]);
+        assert_eq!(gte.data(22), 0x0010_2040);
+    }
+
+    #[test]
+    pub fn cdp() {
+        let mut gte = light_setup();
+
+        gte.set_data(6, 0x0020_4080);
+        set_ir(&mut gte, [0, 0x800, 0x800, 0x800]);
+        gte.command(SF | LM | 0x14);
+
+        assert_eq!(mac(&gte), [0x400, 0x200, 0x100]);
+        assert_eq!(gte.data(22), 0x0010_2040);
+    }
+
+    #[test]
+    pub fn dpcs() {
+        let mut gte = Gte::new();
+
+        gte.set_data(6, 0x3320_4080);
+        gte.set_control(21, 0x1000);
+        gte.set_data(8, 0x800);
+        gte.command(SF | 0x10);
+
+        assert_eq!(mac(&gte), [0xc00, 0x200, 0x100]);
+        assert_eq!(gte.data(22), 0x3310_20c0);
+    }
+
+    #[test]
+    pub fn dpct() {
+        let mut gte = Gte::new();
+
+        gte.set_data(6, 0x3300_0000);
+        gte.set_data(20, 0x0000_0010);
+        gte.set_data(21, 0x0000_0020);
+        gte.set_data(22, 0x0000_0030);
+        gte.set_data(8, 0);
+        gte.command(SF | 0x2a);
+
+        assert_eq!(gte.data(20), 0x3300_0010);
+        assert_eq!(gte.data(21), 0x3300_0020);
+        assert_eq!(gte.data(22), 0x3300_0030);
+    }
+
+    #[test]
+    pub fn dcpl() {
+        let mut gte = Gte::new();
+
+        gte.set_data(6, 0x0020_4080);
+        set_ir(&mut gte, [0, 0x800, 0x800, 0x800]);
+        gte.command(SF | LM | 0x29);
+
+        assert_eq!(gte.data(22), 0x0010_2040);
+    }
+
+    #[test]
+    pub fn intpl() {
+        let mut gte = Gte::new();
+
+        set_ir(&mut gte, [0, 0x100, 0x200, 0x300]);
+        gte.command(SF | 0x11);
+
+        assert_eq!(mac(&gte), [0x100, 0x200, 0x300]);
+        assert_eq!(gte.data(22), 0x0030_2010);
+    }
+
+    #[test]
+    pub fn gpf() {
+        let mut gte = Gte::new();
+
+        set_ir(&mut gte, [0x800, 0x200, 0x400, 0x600]);
+        gte.command(SF | 0x3d);
+
+        assert_eq!(mac(&gte), [0x100, 0x200, 0x300]);
+        assert_eq!(gte.data(22), 0x0030_2010);
+    }
+
+    #[test]
+    pub fn gpl() {
+        let mut gte = Gte::new();
+
+        gte.set_data(25, 0x10);
+        gte.set_data(26, 0x20);
+        gte.set_data(27, 0x30);
+        set_ir(&mut gte, [0x800, 0x200, 0x400, 0x600]);
+        gte.command(SF | 0x3e);
+
+        assert_eq!(mac(&gte), [0x110, 0x220, 0x330]);
+    }
+
+    #[test]
+    pub fn color_saturation() {
+        let mut gte = Gte::new();
+
+        set_ir(&mut gte, [0x1000, 0x7fff, -0x100, 0]);
+        gte.command(SF | 0x3d);
+
+        assert_eq!(gte.data(22), 0x0000_00ff);
+        let flag = gte.control(31);
+        assert_ne!(flag & (1 << 21), 0);
+        assert_ne!(flag & (1 << 20), 0);
+        assert_eq!(flag & (1 << 19), 0);
+    }
+}
@@ -0,0 +1,408 @@
+use crate::libs::bios::Bios;
+use crate::libs::bus::Bus;
+use crate::libs::cpu::CPU;
+use crate::libs::disc::{Disc, SECTOR_SIZE};
+use crate::libs::error::EmuError;
+use crate::libs::exe::Exe;
+use crate::libs::hle::RETURN_ADDR;
+use crate::libs::iso9660;
+use crate::libs::ram::Ram;
+use crate::libs::tty::TtySink;
+
+const TEXT: u32 = 0x8001_0000;
+const DATA: u32 = 0x8002_0000;
+
+/// Hand assembled program calling into the kernel
+struct Program {
+    code: Vec<u32>,
+}
+
+impl Program {
+    fn new() -> Program {
+        Program { code: Vec::new() }
+    }
+
+    fn word(&mut self, word: u32) -> &mut Self {
+        self.code.push(word);
+        self
+    }
+
+    fn li(&mut self, reg: u32, val: u32) -> &mut Self {
+        // lui reg, hi; ori reg, reg, lo
+        self.code.push(0x3c00_0000 | reg << 16 | val >> 16);
+        self.code
+            .push(0x3400_0000 | reg << 21 | reg << 16 | (val & 0xffff));
+        self
+    }
+
+    /// Call kernel function `table:function` with `args` in a0-a3
+    fn call(&mut self, table: u32, function: u32, args: &[u32]) -> &mut Self {
+        for (i, &arg) in args.iter().enumerate() {
+            self.li(4 + i as u32, arg);
+        }
+
+        self.li(9, function).li(8, table);
+        // jalr t0; nop
+        self.code.extend([0x0100_f809, 0]);
+        self
+    }
+
+    /// sw v0, addr
+    fn store_v0(&mut self, addr: u32) -> &mut Self {
+        self.li(8, addr);
+        self.code.push(0xad02_0000);
+        self
+    }
+
+    fn syscall(&mut self, function: u32) -> &mut Self {
+        self.li(4, function);
+        self.code.push(0x0000_000c);
+        self
+    }
+
+    /// Idle loop at the end of the program
+    fn exe(&mut self) -> Exe {
+        let end = TEXT + 4 * self.code.len() as u32;
+        self.code
+            .extend([0x0800_0000 | ((end >> 2) & 0x03ff_ffff), 0]);
+
+        Exe {
+            pc: TEXT,
+            gp: 0,
+            text_addr: TEXT,
+            text: self.code.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            bss_addr: 0,
+            bss_size: 0,
+            sp: 0,
+        }
+    }
+}
+
+fn cpu(disc: Option<Disc>) -> CPU {
+    let mut bus = Bus::new(Bios::hle(), Ram::new());
+
+    bus.tty_mut().set_sink(TtySink::Buffer(Vec::new()));
+
+    if let Some(disc) = disc {
+        bus.insert_disc(disc);
+    }
+
+    CPU::new(bus)
+}
+
+fn run(cpu: &mut CPU, steps: usize) {
+    for _ in 0..steps {
+        cpu.run_next_opcode().unwrap();
+    }
+}
+
+/// First error within `steps` instructions
+fn run_until_error(cpu: &mut CPU, steps: usize) -> Option<EmuError> {
+    (0..steps).find_map(|_| cpu.run_next_opcode().err())
+}
+
+fn store_string(cpu: &mut CPU, addr: u32, s: &[u8]) {
+    for (i, &c) in s.iter().chain(&[0]).enumerate() {
+        cpu.bus_mut()
+            .ram_mut()
+            .store8((addr & 0x1f_ffff) as usize + i, c);
+    }
+}
+
+fn load32(cpu: &mut CPU, addr: u32) -> u32 {
+    cpu.bus_mut().load32(addr as usize).unwrap()
+}
+
+/// Data disc with the ISO9660 file system holding `files`
+fn iso(files: &[(&str, &[u8])]) -> Disc {
+    const ROOT: u32 = 18;
+
+    let mut sectors = vec![vec![0u8; iso9660::DATA_SIZE]; ROOT as usize + 1];
+
+    let record = |name: &[u8], lba: u32, size: u32, dir: bool| {
+        let mut r = vec![0u8; 33 + name.len() + (name.len() + 1) % 2];
+        r[0] = r.len() as u8;
+        r[2..6].copy_from_slice(&lba.to_le_bytes());
+        r[10..14].copy_from_slice(&size.to_le_bytes());
+        r[25] = if dir { 2 } else { 0 };
+        r[32] = name.len() as u8;
+        r[33..33 + name.len()].copy_from_slice(name);
+        r
+    };
+
+    let pvd = &mut sectors[16];
+    pvd[0] = 1;
+    pvd[1..6].copy_from_slice(b"CD001");
+    let root = record(&[0], ROOT, iso9660::DATA_SIZE as u32, true);
+    pvd[156..156 + root.len()].copy_from_slice(&root);
+
+    let mut dir = [
+        record(&[0], ROOT, 2048, true),
+        record(&[1], ROOT, 2048, true),
+    ]
+    .concat();
+
+    for (name, data) in files {
+        let lba = sectors.len() as u32;
+        dir.extend(record(name.as_bytes(), lba, data.len() as u32, false));
+
+        for chunk in data.chunks(iso9660::DATA_SIZE) {
+            let mut sector = chunk.to_vec();
+            sector.resize(iso9660::DATA_SIZE, 0);
+            sectors.push(sector);
+        }
+    }
+
+    sectors[ROOT as usize][..dir.len()].copy_from_slice(&dir);
+
+    let bin = sectors
+        .iter()
+        .flat_map(|data| {
+            let mut raw = vec![0u8; SECTOR_SIZE];
+            raw[15] = 2;
+            raw[24..24 + iso9660::DATA_SIZE].copy_from_slice(data);
+            raw
+        })
+        .collect();
+
+    Disc::from_bin(bin).unwrap()
+}
+
+/// PS-X EXE file for `exe`
+fn exe_file(exe: &Exe) -> Vec<u8> {
+    let mut data = vec![0u8; 0x800];
+
+    data[..8].copy_from_slice(b"PS-X EXE");
+    data[0x10..0x14].copy_from_slice(&exe.pc.to_le_bytes());
+    data[0x18..0x1c].copy_from_slice(&exe.text_addr.to_le_bytes());
+    data[0x1c..0x20].copy_from_slice(&(exe.text.len() as u32).to_le_bytes());
+    data.extend_from_slice(&exe.text);
+    data
+}
+
+#[test]
+pub fn printf() {
+    let mut cpu = cpu(None);
+    store_string(&mut cpu, DATA, b"%d %5s|%-4x|%04X%%\n");
+    store_string(&mut cpu, DATA + 0x100, b"psx");
+
+    // The fifth argument is on the stack
+    let exe = Program::new()
+        .li(29, 0x801f_ff00)
+        .li(8, 0x2a)
+        .li(10, 0x801f_ff10)
+        .word(0xad48_0000)
+        .call(0xa0, 0x3f, &[DATA, (-12i32) as u32, DATA + 0x100, 0xab])
+        .exe();
+
+    cpu.side_load(exe);
+    run(&mut cpu, 200);
+
+    assert_eq!(
+        String::from_utf8_lossy(cpu.bus().tty().buffer()),
+        "-12   psx|ab  |002A%\n"
+    );
+}
+
+#[test]
+pub fn heap_and_strings() {
+    let mut cpu = cpu(None);
+    store_string(&mut cpu, DATA, b"kernel");
+
+    let exe = Program::new()
+        .call(0xa0, 0x39, &[0x8010_0000, 0x1000])
+        .call(0xa0, 0x33, &[10])
+        .store_v0(DATA + 0x100)
+        .call(0xa0, 0x33, &[4])
+        .store_v0(DATA + 0x104)
+        .call(0xa0, 0x34, &[0x8010_0000])
+        .call(0xa0, 0x33, &[8])
+        .store_v0(DATA + 0x108)
+        .call(0xa0, 0x19, &[0x8010_0000, DATA])
+        .call(0xa0, 0x1b, &[0x8010_0000])
+        .store_v0(DATA + 0x10c)
+        .call(0xa0, 0x17, &[0x8010_0000, DATA])
+        .store_v0(DATA + 0x110)
+        .exe();
+
+    cpu.side_load(exe);
+    run(&mut cpu, 400);
+
+    assert_eq!(load32(&mut cpu, DATA + 0x100), 0x8010_0000);
+    assert_eq!(load32(&mut cpu, DATA + 0x104), 0x8010_000c);
+    // The first block is reused
+    assert_eq!(load32(&mut cpu, DATA + 0x108), 0x8010_0000);
+    assert_eq!(load32(&mut cpu, DATA + 0x10c), 6);
+    assert_eq!(load32(&mut cpu, DATA + 0x110), 0);
+}
+
+#[test]
+pub fn critical_section() {
+    let mut cpu = cpu(None);
+
+    let exe = Program::new()
+        .syscall(1)
+        .store_v0(DATA)
+        .syscall(1)
+        .store_v0(DATA + 4)
+        .syscall(2)
+        .exe();
+
+    cpu.side_load(exe);
+    run(&mut cpu, 100);
+
+    assert_eq!(load32(&mut cpu, DATA), 1);
+    assert_eq!(load32(&mut cpu, DATA + 4), 0);
+    assert_eq!(cpu.sr() & 0x401, 0x401);
+}
+
+#[test]
+pub fn vblank_event() {
+    let mut cpu = cpu(None);
+
+    let exe = Program::new()
+        .call(0xb0, 0x08, &[0xf200_0003, 0x0002, 0x2000, 0])
+        .store_v0(DATA)
+        .call(0xb0, 0x0c, &[0xf100_0000])
+        .call(0xb0, 0x04, &[3])
+        .call(0xb0, 0x0a, &[0xf100_0000])
+        .store_v0(DATA + 4)
+        .exe();
+
+    cpu.side_load(exe);
+
+    // A frame is about 565000 cycles
+    for _ in 0..400_000 {
+        cpu.run_next_opcode().unwrap();
+
+        if load32(&mut cpu, DATA + 4) == 1 {
+            break;
+        }
+    }
+
+    assert_eq!(load32(&mut cpu, DATA), 0xf100_0000);
+    assert_eq!(load32(&mut cpu, DATA + 4), 1);
+}
+
+#[test]
+pub fn iso9660_find() {
+    let disc = iso(&[("SYSTEM.CNF;1", b"BOOT = cdrom:\\MAIN.EXE;1\r\n")]);
+
+    let entry = iso9660::find(&disc, "\\system.cnf").unwrap();
+    assert_eq!(entry.size, 26);
+    assert_eq!(
+        iso9660::read(&disc, &entry).unwrap(),
+        b"BOOT = cdrom:\\MAIN.EXE;1\r\n"
+    );
+
+    assert!(iso9660::find(&disc, "MISSING.DAT").is_err());
+}
+
+#[test]
+pub fn boot_from_disc() {
+    let exe = Program::new()
+        .li(10, DATA)
+        .li(8, u32::from_le_bytes(*b"cdro"))
+        .word(0xad48_0000)
+        .li(8, u32::from_le_bytes(*b"m:DA"))
+        .word(0xad48_0004)
+        .li(8, u32::from_le_bytes(*b"TA.B"))
+        .word(0xad48_0008)
+        .li(8, u32::from_le_bytes(*b"IN\0\0"))
+        .word(0xad48_000c)
+        .call(0xb0, 0x32, &[DATA, 1])
+        .store_v0(DATA + 0x100)
+        .call(0xb0, 0x33, &[2, 2046, 0])
+        .store_v0(DATA + 0x104)
+        .call(0xb0, 0x34, &[2, DATA + 0x200, 8])
+        .store_v0(DATA + 0x108)
+        .call(0xb0, 0x36, &[2])
+        .exe();
+
+    let data: Vec<u8> = (0..3000u32).map(|i| i as u8).collect();
+    let disc = iso(&[
+        (
+            "SYSTEM.CNF;1",
+            b"BOOT = cdrom:\\MAIN.EXE;1\nSTACK = 801FFF00\n",
+        ),
+        ("MAIN.EXE;1", &exe_file(&exe)),
+        ("DATA.BIN;1", &data),
+    ]);
+
+    let mut cpu = cpu(Some(disc));
+    run(&mut cpu, 400);
+
+    // Opened as fd 2, 8 bytes read across a sector boundary
+    assert_eq!(load32(&mut cpu, DATA + 0x100), 2);
+    assert_eq!(load32(&mut cpu, DATA + 0x104), 2046);
+    assert_eq!(load32(&mut cpu, DATA + 0x108), 8);
+    assert_eq!(
+        load32(&mut cpu, DATA + 0x200),
+        u32::from_le_bytes([254, 255, 0, 1])
+    );
+    assert_eq!(cpu.reg(29), 0x801f_ff00);
+}
+
+#[test]
+pub fn unhandled_exception() {
+    let mut cpu = cpu(None);
+
+    // break
+    let exe = Program::new().word(0x0000_000d).exe();
+
+    cpu.side_load(exe);
+    assert_eq!(
+        run_until_error(&mut cpu, 100),
+        Some(EmuError::UnhandledException { epc: TEXT, code: 9 })
+    );
+
+    // The kernel hangs
+    assert_eq!(run_until_error(&mut cpu, 100), None);
+}
+
+#[test]
+pub fn debug_exception() {
+    let mut cpu = cpu(None);
+
+    // Code breakpoint on the second nop
+    let exe = Program::new()
+        .li(8, TEXT + 0x24)
+        .word(0x4088_1800)
+        .li(9, 0xffff_ffff)
+        .word(0x4089_5800)
+        .li(10, 0xc100_0000)
+        .word(0x408a_3800)
+        .word(0)
+        .word(0)
+        .exe();
+
+    cpu.side_load(exe);
+    assert_eq!(
+        run_until_error(&mut cpu, 100),
+        Some(EmuError::UnhandledException {
+            epc: TEXT + 0x24,
+            code: 9
+        })
+    );
+}
+
+#[test]
+pub fn stray_callback_return() {
+    let mut cpu = cpu(None);
+
+    // jr t0; nop
+    let exe = Program::new()
+        .li(8, RETURN_ADDR)
+        .word(0x0100_0008)
+        .word(0)
+        .exe();
+
+    cpu.side_load(exe);
+    assert!(matches!(
+        run_until_error(&mut cpu, 100),
+        Some(EmuError::Kernel {
+            pc: RETURN_ADDR,
+            ..
+        })
+    ));
+}
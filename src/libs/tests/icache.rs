@@ -0,0 +1,148 @@
+use crate::libs::icache::{ICache, ENABLE, TAG_TEST};
+use crate::libs::tests::cpu_with_program;
+
+#[test]
+pub fn fill_and_fetch() {
+    let mut cache = ICache::new();
+
+    assert_eq!(cache.fetch(0x8000_1004), None);
+
+    cache.fill(0x8000_1004, 0x1234);
+    assert_eq!(cache.fetch(0x8000_1004), Some(0x1234));
+    // KUSEG and KSEG0 share the cache lines
+    assert_eq!(cache.fetch(0x0000_1004), Some(0x1234));
+    // Same line, other word
+    assert_eq!(cache.fetch(0x8000_1000), None);
+    // Same line, other tag
+    assert_eq!(cache.fetch(0x8000_2004), None);
+
+    // Retagging drops the other words
+    cache.fill(0x8000_1008, 0x5678);
+    cache.fill(0x8000_2000, 0x9abc);
+    assert_eq!(cache.fetch(0x8000_1008), None);
+    assert_eq!(cache.fetch(0x8000_2000), Some(0x9abc));
+}
+
+#[test]
+pub fn fill_range() {
+    let words: Vec<u32> = ICache::fill_range(0x8000_1008).collect();
+
+    assert_eq!(words, [0x8000_1008, 0x8000_100c]);
+    assert!(ICache::cached(0x9fc0_0000));
+    assert!(!ICache::cached(0xbfc0_0000));
+}
+
+#[test]
+pub fn invalidate() {
+    let mut cache = ICache::new();
+    cache.fill(0x8000_1000, 1);
+    cache.fill(0x8000_1004, 2);
+
+    // Another tag leaves the line alone
+    cache.invalidate(0x8000_2000);
+    assert_eq!(cache.fetch(0x8000_1000), Some(1));
+
+    cache.invalidate(0xa000_1000);
+    assert_eq!(cache.fetch(0x8000_1000), None);
+    assert_eq!(cache.fetch(0x8000_1004), Some(2));
+}
+
+#[test]
+pub fn isolated_stores() {
+    let mut cache = ICache::new();
+    cache.fill(0x8000_1000, 1);
+    cache.fill(0x8000_1004, 2);
+
+    // Ignored with the cache disabled
+    cache.isolated_store(TAG_TEST, 0x8000_1000, 0);
+    assert_eq!(cache.fetch(0x8000_1004), Some(2));
+
+    cache.isolated_store(ENABLE, 0x8000_1004, 3);
+    assert_eq!(cache.fetch(0x8000_1004), Some(3));
+
+    // Tag test mode invalidates the whole line
+    cache.isolated_store(ENABLE | TAG_TEST, 0x8000_1000, 0);
+    assert_eq!(cache.fetch(0x8000_1000), None);
+    assert_eq!(cache.fetch(0x8000_1004), None);
+}
+
+/// Call a routine in RAM, patch it, call it again, then invalidate its
+/// line through an isolated store and call it a last time
+fn self_modifying(cache_control: u32) -> Vec<u32> {
+    // lui t1, 0x8000; ori t1, t1, 0x1000; jalr t1; nop
+    // sw t2, 0(t1); jalr t1; nop
+    // mtc0 t3, sr; sw zero, 0(t1); mtc0 zero, sr; jalr t1; nop
+    let program: [u32; 12] = [
+        0x3c09_8000,
+        0x3529_1000,
+        0x0120_f809,
+        0,
+        0xad2a_0000,
+        0x0120_f809,
+        0,
+        0x408b_6000,
+        0xad20_0000,
+        0x4080_6000,
+        0x0120_f809,
+        0,
+    ];
+    let mut cpu = cpu_with_program(&program);
+
+    // addiu t0, t0, 1; jr ra; nop
+    cpu.bus_mut().ram_mut().store32(0x1000, 0x2508_0001);
+    cpu.bus_mut().ram_mut().store32(0x1004, 0x03e0_0008);
+    cpu.bus_mut().ram_mut().store32(0x1008, 0);
+    cpu.bus_mut().store32(0xfffe_0130, cache_control).unwrap();
+
+    // addiu t0, t0, 0x10
+    cpu.set_reg(10, 0x2508_0010);
+    cpu.set_reg(11, 0x10000);
+    cpu.jump(0xbfc0_0000);
+
+    let mut results = Vec::new();
+
+    for steps in [7, 6, 8] {
+        for _ in 0..steps {
+            cpu.run_next_opcode().unwrap();
+        }
+
+        results.push(cpu.reg(8));
+    }
+
+    results
+}
+
+#[test]
+pub fn stale_until_invalidated() {
+    assert_eq!(self_modifying(ENABLE | TAG_TEST), [1, 2, 0x12]);
+}
+
+#[test]
+pub fn disabled_cache_fetches_memory() {
+    assert_eq!(self_modifying(0), [1, 0x11, 0x21]);
+}
+
+#[test]
+pub fn debugger_writes_invalidate() {
+    // lui t1, 0x8000; ori t1, t1, 0x1000; jalr t1; nop; jalr t1; nop
+    let mut cpu = cpu_with_program(&[0x3c09_8000, 0x3529_1000, 0x0120_f809, 0, 0x0120_f809, 0]);
+
+    // addiu t0, t0, 1; jr ra; nop
+    cpu.bus_mut().ram_mut().store32(0x1000, 0x2508_0001);
+    cpu.bus_mut().ram_mut().store32(0x1004, 0x03e0_0008);
+    cpu.bus_mut().ram_mut().store32(0x1008, 0);
+    cpu.bus_mut().store32(0xfffe_0130, ENABLE).unwrap();
+
+    for _ in 0..7 {
+        cpu.run_next_opcode().unwrap();
+    }
+    assert_eq!(cpu.reg(8), 1);
+
+    // addiu t0, t0, 0x10
+    assert!(cpu.poke8(0xa000_1000, 0x10));
+
+    for _ in 0..5 {
+        cpu.run_next_opcode().unwrap();
+    }
+    assert_eq!(cpu.reg(8), 0x11);
+}
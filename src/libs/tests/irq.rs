@@ -0,0 +1,73 @@
+use crate::libs::cpu::CPU;
+use crate::libs::irq::{Interrupt, Irq};
+use crate::libs::tests::cpu_with_program;
+
+const I_STAT: usize = 0x1f80_1070;
+const I_MASK: usize = 0x1f80_1074;
+
+/// CPU with a pending VBlank interrupt and `sr` set, running NOPs
+fn pending_vblank(sr: u32) -> CPU {
+    let mut cpu = cpu_with_program(&[0; 4]);
+
+    cpu.set_sr(sr);
+    cpu.bus_mut().store32(I_MASK, 1).unwrap();
+    cpu.bus_mut().irq_mut().assert(Interrupt::VBlank);
+    cpu
+}
+
+#[test]
+pub fn ack_on_write_zero() {
+    let mut irq = Irq::new();
+
+    irq.assert(Interrupt::VBlank);
+    irq.assert(Interrupt::Dma);
+    assert_eq!(irq.status(), 0b1001);
+
+    // Writing 1 leaves the bit alone, writing 0 clears it
+    irq.ack(!0b0001);
+    assert_eq!(irq.status(), 0b1000);
+}
+
+#[test]
+pub fn mask() {
+    let mut irq = Irq::new();
+
+    irq.assert(Interrupt::Timer2);
+    assert!(!irq.active());
+
+    irq.set_mask(1 << 6);
+    assert!(irq.active());
+
+    irq.ack(0);
+    assert!(!irq.active());
+}
+
+#[test]
+pub fn cause_bit_10() {
+    let mut cpu = pending_vblank(0);
+    assert_ne!(cpu.cause() & (1 << 10), 0);
+
+    cpu.bus_mut().store32(I_MASK, 0).unwrap();
+    assert_eq!(cpu.cause() & (1 << 10), 0);
+
+    cpu.bus_mut().store32(I_MASK, 1).unwrap();
+    cpu.bus_mut().store32(I_STAT, 0).unwrap();
+    assert_eq!(cpu.cause() & (1 << 10), 0);
+}
+
+#[test]
+pub fn interrupt_exception() {
+    // IEc and IM2 set
+    let mut cpu = pending_vblank(0x401);
+    cpu.run_next_opcode().unwrap();
+    assert_eq!((cpu.cause() >> 2) & 0x1f, 0);
+    assert_eq!(cpu.epc(), 0x1fc0_0000);
+    assert_eq!(cpu.pc(), 0x8000_0080);
+
+    // Masked by either bit
+    for sr in [0x400, 0x001] {
+        let mut cpu = pending_vblank(sr);
+        cpu.run_next_opcode().unwrap();
+        assert_eq!(cpu.pc(), 0x1fc0_0004);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::libs::kernel::{function_name, KernelTracer};
+use crate::libs::tests::Log;
+
+fn tracer() -> (KernelTracer, Log) {
+    let log = Log::new();
+
+    (KernelTracer::new(Box::new(log.clone())), log)
+}
+
+fn call(function: u32, args: [u32; 4], ra: u32) -> [u32; 32] {
+    let mut r = [0; 32];
+
+    r[4..8].copy_from_slice(&args);
+    r[9] = function;
+    r[31] = ra;
+    r
+}
+
+#[test]
+pub fn names() {
+    assert_eq!(function_name(0xa0, 0x3f), Some("printf"));
+    assert_eq!(function_name(0xb0, 0x08), Some("OpenEvent"));
+    assert_eq!(function_name(0xb0, 0x32), Some("open"));
+    assert_eq!(function_name(0xc0, 0x1d), Some("get_card_find_mode"));
+    assert_eq!(function_name(0xc0, 0x1e), None);
+    assert_eq!(function_name(0xd0, 0), None);
+}
+
+#[test]
+pub fn call_and_return() {
+    let (mut tracer, log) = tracer();
+
+    tracer.step(0xa0, &call(0x3f, [0x8001_0000, 1, 2, 3], 0x8003_0010));
+    tracer.step(0xbfc0_1000, &[0; 32]);
+
+    let mut r = [0; 32];
+    r[2] = 5;
+    tracer.step(0x8003_0010, &r);
+
+    assert_eq!(
+        log.lines(),
+        [
+            "A0:3F printf(80010000, 00000001, 00000002, 00000003)",
+            "A0:3F printf = 00000005",
+        ]
+    );
+}
+
+#[test]
+pub fn nested_calls() {
+    let (mut tracer, log) = tracer();
+
+    tracer.step(0xb0, &call(0x32, [0x8001_0000, 1, 0, 0], 0x8003_0010));
+    tracer.step(0xc0, &call(0x13, [0; 4], 0xbfc0_2000));
+    // ReturnFromException never comes back
+    tracer.step(0xb0, &call(0x17, [0; 4], 0xbfc0_3000));
+    tracer.step(0xbfc0_3000, &[0; 32]);
+
+    let mut r = [0; 32];
+    r[2] = 3;
+    // Returning to the outer caller drops the inner call
+    tracer.step(0x8003_0010, &r);
+
+    assert_eq!(
+        log.lines(),
+        [
+            "B0:32 open(80010000, 00000001, 00000000, 00000000)",
+            "C0:13 FlushStdInOutPut(00000000, 00000000, 00000000, 00000000)",
+            "B0:17 ReturnFromException(00000000, 00000000, 00000000, 00000000)",
+            "B0:32 open = 00000003",
+        ]
+    );
+}
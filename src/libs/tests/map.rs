@@ -1,3 +1,4 @@
+#[allow(clippy::unusual_byte_groupings)]
 mod instruction {
 
     use crate::libs::map::opcode::Instruction;
@@ -1 +1,67 @@
+mod bus;
+mod cartridge;
+mod cdrom;
+mod cpu;
+mod disasm;
+mod disc;
+mod error;
+mod exe;
+mod framebuffer;
+mod gdb;
+mod gpu;
+mod gte;
+mod hle;
+mod icache;
+mod irq;
+mod kernel;
 mod map;
+mod monitor;
+mod rasterizer;
+mod scratchpad;
+mod timers;
+mod trace;
+mod tty;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::libs::bios::Bios;
+use crate::libs::bus::Bus;
+use crate::libs::cpu::CPU;
+use crate::libs::ram::Ram;
+
+/// Writer whose output stays readable once boxed into a tracer
+#[derive(Clone)]
+pub struct Log(Rc<RefCell<Vec<u8>>>);
+
+impl Log {
+    pub fn new() -> Log {
+        Log(Rc::new(RefCell::new(Vec::new())))
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.borrow().clone())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+impl Write for Log {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// CPU running `program` from the start of the BIOS
+pub fn cpu_with_program(program: &[u32]) -> CPU {
+    let bios = Bios::from_bytes(program.iter().flat_map(|w| w.to_le_bytes()).collect());
+    CPU::new(Bus::new(bios, Ram::new()))
+}
@@ -0,0 +1,95 @@
+use std::io::Cursor;
+
+use crate::libs::cpu::CPU;
+use crate::libs::monitor::Monitor;
+use crate::libs::tests::{cpu_with_program, Log};
+
+// lui t0, 0x8001; ori t0, t0, 0x10; li t1, 0x2a; sw t1, 0(t0); lw t2, 0(t0)
+// j .; nop
+const PROGRAM: [u32; 7] = [
+    0x3c08_8001,
+    0x3508_0010,
+    0x2409_002a,
+    0xad09_0000,
+    0x8d0a_0000,
+    0x0bf0_0005,
+    0,
+];
+
+/// Run the program under the monitor, `script` holds the commands
+fn session(script: &str) -> (CPU, Vec<String>) {
+    let mut cpu = cpu_with_program(&PROGRAM);
+    let log = Log::new();
+    let input = Box::new(Cursor::new(script.as_bytes().to_vec()));
+    let mut monitor = Monitor::new(input, Box::new(log.clone()));
+
+    for _ in 0..1000 {
+        if !monitor.poll(&mut cpu) {
+            break;
+        }
+
+        cpu.run_next_opcode().unwrap();
+    }
+
+    // Output follows the prompt on the same line
+    let lines = log
+        .lines()
+        .iter()
+        .map(|l| l.trim_start_matches("> ").to_string())
+        .collect();
+
+    (cpu, lines)
+}
+
+#[test]
+pub fn breakpoints_and_stepping() {
+    let (cpu, lines) = session("b 1fc0000c\nc\ns\n\nq\n");
+
+    assert!(lines.contains(&"Breakpoint at 1fc0000c".to_string()));
+    assert!(lines.contains(&"=> 1fc0000c: ad090000 sw      t1, 0x0(t0)".to_string()));
+    // The empty line repeats the step
+    assert!(lines.contains(&"=> 1fc00014: 0bf00005 j       0x1fc00014".to_string()));
+    assert_eq!(cpu.pc(), 0x1fc0_0014);
+}
+
+#[test]
+pub fn watchpoints_and_memory() {
+    let (cpu, lines) = session("w 80010010 4 r\nc\nx 80010010 4\nr\nq\n");
+
+    assert!(lines.contains(&"Watchpoint: read of 4 byte(s) at 80010010".to_string()));
+    assert!(lines
+        .contains(&"80010010: 2a 00 00 00                                      *...".to_string()));
+    assert!(lines.iter().any(|l| l.contains("t0: 80010010")));
+    assert_eq!(cpu.pc(), 0x1fc0_0014);
+}
+
+#[test]
+pub fn run_until_and_disassemble() {
+    let (cpu, lines) = session("c 1fc00008\nu 1fc00004 2\nq\n");
+
+    assert!(lines.contains(&"Reached 1fc00008".to_string()));
+    assert!(lines.contains(&"   1fc00004: 35080010 ori     t0, t0, 0x10".to_string()));
+    assert!(lines.contains(&"=> 1fc00008: 2409002a li      t1, 0x2a".to_string()));
+    assert_eq!(cpu.pc(), 0x1fc0_0008);
+}
+
+#[test]
+pub fn halt_drops_pending_steps() {
+    let mut cpu = cpu_with_program(&PROGRAM);
+    let input = Box::new(Cursor::new(b"s 100\nc\nq\n".to_vec()));
+    let mut monitor = Monitor::new(input, Box::new(Log::new()));
+
+    // An error halts the monitor in the middle of the steps
+    for _ in 0..2 {
+        assert!(monitor.poll(&mut cpu));
+        cpu.run_next_opcode().unwrap();
+    }
+
+    monitor.halt();
+
+    // Continuing doesn't stop where the steps would have ended
+    for _ in 0..200 {
+        assert!(monitor.poll(&mut cpu));
+        cpu.run_next_opcode().unwrap();
+    }
+}
@@ -0,0 +1,375 @@
+use crate::libs::gpu::{Gpu, VRAM_WIDTH};
+
+/// GPU with the drawing area covering the whole VRAM
+fn gpu() -> Gpu {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0xe300_0000);
+    gpu.gp0(0xe400_0000 | (511 << 10) | 1023);
+
+    gpu
+}
+
+fn pixel(gpu: &Gpu, x: usize, y: usize) -> u16 {
+    gpu.vram()[y * VRAM_WIDTH + x]
+}
+
+/// Compare a VRAM region with a golden dump where '.' is an untouched pixel
+/// and '#' is `color`
+fn assert_region(gpu: &Gpu, x: usize, y: usize, color: u16, golden: &[&str]) {
+    for (dy, row) in golden.iter().enumerate() {
+        for (dx, c) in row.chars().enumerate() {
+            let expected = match c {
+                '#' => color,
+                _ => 0,
+            };
+
+            assert_eq!(
+                pixel(gpu, x + dx, y + dy),
+                expected,
+                "pixel ({}, {})",
+                x + dx,
+                y + dy
+            );
+        }
+    }
+}
+
+fn load_image(gpu: &mut Gpu, x: u32, y: u32, w: u32, h: u32, pixels: &[u16]) {
+    gpu.gp0(0xa000_0000);
+    gpu.gp0((y << 16) | x);
+    gpu.gp0((h << 16) | w);
+
+    for pair in pixels.chunks(2) {
+        let hi = pair.get(1).copied().unwrap_or(0) as u32;
+        gpu.gp0(pair[0] as u32 | (hi << 16));
+    }
+}
+
+#[test]
+pub fn flat_triangle() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0x2000_00ff);
+    gpu.gp0(0);
+    gpu.gp0(4);
+    gpu.gp0(4 << 16);
+
+    // Right and bottom edges are not drawn
+    assert_region(
+        &gpu,
+        0,
+        0,
+        0x1f,
+        &["####.", "###..", "##...", "#....", "....."],
+    );
+}
+
+#[test]
+pub fn flat_quad() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0x2800_ff00);
+    gpu.gp0((2 << 16) | 2);
+    gpu.gp0((2 << 16) | 6);
+    gpu.gp0((6 << 16) | 2);
+    gpu.gp0((6 << 16) | 6);
+
+    // Both triangles cover the square exactly once
+    assert_region(
+        &gpu,
+        1,
+        1,
+        0x3e0,
+        &["......", ".####.", ".####.", ".####.", ".####.", "......"],
+    );
+}
+
+#[test]
+pub fn gouraud_triangle() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0x3000_00ff);
+    gpu.gp0(0);
+    gpu.gp0(0x0000_ff00);
+    gpu.gp0(4);
+    gpu.gp0(0x00ff_0000);
+    gpu.gp0(4 << 16);
+
+    assert_eq!(pixel(&gpu, 0, 0), 0x1f);
+    // Halfway between red and green
+    assert_eq!(pixel(&gpu, 2, 0), 15 | (15 << 5));
+    assert_eq!(pixel(&gpu, 0, 2), 15 | (15 << 10));
+}
+
+#[test]
+pub fn dithering() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0xe100_0200);
+
+    gpu.gp0(0x3000_0080);
+    gpu.gp0(0);
+    gpu.gp0(0x0000_0080);
+    gpu.gp0(8);
+    gpu.gp0(0x0000_0080);
+    gpu.gp0(8 << 16);
+
+    // 0x80 is 16 once truncated, the matrix adds -4 at (0, 0) and 2 at (0, 1)
+    assert_eq!(pixel(&gpu, 0, 0), 15);
+    assert_eq!(pixel(&gpu, 1, 0), 16);
+    assert_eq!(pixel(&gpu, 0, 1), 16);
+    assert_eq!(pixel(&gpu, 1, 1), 15);
+
+    // Flat untextured polygons are never dithered
+    gpu.gp0(0x2000_0080);
+    gpu.gp0(0);
+    gpu.gp0(8);
+    gpu.gp0(8 << 16);
+    assert_eq!(pixel(&gpu, 0, 0), 16);
+}
+
+#[test]
+pub fn semi_transparency() {
+    let expected = [12, 24, 8, 18];
+
+    for (mode, expected) in expected.into_iter().enumerate() {
+        let mut gpu = gpu();
+
+        // Background of 16, foreground of 8
+        gpu.gp0(0x6800_0080);
+        gpu.gp0(0);
+        gpu.gp0(0xe100_0000 | ((mode as u32) << 5));
+        gpu.gp0(0x6a00_0040);
+        gpu.gp0(0);
+
+        assert_eq!(pixel(&gpu, 0, 0), expected, "mode {}", mode);
+    }
+}
+
+#[test]
+pub fn texture_4bit_clut() {
+    let mut gpu = gpu();
+
+    // Texture page at x = 64, CLUT at (0, 20)
+    load_image(&mut gpu, 64, 0, 1, 1, &[0x3210]);
+    load_image(&mut gpu, 0, 20, 4, 1, &[0x0000, 0x001f, 0x03e0, 0x7c00]);
+
+    gpu.gp0(0xe100_0001);
+
+    // Raw textured 4x1 rectangle at (0, 10)
+    gpu.gp0(0x6500_0000);
+    gpu.gp0(10 << 16);
+    gpu.gp0((20 << 6) << 16);
+    gpu.gp0((1 << 16) | 4);
+
+    // Index 0 is black and thus transparent
+    assert_eq!(pixel(&gpu, 0, 10), 0);
+    assert_eq!(pixel(&gpu, 1, 10), 0x001f);
+    assert_eq!(pixel(&gpu, 2, 10), 0x03e0);
+    assert_eq!(pixel(&gpu, 3, 10), 0x7c00);
+}
+
+#[test]
+pub fn texture_8bit_clut() {
+    let mut gpu = gpu();
+
+    load_image(&mut gpu, 64, 0, 1, 1, &[0x0201]);
+    load_image(&mut gpu, 0, 20, 3, 1, &[0, 0x1234, 0x4321]);
+
+    gpu.gp0(0xe100_0001 | (1 << 7));
+
+    gpu.gp0(0x6500_0000);
+    gpu.gp0(10 << 16);
+    gpu.gp0((20 << 6) << 16);
+    gpu.gp0((1 << 16) | 2);
+
+    assert_eq!(pixel(&gpu, 0, 10), 0x1234);
+    assert_eq!(pixel(&gpu, 1, 10), 0x4321);
+}
+
+#[test]
+pub fn texture_modulation() {
+    let mut gpu = gpu();
+
+    load_image(&mut gpu, 128, 0, 2, 1, &[0x001f, 0x001f]);
+    gpu.gp0(0xe100_0002 | (2 << 7));
+
+    // 0x80 is the neutral color
+    gpu.gp0(0x6c80_8080);
+    gpu.gp0(10 << 16);
+    gpu.gp0(0);
+    assert_eq!(pixel(&gpu, 0, 10), 0x1f);
+
+    gpu.gp0(0x6c40_4040);
+    gpu.gp0(11 << 16);
+    gpu.gp0(0);
+    assert_eq!(pixel(&gpu, 0, 11), 15);
+}
+
+#[test]
+pub fn textured_triangle() {
+    let mut gpu = gpu();
+
+    let texture: Vec<u16> = (1..=16).collect();
+    load_image(&mut gpu, 128, 0, 4, 4, &texture);
+
+    gpu.gp0(0xe100_0002 | (2 << 7));
+
+    // Raw textured triangle mapping the texture 1:1 at (0, 10)
+    gpu.gp0(0x2500_0000);
+    gpu.gp0(10 << 16);
+    gpu.gp0(0);
+    gpu.gp0((10 << 16) | 4);
+    gpu.gp0((((2 << 7) | 2) << 16) | 4);
+    gpu.gp0(14 << 16);
+    gpu.gp0(4 << 8);
+
+    assert_eq!(pixel(&gpu, 0, 10), 1);
+    assert_eq!(pixel(&gpu, 1, 10), 2);
+    assert_eq!(pixel(&gpu, 0, 11), 5);
+    assert_eq!(pixel(&gpu, 1, 12), 10);
+}
+
+#[test]
+pub fn texture_window() {
+    let mut gpu = gpu();
+
+    let texture: Vec<u16> = (1..=16).collect();
+    load_image(&mut gpu, 128, 0, 16, 1, &texture);
+
+    gpu.gp0(0xe100_0002 | (2 << 7));
+    // Mask of 8 pixels with the offset bit set
+    gpu.gp0(0xe200_0000 | (1 << 10) | 1);
+
+    gpu.gp0(0x6500_0000);
+    gpu.gp0(10 << 16);
+    gpu.gp0(0);
+    gpu.gp0((1 << 16) | 4);
+
+    assert_eq!(pixel(&gpu, 0, 10), 9);
+    assert_eq!(pixel(&gpu, 3, 10), 12);
+}
+
+#[test]
+pub fn rectangle_flip() {
+    let mut gpu = gpu();
+
+    let texture: Vec<u16> = (1..=4).collect();
+    load_image(&mut gpu, 128, 0, 4, 1, &texture);
+
+    gpu.gp0(0xe100_0002 | (2 << 7) | (1 << 12));
+
+    gpu.gp0(0x6500_0000);
+    gpu.gp0(10 << 16);
+    gpu.gp0(3);
+    gpu.gp0((1 << 16) | 4);
+
+    assert_eq!(pixel(&gpu, 0, 10), 4);
+    assert_eq!(pixel(&gpu, 3, 10), 1);
+}
+
+#[test]
+pub fn mask_bit() {
+    let mut gpu = gpu();
+
+    load_image(&mut gpu, 0, 0, 1, 1, &[0x8000]);
+
+    gpu.gp0(0xe600_0003);
+    gpu.gp0(0x7000_00ff);
+    gpu.gp0(0);
+
+    // The masked pixel is preserved, the new pixels get the mask bit
+    assert_eq!(pixel(&gpu, 0, 0), 0x8000);
+    assert_eq!(pixel(&gpu, 1, 0), 0x801f);
+    assert_eq!(pixel(&gpu, 7, 7), 0x801f);
+}
+
+#[test]
+pub fn drawing_area_and_offset() {
+    let mut gpu = Gpu::new();
+
+    gpu.gp0(0xe300_0000 | (8 << 10) | 8);
+    gpu.gp0(0xe400_0000 | (11 << 10) | 11);
+    gpu.gp0(0xe500_0000 | (2 << 11) | 2);
+
+    // 8x8 rectangle at (6, 6) once offset, clipped to (8, 8)-(11, 11)
+    gpu.gp0(0x7000_00ff);
+    gpu.gp0((4 << 16) | 4);
+
+    assert_region(
+        &gpu,
+        7,
+        7,
+        0x1f,
+        &["......", ".####.", ".####.", ".####.", ".####.", "......"],
+    );
+}
+
+#[test]
+pub fn negative_offset() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0xe500_0000 | (0x7fe << 11) | 0x7fe);
+
+    gpu.gp0(0x6800_00ff);
+    gpu.gp0((3 << 16) | 3);
+
+    assert_eq!(pixel(&gpu, 1, 1), 0x1f);
+}
+
+#[test]
+pub fn oversized_polygon() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0x2000_00ff);
+    gpu.gp0(0);
+    gpu.gp0(1024);
+    gpu.gp0(4 << 16);
+
+    assert_eq!(pixel(&gpu, 0, 0), 0);
+}
+
+#[test]
+pub fn lines() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0x4000_00ff);
+    gpu.gp0(0);
+    gpu.gp0(3);
+
+    // Polyline (0, 2) -> (2, 2) -> (2, 4)
+    gpu.gp0(0x4800_00ff);
+    gpu.gp0(2 << 16);
+    gpu.gp0((2 << 16) | 2);
+    gpu.gp0((4 << 16) | 2);
+    gpu.gp0(0x5555_5555);
+
+    // Both end points are drawn
+    assert_region(
+        &gpu,
+        0,
+        0,
+        0x1f,
+        &["####.", ".....", "###..", "..#..", "..#..", "....."],
+    );
+
+    // The terminator ends the polyline
+    gpu.gp0(0x6800_00ff);
+    gpu.gp0(10);
+    assert_eq!(pixel(&gpu, 10, 0), 0x1f);
+}
+
+#[test]
+pub fn gouraud_line() {
+    let mut gpu = gpu();
+
+    gpu.gp0(0x5000_0000);
+    gpu.gp0(0);
+    gpu.gp0(0x0000_00f8);
+    gpu.gp0(2);
+
+    assert_eq!(pixel(&gpu, 0, 0), 0);
+    assert_eq!(pixel(&gpu, 1, 0), 15);
+    assert_eq!(pixel(&gpu, 2, 0), 31);
+}
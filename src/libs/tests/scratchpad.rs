@@ -0,0 +1,79 @@
+use crate::libs::bios::Bios;
+use crate::libs::bus::Bus;
+use crate::libs::error::BusError;
+use crate::libs::map::memory;
+use crate::libs::ram::Ram;
+
+fn bus() -> Bus {
+    Bus::new(Bios::from_bytes(Vec::new()), Ram::new())
+}
+
+#[test]
+pub fn segments() {
+    assert_eq!(memory::SCRATCHPAD.contains_cached(0x1f80_0010), Some(0x10));
+    assert_eq!(memory::SCRATCHPAD.contains_cached(0x9f80_03fc), Some(0x3fc));
+    assert_eq!(memory::SCRATCHPAD.contains_cached(0xbf80_0010), None);
+    assert_eq!(memory::SCRATCHPAD.contains_cached(0x1f80_0400), None);
+
+    assert!(memory::is_mapped(0x9f80_0000));
+    assert!(!memory::is_mapped(0xbf80_0000));
+}
+
+#[test]
+pub fn access_widths() {
+    let mut bus = bus();
+
+    bus.store32(0x1f80_0000, 0x1234_5678).unwrap();
+    assert_eq!(bus.load8(0x1f80_0001), Ok(0x56));
+    assert_eq!(bus.load16(0x1f80_0002), Ok(0x1234));
+
+    bus.store8(0x1f80_0003, 0xab).unwrap();
+    bus.store16(0x1f80_0000, 0xcdef).unwrap();
+    assert_eq!(bus.load32(0x1f80_0000), Ok(0xab34_cdef));
+
+    // The RAM below isn't touched
+    assert_eq!(bus.ram().load32(0), 0xcaca_caca);
+}
+
+#[test]
+pub fn kuseg_and_kseg0_mirror() {
+    let mut bus = bus();
+
+    bus.store32(0x1f80_03fc, 0xdead_beef).unwrap();
+    assert_eq!(bus.load32(0x9f80_03fc), Ok(0xdead_beef));
+
+    bus.store16(0x9f80_0100, 0x55aa).unwrap();
+    assert_eq!(bus.load16(0x1f80_0100), Ok(0x55aa));
+}
+
+#[test]
+pub fn not_reachable_through_kseg1() {
+    let mut bus = bus();
+
+    assert_eq!(
+        bus.load32(0xbf80_0000),
+        Err(BusError::Unmapped {
+            addr: 0xbf80_0000,
+            width: 4,
+            val: None,
+        })
+    );
+    assert!(bus.store8(0xbf80_0000, 1).is_err());
+
+    // Past the 1KB
+    assert!(bus.load32(0x1f80_0400).is_err());
+}
+
+#[test]
+pub fn not_reachable_by_dma() {
+    let mut bus = bus();
+
+    // OTC channel: one word, decrementing, from the scratchpad address
+    bus.store32(0x1f80_10e0, 0x1f80_00fc).unwrap();
+    bus.store32(0x1f80_10e4, 1).unwrap();
+    bus.store32(0x1f80_10e8, 0x1100_0002).unwrap();
+
+    // The address wraps into RAM
+    assert_eq!(bus.load32(0x1f80_00fc), Ok(0));
+    assert_eq!(bus.ram().load32(0xfc), 0x00ff_ffff);
+}
@@ -0,0 +1,124 @@
+use crate::libs::irq::Irq;
+use crate::libs::timers::Timers;
+
+const RESET_ON_TARGET: u32 = 1 << 3;
+const IRQ_ON_TARGET: u32 = 1 << 4;
+const IRQ_ON_OVERFLOW: u32 = 1 << 5;
+const IRQ_REPEAT: u32 = 1 << 6;
+const IRQ_TOGGLE: u32 = 1 << 7;
+
+#[test]
+pub fn counts_system_clock() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.tick(100, &mut irq);
+    assert_eq!(timers.load(0x00), 100);
+    assert_eq!(timers.load(0x10), 100);
+    assert_eq!(timers.load(0x20), 100);
+}
+
+#[test]
+pub fn system_clock_div8() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.store(0x24, 2 << 8);
+    timers.tick(7, &mut irq);
+    assert_eq!(timers.load(0x20), 0);
+    timers.tick(10, &mut irq);
+    assert_eq!(timers.load(0x20), 2);
+}
+
+#[test]
+pub fn reset_on_target() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.store(0x08, 10);
+    timers.store(0x04, RESET_ON_TARGET | IRQ_ON_TARGET | IRQ_REPEAT);
+    timers.tick(25, &mut irq);
+
+    // The counter wraps to 0 as soon as it reaches the target
+    assert_eq!(timers.load(0x00), 5);
+    assert_eq!(irq.status(), 1 << 4);
+
+    // Reached target is cleared on read
+    assert_ne!(timers.load(0x04) & (1 << 11), 0);
+    assert_eq!(timers.load(0x04) & (1 << 11), 0);
+}
+
+#[test]
+pub fn one_shot_irq() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.store(0x18, 5);
+    timers.store(0x14, RESET_ON_TARGET | IRQ_ON_TARGET);
+    timers.tick(6, &mut irq);
+    assert_eq!(irq.status(), 1 << 5);
+
+    irq.ack(0);
+    timers.tick(6, &mut irq);
+    assert_eq!(irq.status(), 0);
+}
+
+#[test]
+pub fn overflow_irq() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.store(0x24, IRQ_ON_OVERFLOW | IRQ_REPEAT);
+    timers.tick(0xffff, &mut irq);
+    assert_eq!(irq.status(), 1 << 6);
+    assert_ne!(timers.load(0x24) & (1 << 12), 0);
+
+    timers.tick(1, &mut irq);
+    assert_eq!(timers.load(0x20), 0);
+}
+
+#[test]
+pub fn toggle_irq_line() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.store(0x08, 4);
+    timers.store(
+        0x04,
+        RESET_ON_TARGET | IRQ_ON_TARGET | IRQ_REPEAT | IRQ_TOGGLE,
+    );
+
+    timers.tick(4, &mut irq);
+    assert_eq!(timers.load(0x04) & (1 << 10), 0);
+    assert_eq!(irq.status(), 1 << 4);
+
+    irq.ack(0);
+    timers.tick(5, &mut irq);
+    assert_ne!(timers.load(0x04) & (1 << 10), 0);
+    assert_eq!(irq.status(), 0);
+}
+
+#[test]
+pub fn hblank_source() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.store(0x14, 1 << 8);
+    for _ in 0..3 {
+        timers.set_hblank(true, &mut irq);
+        timers.tick(100, &mut irq);
+        timers.set_hblank(false, &mut irq);
+    }
+
+    assert_eq!(timers.load(0x10), 3);
+}
+
+#[test]
+pub fn timer2_stop() {
+    let mut timers = Timers::new();
+    let mut irq = Irq::new();
+
+    timers.store(0x24, 1);
+    timers.tick(100, &mut irq);
+    assert_eq!(timers.load(0x20), 0);
+}
@@ -0,0 +1,61 @@
+use crate::libs::tests::{cpu_with_program, Log};
+use crate::libs::trace::{TraceOptions, Tracer};
+
+// lui t0, 0x8001; ori t0, t0, 0x10; sw t0, 0(t0); lw t1, 0(t0); nop; addiu t2, t1, 1
+const PROGRAM: [u32; 6] = [
+    0x3c08_8001,
+    0x3508_0010,
+    0xad08_0000,
+    0x8d09_0000,
+    0,
+    0x252a_0001,
+];
+
+fn trace(options: TraceOptions, steps: usize) -> Vec<String> {
+    let mut cpu = cpu_with_program(&PROGRAM);
+    let log = Log::new();
+
+    cpu.set_tracer(Some(Tracer::new(Box::new(log.clone()), options)));
+
+    for _ in 0..steps {
+        cpu.run_next_opcode().unwrap();
+    }
+
+    log.lines()
+}
+
+#[test]
+pub fn register_writes() {
+    assert_eq!(
+        trace(TraceOptions::default(), 6),
+        [
+            "1fc00000 3c088001 lui     t0, 0x8001 ; t0=80010000",
+            "1fc00004 35080010 ori     t0, t0, 0x10 ; t0=80010010",
+            "1fc00008 ad080000 sw      t0, 0x0(t0)",
+            "1fc0000c 8d090000 lw      t1, 0x0(t0) ; load t1=80010010",
+            "1fc00010 00000000 nop ; t1=80010010",
+            "1fc00014 252a0001 addiu   t2, t1, 0x1 ; t2=80010011",
+        ]
+    );
+}
+
+#[test]
+pub fn start_stop_and_limit() {
+    let window = TraceOptions {
+        start: Some(0x1fc0_0004),
+        stop: Some(0x1fc0_0010),
+        limit: None,
+    };
+
+    let lines = trace(window, 6);
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("1fc00004 "));
+    assert!(lines[2].starts_with("1fc0000c "));
+
+    let limited = TraceOptions {
+        limit: Some(2),
+        ..TraceOptions::default()
+    };
+
+    assert_eq!(trace(limited, 6).len(), 2);
+}
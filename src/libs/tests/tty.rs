@@ -0,0 +1,37 @@
+use crate::libs::cpu::CPU;
+use crate::libs::tests::cpu_with_program;
+use crate::libs::tty::TtySink;
+
+fn cpu(program: &[u32]) -> CPU {
+    let mut cpu = cpu_with_program(program);
+
+    cpu.bus_mut()
+        .tty_mut()
+        .set_sink(TtySink::Buffer(Vec::new()));
+    cpu
+}
+
+#[test]
+pub fn putchar() {
+    // ori $9, $0, 0x3d; ori $4, $0, 'H'; ori $8, $0, 0xb0; jr $8; nop
+    let mut cpu = cpu(&[0x3409_003d, 0x3404_0048, 0x3408_00b0, 0x0100_0008, 0]);
+
+    for _ in 0..6 {
+        cpu.run_next_opcode().unwrap();
+    }
+
+    assert_eq!(cpu.bus().tty().buffer(), b"H");
+}
+
+#[test]
+pub fn duart() {
+    // lui $8, 0x1f80; ori $4, $0, 'i'; sb $4, 0x2023($8)
+    let mut cpu = cpu(&[0x3c08_1f80, 0x3404_0069, 0xa104_2023]);
+
+    for _ in 0..3 {
+        cpu.run_next_opcode().unwrap();
+    }
+
+    assert_eq!(cpu.bus().tty().buffer(), b"i");
+    assert_eq!(cpu.bus_mut().load8(0x1f80_2021), Ok(0x0c));
+}
@@ -0,0 +1,302 @@
+use crate::libs::irq::{Interrupt, Irq};
+
+/// The three root counters
+pub struct Timers {
+    timers: [Timer; 3],
+    in_hblank: bool,
+    in_vblank: bool,
+}
+
+impl Timers {
+    pub fn new() -> Timers {
+        Timers {
+            timers: [Timer::new(0), Timer::new(1), Timer::new(2)],
+            in_hblank: false,
+            in_vblank: false,
+        }
+    }
+
+    pub fn load(&mut self, offset: usize) -> u32 {
+        let timer = &mut self.timers[offset >> 4];
+
+        match offset & 0xf {
+            0 => timer.counter,
+            4 => timer.mode(),
+            8 => timer.target,
+            _ => 0,
+        }
+    }
+
+    pub fn store(&mut self, offset: usize, val: u32) {
+        let timer = &mut self.timers[offset >> 4];
+
+        match offset & 0xf {
+            0 => timer.counter = val & 0xffff,
+            4 => timer.set_mode(val),
+            8 => timer.target = val & 0xffff,
+            _ => (),
+        }
+    }
+
+    /// Advance the counters clocked from the system clock by `cycles` CPU
+    /// cycles
+    pub fn tick(&mut self, cycles: u32, irq: &mut Irq) {
+        let blank = [self.in_hblank, self.in_vblank, false];
+
+        for (timer, blank) in self.timers.iter_mut().zip(blank) {
+            let ticks = match timer.source() {
+                Source::SystemClock => cycles,
+                Source::SystemClockDiv8 => {
+                    timer.prescaler += cycles;
+                    let ticks = timer.prescaler / 8;
+                    timer.prescaler %= 8;
+                    ticks
+                }
+                _ => continue,
+            };
+
+            timer.run(ticks, blank, irq);
+        }
+    }
+
+    /// Advance timer 0 when it is clocked from the GPU dot clock
+    pub fn dot_clock(&mut self, dots: u32, irq: &mut Irq) {
+        let timer = &mut self.timers[0];
+
+        if timer.source() == Source::DotClock {
+            timer.run(dots, self.in_hblank, irq);
+        }
+    }
+
+    /// Horizontal blanking edge from the GPU. Timer 0 uses it to
+    /// synchronize and timer 1 can count it.
+    pub fn set_hblank(&mut self, active: bool, irq: &mut Irq) {
+        if active && !self.in_hblank {
+            self.timers[0].blank_start();
+
+            let timer = &mut self.timers[1];
+            if timer.source() == Source::HBlank {
+                timer.run(1, self.in_vblank, irq);
+            }
+        }
+
+        self.in_hblank = active;
+    }
+
+    /// Vertical blanking edge from the GPU, used by timer 1 to synchronize
+    pub fn set_vblank(&mut self, active: bool) {
+        if active && !self.in_vblank {
+            self.timers[1].blank_start();
+        }
+
+        self.in_vblank = active;
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Source {
+    SystemClock,
+    SystemClockDiv8,
+    DotClock,
+    HBlank,
+}
+
+struct Timer {
+    index: usize,
+    /// Kept on 32 bits to make the wrap-around easier to handle, the
+    /// hardware counter is 16 bits wide
+    counter: u32,
+    target: u32,
+    sync: bool,
+    sync_mode: u8,
+    reset_on_target: bool,
+    irq_on_target: bool,
+    irq_on_overflow: bool,
+    irq_repeat: bool,
+    irq_toggle: bool,
+    clock_source: u8,
+    /// Interrupt request line, active low
+    irq_line: bool,
+    /// Set once the IRQ fired in one-shot mode
+    irq_done: bool,
+    reached_target: bool,
+    reached_overflow: bool,
+    /// Sync mode 3 for timers 0 and 1: wait for the first blanking
+    waiting_for_blank: bool,
+    /// Remainder of the system clock divided by 8
+    prescaler: u32,
+}
+
+impl Timer {
+    fn new(index: usize) -> Timer {
+        Timer {
+            index,
+            counter: 0,
+            target: 0,
+            sync: false,
+            sync_mode: 0,
+            reset_on_target: false,
+            irq_on_target: false,
+            irq_on_overflow: false,
+            irq_repeat: false,
+            irq_toggle: false,
+            clock_source: 0,
+            irq_line: true,
+            irq_done: false,
+            reached_target: false,
+            reached_overflow: false,
+            waiting_for_blank: false,
+            prescaler: 0,
+        }
+    }
+
+    fn interrupt(&self) -> Interrupt {
+        match self.index {
+            0 => Interrupt::Timer0,
+            1 => Interrupt::Timer1,
+            _ => Interrupt::Timer2,
+        }
+    }
+
+    fn source(&self) -> Source {
+        match (self.index, self.clock_source) {
+            (0, 1 | 3) => Source::DotClock,
+            (1, 1 | 3) => Source::HBlank,
+            (2, 2 | 3) => Source::SystemClockDiv8,
+            _ => Source::SystemClock,
+        }
+    }
+
+    /// Reading the mode register clears the "reached" flags
+    fn mode(&mut self) -> u32 {
+        let mut r = 0;
+
+        r |= self.sync as u32;
+        r |= (self.sync_mode as u32) << 1;
+        r |= (self.reset_on_target as u32) << 3;
+        r |= (self.irq_on_target as u32) << 4;
+        r |= (self.irq_on_overflow as u32) << 5;
+        r |= (self.irq_repeat as u32) << 6;
+        r |= (self.irq_toggle as u32) << 7;
+        r |= (self.clock_source as u32) << 8;
+        r |= (self.irq_line as u32) << 10;
+        r |= (self.reached_target as u32) << 11;
+        r |= (self.reached_overflow as u32) << 12;
+
+        self.reached_target = false;
+        self.reached_overflow = false;
+
+        r
+    }
+
+    fn set_mode(&mut self, val: u32) {
+        self.sync = val & 1 != 0;
+        self.sync_mode = ((val >> 1) & 3) as u8;
+        self.reset_on_target = (val >> 3) & 1 != 0;
+        self.irq_on_target = (val >> 4) & 1 != 0;
+        self.irq_on_overflow = (val >> 5) & 1 != 0;
+        self.irq_repeat = (val >> 6) & 1 != 0;
+        self.irq_toggle = (val >> 7) & 1 != 0;
+        self.clock_source = ((val >> 8) & 3) as u8;
+
+        // Writing the mode resets the counter and the IRQ line
+        self.counter = 0;
+        self.irq_line = true;
+        self.irq_done = false;
+        self.waiting_for_blank = self.sync && self.sync_mode == 3 && self.index != 2;
+    }
+
+    fn paused(&self, in_blank: bool) -> bool {
+        if !self.sync {
+            return false;
+        }
+
+        match (self.index, self.sync_mode) {
+            (2, 0 | 3) => true,
+            (2, _) => false,
+            (_, 0) => in_blank,
+            (_, 2) => !in_blank,
+            (_, 3) => self.waiting_for_blank,
+            _ => false,
+        }
+    }
+
+    fn blank_start(&mut self) {
+        if !self.sync || self.index == 2 {
+            return;
+        }
+
+        match self.sync_mode {
+            1 | 2 => self.counter = 0,
+            // Switch to free run
+            3 => self.waiting_for_blank = false,
+            _ => (),
+        }
+    }
+
+    fn run(&mut self, mut ticks: u32, in_blank: bool, irq: &mut Irq) {
+        if self.paused(in_blank) {
+            return;
+        }
+
+        while ticks > 0 {
+            let to_target = match self.target > self.counter {
+                true => self.target - self.counter,
+                false => self.target + 0x10000 - self.counter,
+            };
+            let to_overflow = match self.counter < 0xffff {
+                true => 0xffff - self.counter,
+                false => 0x10000,
+            };
+
+            let step = ticks.min(to_target).min(to_overflow);
+
+            ticks -= step;
+            self.counter = (self.counter + step) & 0xffff;
+
+            if self.counter == self.target {
+                self.reached_target = true;
+
+                if self.irq_on_target {
+                    self.trigger_irq(irq);
+                }
+
+                if self.reset_on_target {
+                    self.counter = 0;
+                    continue;
+                }
+            }
+
+            if self.counter == 0xffff {
+                self.reached_overflow = true;
+
+                if self.irq_on_overflow {
+                    self.trigger_irq(irq);
+                }
+            }
+        }
+    }
+
+    fn trigger_irq(&mut self, irq: &mut Irq) {
+        if self.irq_done && !self.irq_repeat {
+            return;
+        }
+
+        self.irq_done = true;
+
+        if self.irq_toggle {
+            self.irq_line = !self.irq_line;
+        } else {
+            // The line is only pulled low for a few cycles
+            self.irq_line = false;
+        }
+
+        if !self.irq_line {
+            irq.assert(self.interrupt());
+        }
+
+        if !self.irq_toggle {
+            self.irq_line = true;
+        }
+    }
+}
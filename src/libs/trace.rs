@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::libs::disasm::{self, REGISTERS};
+use crate::libs::map::opcode::Instruction;
+
+/// When to record instructions
+#[derive(Clone, Copy, Default)]
+pub struct TraceOptions {
+    /// Start recording when this address is reached
+    pub start: Option<u32>,
+    /// Stop recording when this address is reached
+    pub stop: Option<u32>,
+    /// Stop after this many instructions
+    pub limit: Option<u64>,
+}
+
+/// Execution trace, one line per instruction:
+///
+/// `<pc> <opcode> <disassembly> [; <reg>=<value>...] [; load <reg>=<value>]`
+///
+/// The registers are the ones written by the step, the load is the pending
+/// load delay slot value which lands on the next step. Steps taken by an
+/// interrupt show `<interrupt>` instead of the disassembly.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    options: TraceOptions,
+    started: bool,
+    done: bool,
+    count: u64,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>, options: TraceOptions) -> Tracer {
+        Tracer {
+            out,
+            options,
+            started: options.start.is_none(),
+            done: false,
+            count: 0,
+        }
+    }
+
+    pub fn file(path: &Path, options: TraceOptions) -> Result<Tracer, String> {
+        match File::create(path) {
+            Ok(f) => Ok(Tracer::new(Box::new(BufWriter::new(f)), options)),
+            Err(e) => Err(format!("Couldn't create {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Whether the instruction at `pc` should be recorded
+    pub fn wants(&mut self, pc: u32) -> bool {
+        if self.done {
+            return false;
+        }
+
+        if self.options.stop == Some(pc) {
+            self.finish();
+            return false;
+        }
+
+        if !self.started && self.options.start == Some(pc) {
+            self.started = true;
+        }
+
+        self.started
+    }
+
+    /// Record one step, `r` and `out_r` are the registers before and after
+    pub fn record(
+        &mut self,
+        pc: u32,
+        i: Instruction,
+        interrupt: bool,
+        r: &[u32; 32],
+        out_r: &[u32; 32],
+        load: (usize, u32),
+    ) {
+        let mut line = format!("{:08x} {:08x} ", pc, i.0);
+
+        match interrupt {
+            true => line.push_str("<interrupt>"),
+            false => line.push_str(&disasm::disassemble(i, Some(pc))),
+        }
+
+        let written: Vec<String> = (1..32)
+            .filter(|&n| r[n] != out_r[n])
+            .map(|n| format!("{}={:08x}", REGISTERS[n], out_r[n]))
+            .collect();
+
+        if !written.is_empty() {
+            line.push_str(" ; ");
+            line.push_str(&written.join(" "));
+        }
+
+        let (reg, val) = load;
+
+        if reg != 0 {
+            line.push_str(&format!(" ; load {}={:08x}", REGISTERS[reg], val));
+        }
+
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            println!("Trace write failed: {}", e);
+            self.done = true;
+            return;
+        }
+
+        self.count += 1;
+
+        if self.options.limit == Some(self.count) {
+            self.finish();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        let _ = self.out.flush();
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// DUART channel A registers, as offsets in the Expansion 2 region
+pub const DUART_STATUS_A: usize = 0x21;
+pub const DUART_TX_A: usize = 0x23;
+
+/// DUART status with the transmitter ready and empty
+pub const DUART_TX_READY: u8 = 0x0c;
+
+/// Destination of the text printed by the BIOS and the running program
+pub enum TtySink {
+    Stdout,
+    File(BufWriter<File>),
+    /// Kept in memory, for tests
+    Buffer(Vec<u8>),
+    Discard,
+}
+
+impl TtySink {
+    pub fn file(path: &Path) -> Result<TtySink, String> {
+        match File::create(path) {
+            Ok(f) => Ok(TtySink::File(BufWriter::new(f))),
+            Err(e) => Err(format!("Couldn't create {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Console output, fed by the std_out_putchar kernel call (A0h:3Ch and
+/// B0h:3Dh) and the Expansion 2 DUART
+pub struct Tty {
+    sink: TtySink,
+}
+
+impl Tty {
+    pub fn new() -> Tty {
+        Tty {
+            sink: TtySink::Stdout,
+        }
+    }
+
+    pub fn set_sink(&mut self, sink: TtySink) {
+        self.flush();
+        self.sink = sink;
+    }
+
+    pub fn putchar(&mut self, c: u8) {
+        let result = match &mut self.sink {
+            TtySink::Stdout => {
+                let mut out = io::stdout().lock();
+
+                out.write_all(&[c]).and_then(|_| match c {
+                    b'\n' => out.flush(),
+                    _ => Ok(()),
+                })
+            }
+            TtySink::File(f) => f.write_all(&[c]),
+            TtySink::Buffer(buf) => {
+                buf.push(c);
+                Ok(())
+            }
+            TtySink::Discard => Ok(()),
+        };
+
+        if let Err(e) = result {
+            println!("TTY write failed: {}", e);
+            self.sink = TtySink::Discard;
+        }
+    }
+
+    /// Text captured by a `TtySink::Buffer` sink
+    pub fn buffer(&self) -> &[u8] {
+        match &self.sink {
+            TtySink::Buffer(buf) => buf,
+            _ => &[],
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let _ = match &mut self.sink {
+            TtySink::Stdout => io::stdout().flush(),
+            TtySink::File(f) => f.flush(),
+            _ => Ok(()),
+        };
+    }
+}
@@ -0,0 +1,41 @@
+/// Kind of access a watchpoint reacts to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Data watchpoint on `len` bytes at `addr`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub addr: u32,
+    pub len: u32,
+    pub access: Access,
+}
+
+impl Watchpoint {
+    /// Whether an access of `len` bytes at `addr` triggers the watchpoint.
+    /// The segment bits are ignored, KUSEG, KSEG0 and KSEG1 accesses to the
+    /// same location all match.
+    pub fn matches(&self, addr: u32, len: u32, write: bool) -> bool {
+        let kind = match self.access {
+            Access::Read => !write,
+            Access::Write => write,
+            Access::ReadWrite => true,
+        };
+
+        let start = self.addr & 0x1fff_ffff;
+        let addr = addr & 0x1fff_ffff;
+
+        kind && addr < start.wrapping_add(self.len) && start < addr.wrapping_add(len)
+    }
+}
+
+/// Load or store that triggered a watchpoint
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    pub addr: u32,
+    pub write: bool,
+}
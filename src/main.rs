@@ -1,22 +1,291 @@
-mod consts;
-mod libs;
+use std::path::PathBuf;
 
-use crate::libs::bios::Bios;
-use crate::libs::bus::Bus;
-use crate::libs::cpu::CPU;
-use crate::libs::ram::Ram;
+use psx::libs::bios::Bios;
+use psx::libs::bus::Bus;
+use psx::libs::cartridge::Cartridge;
+use psx::libs::cpu::CPU;
+use psx::libs::disc::Disc;
+use psx::libs::exe::Exe;
+use psx::libs::framebuffer::{self, DumpArea, ImageFormat};
+use psx::libs::gdb::{GdbStub, Status};
+use psx::libs::kernel::KernelTracer;
+use psx::libs::monitor::Monitor;
+use psx::libs::ram::Ram;
+use psx::libs::trace::{TraceOptions, Tracer};
+use psx::libs::tty::TtySink;
+
+const USAGE: &str = "Usage: psx [options]
+
+Options:
+    --bios PATH         BIOS ROM dump (default: bios/SCPH1001.BIN)
+    --cart PATH         ROM image to plug into the expansion port
+    --debug             start halted in the interactive monitor
+    --disc PATH         CUE sheet or BIN image to insert in the CD-ROM drive
+    --dump-every N      dump every Nth frame to the dump directory
+    --dump-dir DIR      directory for the frame dumps (default: frames)
+    --dump-format FMT   ppm or png (default: ppm)
+    --dump-vram         dump the whole VRAM instead of the display area
+    --exe PATH          PS-X EXE to run in place of the shell
+    --frames N          stop after N frames
+    --gdb PORT          wait for a GDB connection on localhost:PORT
+    --hle               emulate the BIOS kernel instead of running a ROM dump,
+                        the default when the dump is missing
+    --on-error MODE     halt (default) or log and continue with open bus
+                        values when an instruction fails
+    --strict            report accesses to unmapped addresses as errors
+                        instead of raising bus error exceptions
+    --trace PATH        write an instruction trace to a file
+    --trace-start PC    start the trace when PC is reached (hexadecimal)
+    --trace-stop PC     stop the trace when PC is reached (hexadecimal)
+    --trace-limit N     stop the trace after N instructions
+    --trace-kernel      log the A0h, B0h and C0h kernel calls
+    --tty-file PATH     write the TTY output to a file instead of stdout";
+
+/// What to do when an instruction fails
+#[derive(Clone, Copy)]
+enum OnError {
+    Halt,
+    Log,
+}
+
+struct Args {
+    bios: String,
+    cart: Option<PathBuf>,
+    debug: bool,
+    disc: Option<PathBuf>,
+    dump_every: Option<u64>,
+    dump_dir: PathBuf,
+    dump_format: ImageFormat,
+    dump_area: DumpArea,
+    exe: Option<PathBuf>,
+    frames: Option<u64>,
+    gdb: Option<u16>,
+    hle: bool,
+    on_error: OnError,
+    strict: bool,
+    trace: Option<PathBuf>,
+    trace_options: TraceOptions,
+    trace_kernel: bool,
+    tty_file: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse() -> Result<Args, String> {
+        let mut args = Args {
+            bios: "bios/SCPH1001.BIN".to_string(),
+            cart: None,
+            debug: false,
+            disc: None,
+            dump_every: None,
+            dump_dir: PathBuf::from("frames"),
+            dump_format: ImageFormat::Ppm,
+            dump_area: DumpArea::Display,
+            exe: None,
+            frames: None,
+            gdb: None,
+            hle: false,
+            on_error: OnError::Halt,
+            strict: false,
+            trace: None,
+            trace_options: TraceOptions::default(),
+            trace_kernel: false,
+            tty_file: None,
+        };
+
+        let mut iter = std::env::args().skip(1);
+
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--bios" => args.bios = value()?,
+                "--cart" => args.cart = Some(PathBuf::from(value()?)),
+                "--debug" => args.debug = true,
+                "--disc" => args.disc = Some(PathBuf::from(value()?)),
+                "--dump-every" => args.dump_every = Some(parse_count(&value()?)?),
+                "--dump-dir" => args.dump_dir = PathBuf::from(value()?),
+                "--dump-format" => {
+                    let format = value()?;
+                    args.dump_format = ImageFormat::from_path(format!("_.{}", format).as_ref())?;
+                }
+                "--dump-vram" => args.dump_area = DumpArea::Vram,
+                "--exe" => args.exe = Some(PathBuf::from(value()?)),
+                "--frames" => args.frames = Some(parse_count(&value()?)?),
+                "--gdb" => {
+                    let port = value()?;
+                    args.gdb = Some(port.parse().map_err(|_| format!("Invalid port {}", port))?);
+                }
+                "--hle" => args.hle = true,
+                "--on-error" => {
+                    args.on_error = match value()?.as_str() {
+                        "halt" => OnError::Halt,
+                        "log" => OnError::Log,
+                        mode => return Err(format!("Invalid error mode {}", mode)),
+                    }
+                }
+                "--strict" => args.strict = true,
+                "--trace" => args.trace = Some(PathBuf::from(value()?)),
+                "--trace-start" => args.trace_options.start = Some(parse_address(&value()?)?),
+                "--trace-stop" => args.trace_options.stop = Some(parse_address(&value()?)?),
+                "--trace-limit" => args.trace_options.limit = Some(parse_count(&value()?)?),
+                "--trace-kernel" => args.trace_kernel = true,
+                "--tty-file" => args.tty_file = Some(PathBuf::from(value()?)),
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn parse_address(val: &str) -> Result<u32, String> {
+    let digits = val.trim_start_matches("0x");
+
+    u32::from_str_radix(digits, 16).map_err(|_| format!("Invalid address {}", val))
+}
+
+fn parse_count(val: &str) -> Result<u64, String> {
+    match val.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Invalid count {}", val)),
+    }
+}
 
 fn main() {
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    });
+
     println!("{:032b}", 0x1420fffc);
 
-    let bios = Bios::new("bios/SCPH1001.BIN");
+    let bios = if args.hle {
+        Bios::hle()
+    } else if !std::path::Path::new(&args.bios).exists() {
+        println!("{} not found, using the HLE BIOS", args.bios);
+        Bios::hle()
+    } else {
+        Bios::new(&args.bios)
+    };
     let ram = Ram::new();
 
-    let bus = Bus::new(bios, ram);
+    let mut bus = Bus::new(bios, ram);
+
+    if let Some(path) = &args.tty_file {
+        let sink = TtySink::file(path).unwrap_or_else(|e| panic!("{}", e));
+        bus.tty_mut().set_sink(sink);
+    }
+
+    if let Some(path) = &args.cart {
+        let cartridge = Cartridge::open(path).unwrap_or_else(|e| panic!("{}", e));
+
+        match cartridge.pre_boot_entry() {
+            Some(entry) => println!("Cartridge pre-boot entry at {:08x}", entry),
+            None => println!("Cartridge has no license header, the BIOS won't run it"),
+        }
+
+        bus.insert_cartridge(cartridge);
+    }
+
+    if let Some(path) = &args.disc {
+        let disc = Disc::open(path).unwrap_or_else(|e| panic!("{}", e));
+        bus.insert_disc(disc);
+    }
 
     let mut cpu = CPU::new(bus);
 
+    cpu.set_strict(args.strict);
+
+    if let Some(path) = &args.exe {
+        let exe = Exe::open(path).unwrap_or_else(|e| panic!("{}", e));
+        cpu.side_load(exe);
+    }
+
+    if let Some(path) = &args.trace {
+        let tracer = Tracer::file(path, args.trace_options).unwrap_or_else(|e| panic!("{}", e));
+        cpu.set_tracer(Some(tracer));
+    }
+
+    if args.trace_kernel {
+        cpu.set_kernel_tracer(Some(KernelTracer::new(Box::new(std::io::stdout()))));
+    }
+
+    if args.dump_every.is_some() {
+        std::fs::create_dir_all(&args.dump_dir)
+            .unwrap_or_else(|e| panic!("Couldn't create {}: {}", args.dump_dir.display(), e));
+    }
+
+    let mut gdb = args
+        .gdb
+        .map(|port| GdbStub::listen(port).unwrap_or_else(|e| panic!("{}", e)));
+
+    let mut monitor = args.debug.then(|| {
+        let input = Box::new(std::io::BufReader::new(std::io::stdin()));
+        Monitor::new(input, Box::new(std::io::stdout()))
+    });
+
+    let mut frame = 0;
+    let mut failed = false;
+
     loop {
-        cpu.run_next_opcode();
+        if let Some(stub) = &mut gdb {
+            match stub.poll(&mut cpu) {
+                Status::Running => (),
+                Status::Detached => gdb = None,
+                Status::Killed => break,
+            }
+        }
+
+        if let Some(monitor) = &mut monitor {
+            if !monitor.poll(&mut cpu) {
+                break;
+            }
+        }
+
+        if let Err(e) = cpu.run_next_opcode() {
+            match (&mut monitor, args.on_error) {
+                // Drop back to the prompt, the state can still be inspected
+                (Some(monitor), _) => {
+                    println!("{}", e);
+                    monitor.halt();
+                }
+                (None, OnError::Log) => println!("{}", e),
+                (None, OnError::Halt) => {
+                    eprintln!("{}\nCPU state: {}", e, cpu);
+                    failed = true;
+                    break;
+                }
+            }
+        }
+
+        let gpu = cpu.bus().gpu();
+        if gpu.frame() == frame {
+            continue;
+        }
+
+        // A frame has been completed
+        frame = gpu.frame();
+
+        if let Some(every) = args.dump_every {
+            if frame % every == 0 {
+                let name = format!("frame_{:06}.{}", frame, args.dump_format.extension());
+                let path = args.dump_dir.join(name);
+
+                framebuffer::dump(gpu, args.dump_area, &path).unwrap_or_else(|e| panic!("{}", e));
+            }
+        }
+
+        if args.frames.is_some_and(|frames| frame >= frames) {
+            break;
+        }
+    }
+
+    if failed {
+        // Flush the trace before exiting
+        drop(cpu);
+        std::process::exit(1);
     }
 }